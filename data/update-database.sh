@@ -0,0 +1,13 @@
+#!/bin/sh
+# Refresh the bundled copy of the MIT licensed chip-8-database
+# (https://github.com/chip-8/chip-8-database), optionally at a given tag or commit
+set -e
+
+ref=${1:-master}
+base=https://raw.githubusercontent.com/chip-8/chip-8-database/$ref
+cd "$(dirname "$0")"
+
+for file in programs.json sha1-hashes.json platforms.json; do
+    curl -fsSL "$base/database/$file" -o "$file"
+done
+curl -fsSL "$base/LICENSE" -o LICENSE.chip-8-database
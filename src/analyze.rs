@@ -0,0 +1,704 @@
+//Module Todo:
+// N/A
+
+use std::{
+    collections::{btree_map::Entry, BTreeMap, BTreeSet, HashMap},
+    fmt::Write as _,
+};
+
+use crate::{
+    disasm,
+    quirks::Platform,
+    symbols::Symbols,
+    ROM_START,
+};
+
+const MAX_JUMP_TABLE: usize = 128; // Entries followed in a bnnn jump table
+const BIG_SPRITE_BYTES: usize = 32; // Dxy0 draws a 16x16 sprite on later platforms
+
+// How control leaves an instruction
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Flow {
+    Next,
+    Skip, // The next instruction, or the one after it
+    Jump(usize),
+    Call(usize),
+    Return,
+    Indirect(usize), // Bnnn, jumping somewhere from its base address
+    Halt, // A jump to itself or an exit
+    Invalid, // Not an instruction on any platform
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Instruction {
+    pub addr: usize,
+    pub opcode: u16,
+    pub len: usize,
+    pub flow: Flow,
+}
+
+// The earliest platform that runs an opcode, or None if none do
+pub fn required_platform(opcode: u16) -> Option<Platform> {
+    let nibbles = (opcode >> 12, (opcode >> 8) & 0xF, (opcode >> 4) & 0xF, opcode & 0xF);
+    match nibbles {
+        (0x0, 0x0, 0xC, _) | (0x0, 0x0, 0xF, 0xB..=0xF) => Some(Platform::SuperChip),
+        (0x0, 0x0, 0xD, _) => Some(Platform::XoChip),
+        (0x0, _, _, _) | (0x1..=0x4, _, _, _) => Some(Platform::Chip8),
+        (0x5, _, _, 0x0) => Some(Platform::Chip8),
+        (0x5, _, _, 0x2 | 0x3) => Some(Platform::XoChip),
+        (0x6 | 0x7, _, _, _) => Some(Platform::Chip8),
+        (0x8, _, _, 0x0..=0x7 | 0xE) => Some(Platform::Chip8),
+        (0x9, _, _, 0x0) => Some(Platform::Chip8),
+        (0xA..=0xD, _, _, _) => Some(Platform::Chip8),
+        (0xE, _, 0x9, 0xE) | (0xE, _, 0xA, 0x1) => Some(Platform::Chip8),
+        (0xF, 0x0, 0x0, 0x0) | (0xF, _, 0x0, 0x1) | (0xF, 0x0, 0x0, 0x2) => Some(Platform::XoChip),
+        (0xF, _, 0x0, 0x7 | 0xA) | (0xF, _, 0x1, 0x5 | 0x8 | 0xE) => Some(Platform::Chip8),
+        (0xF, _, 0x2, 0x9) | (0xF, _, 0x3, 0x3) => Some(Platform::Chip8),
+        (0xF, _, 0x5 | 0x6, 0x5) => Some(Platform::Chip8),
+        (0xF, _, 0x3, 0x0) | (0xF, _, 0x7 | 0x8, 0x5) => Some(Platform::SuperChip),
+        (0xF, _, 0x3, 0xA) => Some(Platform::XoChip),
+        _ => None,
+    }
+}
+
+// Decode the instruction at addr as the given platform would run it
+pub fn decode(mem: &[u8], addr: usize, platform: Platform) -> Instruction {
+    let opcode = disasm::opcode_at(mem, addr);
+    let nnn = (opcode & 0x0FFF) as usize;
+    let len = if opcode == 0xF000 && platform == Platform::XoChip { 4 } else { 2 };
+    let flow = match (opcode >> 12, opcode & 0x00FF) {
+        _ if required_platform(opcode).is_none() => Flow::Invalid,
+        (0x0, 0xEE) if opcode == 0x00EE => Flow::Return,
+        (0x0, 0xFD) if opcode == 0x00FD => Flow::Halt,
+        (0x1, _) if nnn == addr => Flow::Halt,
+        (0x1, _) => Flow::Jump(nnn),
+        (0x2, _) => Flow::Call(nnn),
+        (0x3 | 0x4 | 0x5 | 0x9, _) | (0xE, _) => Flow::Skip,
+        (0xB, _) => Flow::Indirect(nnn),
+        _ => Flow::Next,
+    };
+    Instruction { addr, opcode, len, flow }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EdgeKind {
+    Fallthrough,
+    Jump,
+    Skip,
+    Indirect,
+}
+
+// A run of instructions entered only at the top and left only at the bottom
+pub struct Block {
+    pub start: usize,
+    pub end: usize, // Address after the last instruction
+    pub function: usize, // Entry of the first subroutine found reaching the block
+    pub successors: Vec<(usize, EdgeKind)>,
+    pub calls: Vec<usize>,
+}
+
+// Bytes a dxyn draws from, found by the annn that set I on every path to it
+pub struct SpriteData {
+    pub start: usize,
+    pub len: usize,
+    pub drawn_at: usize,
+}
+
+pub struct Finding {
+    pub addr: usize,
+    pub message: String,
+}
+
+pub struct Analysis {
+    pub platform: Platform,
+    pub rom_end: usize,
+    pub instructions: BTreeMap<usize, Instruction>,
+    pub blocks: Vec<Block>,
+    pub call_graph: BTreeMap<usize, BTreeSet<usize>>, // Subroutine entry to the ones it calls
+    pub jump_tables: BTreeMap<usize, Vec<usize>>, // Bnnn address to the targets assumed
+    pub sprites: Vec<SpriteData>,
+    pub unreachable: Vec<(usize, usize)>, // Start and end of rom ranges never reached
+    pub findings: Vec<Finding>,
+}
+
+// Targets assumed for a bnnn: a run of jumps at nnn is taken as a jump table indexed
+// by v0, otherwise only nnn itself
+fn indirect_targets(mem: &[u8], base: usize, rom_end: usize) -> Vec<usize> {
+    let targets: Vec<usize> = (0..MAX_JUMP_TABLE)
+        .map(|entry| base + entry * 2)
+        .take_while(|&addr| addr + 1 < rom_end && disasm::opcode_at(mem, addr) >> 12 == 0x1)
+        .collect();
+    if targets.is_empty() {
+        vec![base]
+    } else {
+        targets
+    }
+}
+
+// Walk a rom loaded in mem from ROM_START, following every path the code can take
+pub fn analyze(mem: &[u8], rom_len: usize, platform: Platform) -> Analysis {
+    let rom_end = (ROM_START + rom_len).min(mem.len());
+    let in_rom = |addr: usize| (ROM_START..rom_end).contains(&addr);
+    let mut analysis = Analysis {
+        platform,
+        rom_end,
+        instructions: BTreeMap::new(),
+        blocks: Vec::new(),
+        call_graph: BTreeMap::new(),
+        jump_tables: BTreeMap::new(),
+        sprites: Vec::new(),
+        unreachable: Vec::new(),
+        findings: Vec::new(),
+    };
+
+    // Each subroutine is walked separately so blocks know which one they belong to
+    let mut function_of: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut leaders: BTreeSet<usize> = BTreeSet::from([ROM_START]);
+    let mut functions = vec![ROM_START];
+    analysis.call_graph.insert(ROM_START, BTreeSet::new());
+    while let Some(function) = functions.pop() {
+        let mut pending = vec![function];
+        while let Some(addr) = pending.pop() {
+            if !in_rom(addr) {
+                continue;
+            }
+            if function_of.contains_key(&addr) {
+                continue;
+            }
+            function_of.insert(addr, function);
+            let instruction = decode(mem, addr, platform);
+            analysis.instructions.insert(addr, instruction);
+            let next = addr + instruction.len;
+
+            // Branch targets start blocks, and so does whatever follows a branch
+            let targets = match instruction.flow {
+                Flow::Next | Flow::Call(_) => vec![next],
+                Flow::Skip => vec![next, next + decode(mem, next, platform).len],
+                Flow::Jump(target) => vec![target],
+                Flow::Indirect(base) => indirect_targets(mem, base, rom_end),
+                Flow::Return | Flow::Halt | Flow::Invalid => Vec::new(),
+            };
+            if instruction.flow != Flow::Next {
+                leaders.insert(next);
+                leaders.extend(targets.iter().copied());
+            }
+            for &target in &targets {
+                if in_rom(target) {
+                    pending.push(target);
+                } else {
+                    analysis.findings.push(Finding {
+                        addr,
+                        message: format!("Control reaches {:03X}, outside the rom", target),
+                    });
+                }
+            }
+
+            match instruction.flow {
+                Flow::Call(target) => {
+                    analysis.call_graph.entry(function).or_default().insert(target);
+                    if !in_rom(target) {
+                        analysis.findings.push(Finding {
+                            addr,
+                            message: format!("Calls {:03X}, outside the rom", target),
+                        });
+                    } else if let Entry::Vacant(entry) = analysis.call_graph.entry(target) {
+                        entry.insert(BTreeSet::new());
+                        leaders.insert(target);
+                        functions.push(target);
+                    }
+                },
+                Flow::Indirect(_) => {
+                    analysis.jump_tables.insert(addr, targets);
+                },
+                Flow::Invalid => analysis.findings.push(Finding {
+                    addr,
+                    message: format!("Reached {:04X}, which is not an instruction", instruction.opcode),
+                }),
+                _ => {},
+            }
+
+            match required_platform(instruction.opcode) {
+                Some(required) if required > platform => analysis.findings.push(Finding {
+                    addr,
+                    message: format!("{:04X} needs {}", instruction.opcode, required.name()),
+                }),
+                Some(Platform::Chip8) if instruction.opcode >> 12 == 0x0
+                    && !matches!(instruction.opcode, 0x00E0 | 0x00EE) => {
+                    analysis.findings.push(Finding {
+                        addr,
+                        message: format!("{:04X} calls machine code, which only the COSMAC VIP ran",
+                            instruction.opcode),
+                    });
+                },
+                _ => {},
+            }
+        }
+    }
+
+    build_blocks(&mut analysis, &leaders, &function_of);
+    find_sprites(&mut analysis);
+    find_unreachable(&mut analysis);
+    analysis.findings.sort_by_key(|finding| finding.addr);
+    analysis
+}
+
+// Split the reached instructions into blocks at every leader and after every branch
+fn build_blocks(analysis: &mut Analysis, leaders: &BTreeSet<usize>,
+    function_of: &BTreeMap<usize, usize>) {
+    let mut current: Option<Block> = None;
+    for (&addr, instruction) in &analysis.instructions {
+        let continues = current.as_ref().is_some_and(|block| block.end == addr) && !leaders.contains(&addr);
+        if !continues {
+            if let Some(block) = current.take() {
+                analysis.blocks.push(block);
+            }
+            current = Some(Block {
+                start: addr,
+                end: addr,
+                function: function_of[&addr],
+                successors: Vec::new(),
+                calls: Vec::new(),
+            });
+        }
+        let block = current.as_mut().expect("block started above");
+        block.end = addr + instruction.len;
+
+        let next = block.end;
+        match instruction.flow {
+            Flow::Next => {
+                // The block only ends here if the next instruction leads another block
+                if leaders.contains(&next) || !analysis.instructions.contains_key(&next) {
+                    block.successors.push((next, EdgeKind::Fallthrough));
+                }
+                continue;
+            },
+            Flow::Skip => {
+                let after = next + analysis.instructions.get(&next).map_or(2, |next| next.len);
+                block.successors.push((next, EdgeKind::Fallthrough));
+                block.successors.push((after, EdgeKind::Skip));
+            },
+            Flow::Jump(target) => block.successors.push((target, EdgeKind::Jump)),
+            Flow::Call(target) => {
+                block.calls.push(target);
+                block.successors.push((next, EdgeKind::Fallthrough));
+            },
+            Flow::Indirect(_) => {
+                for &target in &analysis.jump_tables[&addr] {
+                    block.successors.push((target, EdgeKind::Indirect));
+                }
+            },
+            Flow::Return | Flow::Halt | Flow::Invalid => {},
+        }
+        analysis.blocks.push(current.take().expect("block started above"));
+    }
+    if let Some(block) = current {
+        analysis.blocks.push(block);
+    }
+    for block in &mut analysis.blocks {
+        block.successors.retain(|(target, _)| (ROM_START..analysis.rom_end).contains(target));
+    }
+}
+
+// Look for annn then dxyn in each block, with nothing in between changing i
+// The value of I after an instruction, None once it is no longer a known address
+fn track_i(i: Option<usize>, opcode: u16) -> Option<usize> {
+    match opcode >> 12 {
+        0xA => Some((opcode & 0x0FFF) as usize),
+        0xF if matches!(opcode & 0xFF, 0x1E | 0x29 | 0x30 | 0x55 | 0x65) || opcode == 0xF000 => None,
+        _ => i,
+    }
+}
+
+// Subroutines that may change I themselves or through the ones they call
+fn subroutines_changing_i(analysis: &Analysis) -> BTreeSet<usize> {
+    let mut changing: BTreeSet<usize> = analysis.blocks.iter()
+        .filter(|block| analysis.instructions.range(block.start..block.end)
+            .any(|(_, instruction)| track_i(Some(0), instruction.opcode) != Some(0)))
+        .map(|block| block.function)
+        .collect();
+    loop {
+        let callers: Vec<usize> = analysis.call_graph.iter()
+            .filter(|(entry, callees)| !changing.contains(entry)
+                && callees.iter().any(|callee| changing.contains(callee)))
+            .map(|(&entry, _)| entry)
+            .collect();
+        if callers.is_empty() {
+            return changing;
+        }
+        changing.extend(callers);
+    }
+}
+
+// I at the top of each block, known only when every path into it loads the same address
+// Subroutine entries start unknown, as does the instruction after a call that may change I
+fn block_entry_i(analysis: &Analysis) -> HashMap<usize, Option<usize>> {
+    let changing = subroutines_changing_i(analysis);
+    let blocks: HashMap<usize, &Block> = analysis.blocks.iter().map(|block| (block.start, block)).collect();
+    let mut entry: HashMap<usize, Option<usize>> = analysis.call_graph.keys()
+        .map(|&start| (start, None))
+        .collect();
+    let mut worklist: Vec<usize> = entry.keys().copied().collect();
+    while let Some(start) = worklist.pop() {
+        let Some(block) = blocks.get(&start) else { continue };
+        let i = analysis.instructions.range(block.start..block.end)
+            .fold(entry[&start], |i, (_, instruction)| track_i(i, instruction.opcode));
+        let calls_change_i = block.calls.iter().any(|callee| changing.contains(callee));
+        let i = if calls_change_i { None } else { i };
+        for &(target, _) in &block.successors {
+            let merged = match entry.get(&target) {
+                None => i,
+                Some(&known) if known == i => continue,
+                Some(_) => None,
+            };
+            if entry.get(&target) != Some(&merged) {
+                entry.insert(target, merged);
+                worklist.push(target);
+            }
+        }
+    }
+    entry
+}
+
+// Sprites are the bytes at a known I when a dxyn draws
+fn find_sprites(analysis: &mut Analysis) {
+    let entry_i = block_entry_i(analysis);
+    for block in &analysis.blocks {
+        let mut i = entry_i.get(&block.start).copied().flatten();
+        for instruction in analysis.instructions.range(block.start..block.end).map(|(_, ins)| ins) {
+            let opcode = instruction.opcode;
+            if let (0xD, Some(start)) = (opcode >> 12, i) {
+                let len = match (opcode & 0xF) as usize {
+                    0 if analysis.platform > Platform::Chip8 => BIG_SPRITE_BYTES,
+                    n => n,
+                };
+                if len > 0 {
+                    analysis.sprites.push(SpriteData { start, len, drawn_at: instruction.addr });
+                }
+            }
+            i = track_i(i, opcode);
+        }
+    }
+    analysis.sprites.sort_by_key(|sprite| (sprite.start, sprite.drawn_at));
+    analysis.sprites.dedup_by_key(|sprite| (sprite.start, sprite.len));
+
+    for sprite in &analysis.sprites {
+        let overlaps_code = (sprite.start..sprite.start + sprite.len)
+            .any(|addr| analysis.instructions.contains_key(&addr));
+        if overlaps_code {
+            analysis.findings.push(Finding {
+                addr: sprite.drawn_at,
+                message: format!("Draws a sprite from {:03X}, which also runs as code", sprite.start),
+            });
+        }
+    }
+}
+
+// Rom bytes that are neither reached as code nor drawn as sprites
+fn find_unreachable(analysis: &mut Analysis) {
+    let rom_end = analysis.rom_end;
+    let mut used = vec![false; rom_end];
+    let spans = analysis.instructions.values().map(|instruction| (instruction.addr, instruction.len))
+        .chain(analysis.sprites.iter().map(|sprite| (sprite.start, sprite.len)));
+    for (start, len) in spans {
+        let end = (start + len).min(rom_end);
+        if start < end {
+            used[start..end].fill(true);
+        }
+    }
+
+    let mut start = None;
+    for addr in ROM_START..=rom_end {
+        let unused = used.get(addr).is_some_and(|used| !used);
+        match (unused, start) {
+            (true, None) => start = Some(addr),
+            (false, Some(first)) => {
+                analysis.unreachable.push((first, addr));
+                start = None;
+            },
+            _ => {},
+        }
+    }
+}
+
+impl Analysis {
+    fn function_name(&self, entry: usize, symbols: &Symbols) -> String {
+        match symbols.label(entry) {
+            Some(label) => label.to_string(),
+            None if entry == ROM_START => String::from("main"),
+            None => format!("sub_{:03X}", entry),
+        }
+    }
+
+    fn block_lines(&self, block: &Block, symbols: &Symbols) -> Vec<String> {
+        self.instructions.range(block.start..block.end)
+            .map(|(addr, instruction)| format!("{:03X}  {:04X}  {}", addr, instruction.opcode,
+                disasm::disassemble_with(instruction.opcode, symbols)))
+            .collect()
+    }
+
+    pub fn report(&self, symbols: &Symbols) -> String {
+        let mut text = format!("Platform {}, rom {:03X}-{:03X}\n", self.platform.name(), ROM_START,
+            self.rom_end);
+
+        let _ = writeln!(text, "\nBasic blocks ({})", self.blocks.len());
+        for block in &self.blocks {
+            let successors: Vec<String> = block.successors.iter()
+                .map(|(target, kind)| match kind {
+                    EdgeKind::Fallthrough => format!("{:03X}", target),
+                    EdgeKind::Jump => format!("jump {:03X}", target),
+                    EdgeKind::Skip => format!("skip {:03X}", target),
+                    EdgeKind::Indirect => format!("indirect {:03X}", target),
+                })
+                .chain(block.calls.iter().map(|target| format!("call {}", self.function_name(*target, symbols))))
+                .collect();
+            let successors = if successors.is_empty() { String::from("-") } else { successors.join(", ") };
+            let _ = writeln!(text, "  {:03X}-{:03X}  {:<12} -> {}", block.start, block.end - 1,
+                self.function_name(block.function, symbols), successors);
+        }
+
+        let _ = writeln!(text, "\nCall graph");
+        for (entry, callees) in &self.call_graph {
+            let callees: Vec<String> = callees.iter().map(|callee| self.function_name(*callee, symbols)).collect();
+            let _ = writeln!(text, "  {} ({:03X}) -> {}", self.function_name(*entry, symbols), entry,
+                if callees.is_empty() { String::from("-") } else { callees.join(", ") });
+        }
+
+        if !self.jump_tables.is_empty() {
+            let _ = writeln!(text, "\nIndirect jumps");
+            for (addr, targets) in &self.jump_tables {
+                let targets: Vec<String> = targets.iter().map(|target| format!("{:03X}", target)).collect();
+                let _ = writeln!(text, "  {:03X}  {}", addr, targets.join(", "));
+            }
+        }
+
+        let _ = writeln!(text, "\nLikely sprite data");
+        if self.sprites.is_empty() {
+            text.push_str("  none\n");
+        }
+        for sprite in &self.sprites {
+            let _ = writeln!(text, "  {:03X}-{:03X}  {:>2} bytes, drawn at {:03X}", sprite.start,
+                sprite.start + sprite.len - 1, sprite.len, sprite.drawn_at);
+        }
+
+        let _ = writeln!(text, "\nUnreachable");
+        if self.unreachable.is_empty() {
+            text.push_str("  none\n");
+        }
+        for (start, end) in &self.unreachable {
+            let _ = writeln!(text, "  {:03X}-{:03X}  {:>4} bytes", start, end - 1, end - start);
+        }
+
+        let _ = writeln!(text, "\nFindings");
+        if self.findings.is_empty() {
+            text.push_str("  none\n");
+        }
+        for finding in &self.findings {
+            let _ = writeln!(text, "  {:03X}  {}", finding.addr, finding.message);
+        }
+        text
+    }
+
+    // Graphviz source with one cluster per subroutine, calls drawn dashed
+    pub fn dot(&self, symbols: &Symbols) -> String {
+        let mut dot = String::from("digraph rom {\n    node [shape=box fontname=\"monospace\"];\n");
+        for entry in self.call_graph.keys() {
+            let _ = writeln!(dot, "    subgraph cluster_{:03X} {{\n        label=\"{}\";", entry,
+                self.function_name(*entry, symbols));
+            for block in self.blocks.iter().filter(|block| block.function == *entry) {
+                let label: String = self.block_lines(block, symbols).iter()
+                    .map(|line| format!("{}\\l", line.replace('"', "\\\"")))
+                    .collect();
+                let _ = writeln!(dot, "        b{:03X} [label=\"{}\"];", block.start, label);
+            }
+            dot.push_str("    }\n");
+        }
+
+        for block in &self.blocks {
+            for (target, kind) in &block.successors {
+                let style = match kind {
+                    EdgeKind::Fallthrough => "",
+                    EdgeKind::Jump => " [label=\"jump\"]",
+                    EdgeKind::Skip => " [label=\"skip\"]",
+                    EdgeKind::Indirect => " [label=\"indirect\" style=dotted]",
+                };
+                let _ = writeln!(dot, "    b{:03X} -> b{:03X}{};", block.start, target, style);
+            }
+            for target in &block.calls {
+                if self.instructions.contains_key(target) {
+                    let _ = writeln!(dot, "    b{:03X} -> b{:03X} [style=dashed label=\"call\"];",
+                        block.start, target);
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Main skips a call to 210 and calls 216, which 210 calls too
+    const CALLS: [u8; 24] = [
+        0x60, 0x01, // 200 v0 := 1
+        0x30, 0x01, // 202 skip if v0 == 1
+        0x22, 0x10, // 204 call 210
+        0x22, 0x16, // 206 call 216
+        0x12, 0x08, // 208 jump 208
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // 20A never reached
+        0x22, 0x16, // 210 call 216
+        0x00, 0xEE, // 212 return
+        0x00, 0x00, // 214 never reached
+        0x00, 0xEE, // 216 return
+    ];
+
+    fn analyzed(rom: &[u8], platform: Platform) -> Analysis {
+        let mut mem = vec![0; 0x1000];
+        mem[ROM_START..ROM_START + rom.len()].copy_from_slice(rom);
+        analyze(&mem, rom.len(), platform)
+    }
+
+    // Start, end, function and successors of each block
+    type BlockSummary = (usize, usize, usize, Vec<(usize, EdgeKind)>);
+
+    fn blocks(analysis: &Analysis) -> Vec<BlockSummary> {
+        analysis.blocks.iter()
+            .map(|block| (block.start, block.end, block.function, block.successors.clone()))
+            .collect()
+    }
+
+    fn findings(analysis: &Analysis) -> Vec<(usize, &str)> {
+        analysis.findings.iter().map(|finding| (finding.addr, finding.message.as_str())).collect()
+    }
+
+    #[test]
+    fn blocks_split_at_jumps_calls_returns_and_skips() {
+        use EdgeKind::*;
+        let analysis = analyzed(&CALLS, Platform::Chip8);
+        assert_eq!(blocks(&analysis), [
+            (0x200, 0x204, 0x200, vec![(0x204, Fallthrough), (0x206, Skip)]),
+            (0x204, 0x206, 0x200, vec![(0x206, Fallthrough)]),
+            (0x206, 0x208, 0x200, vec![(0x208, Fallthrough)]),
+            (0x208, 0x20A, 0x200, vec![]),
+            (0x210, 0x212, 0x210, vec![(0x212, Fallthrough)]),
+            (0x212, 0x214, 0x210, vec![]),
+            (0x216, 0x218, 0x216, vec![]),
+        ]);
+        let calls: Vec<&[usize]> = analysis.blocks.iter().map(|block| &block.calls[..]).collect();
+        assert_eq!(calls, [&[][..], &[0x210], &[0x216], &[], &[0x216], &[], &[]]);
+        assert_eq!(analysis.instructions[&0x208].flow, Flow::Halt);
+        assert!(findings(&analysis).is_empty());
+    }
+
+    #[test]
+    fn call_graph_links_each_subroutine_to_its_callees() {
+        let analysis = analyzed(&CALLS, Platform::Chip8);
+        let graph: Vec<(usize, Vec<usize>)> = analysis.call_graph.iter()
+            .map(|(entry, callees)| (*entry, callees.iter().copied().collect()))
+            .collect();
+        assert_eq!(graph, [(0x200, vec![0x210, 0x216]), (0x210, vec![0x216]), (0x216, vec![])]);
+    }
+
+    #[test]
+    fn unreached_bytes_are_reported_as_ranges() {
+        let analysis = analyzed(&CALLS, Platform::Chip8);
+        assert_eq!(analysis.unreachable, [(0x20A, 0x210), (0x214, 0x216)]);
+    }
+
+    #[test]
+    fn bnnn_follows_a_jump_table_at_its_base() {
+        let rom = [
+            0xB2, 0x06, // 200 jump0 206
+            0x00, 0x00, 0x00, 0x00, // 202 never reached
+            0x12, 0x0C, // 206 jump 20C, the jump table
+            0x12, 0x0E, // 208 jump 20E
+            0x60, 0x00, // 20A v0 := 0, ends the table
+            0x12, 0x0C, // 20C jump 20C
+            0x12, 0x0E, // 20E jump 20E
+        ];
+        let analysis = analyzed(&rom, Platform::Chip8);
+        assert_eq!(analysis.jump_tables[&0x200], [0x206, 0x208]);
+        let (_, _, _, successors) = &blocks(&analysis)[0];
+        assert_eq!(successors, &[(0x206, EdgeKind::Indirect), (0x208, EdgeKind::Indirect)]);
+        assert!(!analysis.instructions.contains_key(&0x20A));
+
+        // Without jumps at the base, only the base itself is assumed
+        let analysis = analyzed(&[0xB2, 0x04, 0x12, 0x02, 0x60, 0x01, 0x12, 0x06], Platform::Chip8);
+        assert_eq!(analysis.jump_tables[&0x200], [0x204]);
+        assert!(analysis.instructions.contains_key(&0x206));
+    }
+
+    #[test]
+    fn annn_then_dxyn_marks_sprite_data() {
+        let rom = [
+            0xA2, 0x0A, // 200 i := 20A
+            0x60, 0x00, // 202 v0 := 0
+            0xD0, 0x05, // 204 sprite v0 v0 5
+            0x12, 0x06, // 206 jump 206
+            0x00, 0x00, // 208 never reached
+            0xF0, 0x90, 0x90, 0x90, 0xF0, // 20A a zero
+            0x00, // 20F never reached
+        ];
+        let analysis = analyzed(&rom, Platform::Chip8);
+        let sprites: Vec<(usize, usize, usize)> = analysis.sprites.iter()
+            .map(|sprite| (sprite.start, sprite.len, sprite.drawn_at))
+            .collect();
+        assert_eq!(sprites, [(0x20A, 5, 0x204)]);
+        assert_eq!(analysis.unreachable, [(0x208, 0x20A), (0x20F, 0x210)]);
+    }
+
+    #[test]
+    fn sprites_need_the_same_i_on_every_path() {
+        let rom = [
+            0xA2, 0x0C, // 200 i := 20C
+            0x30, 0x00, // 202 skip if v0 == 0
+            0xA2, 0x0E, // 204 i := 20E
+            0xD0, 0x01, // 206 sprite v0 v0 1, from either address
+            0xF0, 0x1E, // 208 i += v0
+            0xD0, 0x01, // 20A sprite v0 v0 1, from an unknown address
+        ];
+        let analysis = analyzed(&rom, Platform::Chip8);
+        assert!(analysis.sprites.is_empty());
+    }
+
+    #[test]
+    fn opcodes_beyond_the_platform_are_reported() {
+        let rom = [
+            0x00, 0xFF, // 200 hires, SUPER-CHIP
+            0xF0, 0x02, // 202 audio, XO-CHIP
+            0x01, 0x23, // 204 machine code
+            0x51, 0x21, // 206 not an instruction
+        ];
+        let analysis = analyzed(&rom, Platform::Chip8);
+        assert_eq!(findings(&analysis), [
+            (0x200, "00FF needs SUPER-CHIP"),
+            (0x202, "F002 needs XO-CHIP"),
+            (0x204, "0123 calls machine code, which only the COSMAC VIP ran"),
+            (0x206, "Reached 5121, which is not an instruction"),
+        ]);
+
+        let analysis = analyzed(&rom, Platform::XoChip);
+        let addrs: Vec<usize> = analysis.findings.iter().map(|finding| finding.addr).collect();
+        assert_eq!(addrs, [0x204, 0x206]);
+    }
+
+    #[test]
+    fn dot_output_has_a_cluster_per_subroutine() {
+        let dot = analyzed(&CALLS, Platform::Chip8).dot(&Symbols::default());
+        assert!(dot.starts_with("digraph rom {\n"));
+        assert!(dot.ends_with(";\n}\n"));
+        assert_eq!(dot.matches('{').count(), dot.matches('}').count());
+        assert_eq!(dot.matches("subgraph cluster_").count(), 3);
+        for line in [
+            "    subgraph cluster_200 {\n        label=\"main\";\n",
+            "    subgraph cluster_216 {\n        label=\"sub_216\";\n",
+            "        b200 [label=\"200  6001  LD V0, #01\\l202  3001  SE V0, #01\\l\"];\n",
+            "    b200 -> b204;\n",
+            "    b200 -> b206 [label=\"skip\"];\n",
+            "    b204 -> b210 [style=dashed label=\"call\"];\n",
+        ] {
+            assert!(dot.contains(line), "missing {:?} in\n{}", line, dot);
+        }
+    }
+}
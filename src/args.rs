@@ -0,0 +1,290 @@
+//Module Todo:
+// N/A
+
+use std::env;
+use crate::{
+    config::Settings,
+    debugger,
+    diff::KeyPress,
+    frame::RenderMode,
+    quirks::Quirks,
+    scheduler::{self, Speed},
+    smc::SmcMode,
+    trace::{self, TraceFilter},
+};
+
+// Command line flags, each overriding the matching setting when given
+pub struct Args {
+    pub rom_path: Option<String>,
+    pub config_path: Option<String>, // Used instead of the user config file
+    pub print_config: bool,
+    pub render_mode: Option<RenderMode>,
+    pub quirk_overrides: Vec<(String, bool)>, // chip-8-database quirk names
+    pub record_path: Option<String>,
+    pub headless_frames: Option<u64>,
+    pub diff_frames: Option<u64>, // Run twice in lockstep, the second run with diff_quirk_overrides
+    pub diff_quirk_overrides: Vec<(String, bool)>,
+    pub diff_keys: Vec<KeyPress>, // Keys pressed in both lockstep runs
+    pub diff_traces: Option<(String, String)>,
+    pub lint: bool, // Report platform compatibility problems, tracing a headless run if given
+    pub tui: bool, // Run the terminal debugger instead of the sdl window
+    pub braille: bool, // Draw the tui framebuffer with braille instead of half blocks
+    pub clock_hz: Option<u32>, // Instructions per second
+    pub vsync: bool,
+    pub show_stats: bool, // FPS and instructions per second on the osd
+    pub debugger: bool, // Show debugger panels beside the game
+    pub breakpoints: Vec<String>, // Labels or hex addresses, resolved once symbols are loaded
+    pub symbols_path: Option<String>, // Used instead of the .sym file beside the rom
+    pub gdb_port: Option<u16>, // Serve the gdb remote protocol on this localhost port
+    pub trace_path: Option<String>,
+    pub trace_filter: TraceFilter,
+    pub profile_prefix: Option<String>, // Profiler reports go to PREFIX.txt, .json and .folded
+    pub coverage_prefix: Option<String>, // Coverage reports go to PREFIX.asm, .info and .png
+    pub smc_mode: Option<SmcMode>, // Check for self modifying code and executed data
+    pub fast_forward: Option<Speed>, // Speed while the fast forward key is held
+    pub watch: bool, // Reload the rom when the file changes
+    pub use_database: Option<bool>,
+    pub database_dir: Option<String>, // chip-8-database checkout used instead of the bundled copy
+    pub overrides_path: Option<String>, // User per rom settings
+}
+
+impl Args {
+    // Parse command line flags, panicking on anything unknown
+    pub fn parse() -> Self {
+        Self::parse_from(env::args().skip(1))
+    }
+
+    pub fn parse_from(flags: impl IntoIterator<Item = String>) -> Self {
+        let mut args = Self {
+            rom_path: None,
+            config_path: None,
+            print_config: false,
+            render_mode: None,
+            quirk_overrides: Vec::new(),
+            record_path: None,
+            headless_frames: None,
+            diff_frames: None,
+            diff_quirk_overrides: Vec::new(),
+            diff_keys: Vec::new(),
+            diff_traces: None,
+            lint: false,
+            tui: false,
+            braille: false,
+            clock_hz: None,
+            vsync: false,
+            show_stats: false,
+            debugger: false,
+            breakpoints: Vec::new(),
+            symbols_path: None,
+            gdb_port: None,
+            trace_path: None,
+            trace_filter: TraceFilter::default(),
+            profile_prefix: None,
+            coverage_prefix: None,
+            smc_mode: None,
+            fast_forward: None,
+            watch: false,
+            use_database: None,
+            database_dir: None,
+            overrides_path: None,
+        };
+
+        let mut iter = flags.into_iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--config" => {
+                    args.config_path = Some(iter.next()
+                        .expect("--config requires a toml file"));
+                },
+                "--print-config" => args.print_config = true,
+                "--render" => {
+                    let mode = iter.next()
+                        .expect("--render requires a mode (normal, phosphor[:N], blend, vblank)");
+                    args.render_mode = Some(RenderMode::from_arg(&mode)
+                        .unwrap_or_else(|| panic!("Unknown render mode {}", mode)));
+                },
+                "--display-wait" => args.quirk_overrides.push((String::from("vblank"), true)),
+                "--wrap-sprites" => args.quirk_overrides.push((String::from("wrap"), true)),
+                "--quirk" => {
+                    let quirk = iter.next()
+                        .expect("--quirk requires name=on or name=off");
+                    args.quirk_overrides.push(parse_quirk(&quirk));
+                },
+                "--record" => {
+                    args.record_path = Some(iter.next()
+                        .expect("--record requires a .gif or .y4m path, or - for stdout"));
+                },
+                "--headless" => {
+                    let frames = iter.next()
+                        .expect("--headless requires a frame count");
+                    args.headless_frames = Some(frames.parse()
+                        .unwrap_or_else(|_| panic!("Invalid frame count {}", frames)));
+                },
+                "--gdb" => {
+                    let port = iter.next()
+                        .expect("--gdb requires a tcp port");
+                    args.gdb_port = Some(port.parse()
+                        .unwrap_or_else(|_| panic!("Invalid gdb port {}", port)));
+                },
+                "--diff" => {
+                    let frames = iter.next()
+                        .expect("--diff requires a frame count");
+                    args.diff_frames = Some(frames.parse()
+                        .unwrap_or_else(|_| panic!("Invalid diff frame count {}", frames)));
+                },
+                "--diff-quirk" => {
+                    let quirk = iter.next()
+                        .expect("--diff-quirk requires name=on or name=off");
+                    args.diff_quirk_overrides.push(parse_quirk(&quirk));
+                },
+                "--diff-key" => {
+                    let key = iter.next()
+                        .expect("--diff-key requires KEY@FRAME or KEY@FIRST-LAST");
+                    args.diff_keys.push(KeyPress::parse(&key).unwrap_or_else(|| {
+                        panic!("Invalid diff key {}, use KEY@FRAME or KEY@FIRST-LAST", key)
+                    }));
+                },
+                "--diff-traces" => {
+                    let a = iter.next().expect("--diff-traces requires two trace files");
+                    let b = iter.next().expect("--diff-traces requires two trace files");
+                    args.diff_traces = Some((a, b));
+                },
+                "--lint" => args.lint = true,
+                "--tui" => args.tui = true,
+                "--braille" => args.braille = true,
+                "--clock" => {
+                    let hz = iter.next()
+                        .expect("--clock requires a rate in instructions per second");
+                    args.clock_hz = Some(hz.parse().ok()
+                        .filter(|hz| scheduler::valid_clock_hz(*hz))
+                        .unwrap_or_else(|| panic!("Invalid clock rate {}, expected 1 to {}",
+                            hz, scheduler::MAX_CLOCK_HZ)));
+                },
+                "--vsync" => args.vsync = true,
+                "--show-stats" => args.show_stats = true,
+                "--debugger" => args.debugger = true,
+                "--break" => {
+                    args.breakpoints.push(iter.next()
+                        .expect("--break requires a label or hex address"));
+                },
+                "--symbols" => {
+                    args.symbols_path = Some(iter.next()
+                        .expect("--symbols requires a symbol file"));
+                },
+                "--trace" => {
+                    args.trace_path = Some(iter.next()
+                        .expect("--trace requires a .txt or .bin path, or - for stdout"));
+                },
+                "--trace-range" => {
+                    let range = iter.next()
+                        .expect("--trace-range requires a hex range START-END");
+                    args.trace_filter.range = Some(trace::parse_range(&range)
+                        .unwrap_or_else(|| panic!("Invalid trace range {}", range)));
+                },
+                "--trace-class" => {
+                    let classes = iter.next()
+                        .expect("--trace-class requires opcode classes such as 8,D,F");
+                    args.trace_filter.classes = trace::parse_classes(&classes)
+                        .unwrap_or_else(|| panic!("Invalid trace classes {}", classes));
+                },
+                "--trace-start" | "--trace-stop" => {
+                    let addr = iter.next()
+                        .unwrap_or_else(|| panic!("{} requires a hex address", arg));
+                    let addr = Some(debugger::parse_address(&addr)
+                        .unwrap_or_else(|| panic!("Invalid trace trigger address {}", addr)));
+                    if arg == "--trace-start" {
+                        args.trace_filter.start = addr;
+                    } else {
+                        args.trace_filter.stop = addr;
+                    }
+                },
+                "--profile" => {
+                    args.profile_prefix = Some(iter.next()
+                        .expect("--profile requires a report path prefix"));
+                },
+                "--coverage" => {
+                    args.coverage_prefix = Some(iter.next()
+                        .expect("--coverage requires a report path prefix"));
+                },
+                "--smc" => {
+                    let mode = iter.next()
+                        .expect("--smc requires warn or break");
+                    args.smc_mode = Some(SmcMode::from_arg(&mode)
+                        .unwrap_or_else(|| panic!("Unknown self modifying code mode {}", mode)));
+                },
+                "--watch" => args.watch = true,
+                "--no-database" => args.use_database = Some(false),
+                "--database" => {
+                    args.database_dir = Some(iter.next()
+                        .expect("--database requires a chip-8-database directory"));
+                },
+                "--overrides" => {
+                    args.overrides_path = Some(iter.next()
+                        .expect("--overrides requires a json file"));
+                },
+                "--fast-forward" => {
+                    let scale = iter.next()
+                        .expect("--fast-forward requires a speed multiple, or 0 for uncapped");
+                    args.fast_forward = Some(scale.parse().ok()
+                        .and_then(Speed::from_scale)
+                        .unwrap_or_else(|| panic!("Invalid fast forward speed {}, expected 0 or {} to {}",
+                            scale, scheduler::MIN_SPEED_SCALE, scheduler::MAX_SPEED_SCALE)));
+                },
+                _ if arg.starts_with("--") => panic!("Unknown argument {}", arg),
+                _ => args.rom_path = Some(arg),
+            }
+        }
+
+        args
+    }
+
+    // Layer the flags that were given over the current settings
+    pub fn apply(&self, settings: &mut Settings) {
+        if let Some(rom_path) = &self.rom_path {
+            settings.rom_path = rom_path.clone();
+        }
+        if let Some(render_mode) = self.render_mode {
+            settings.render_mode = render_mode;
+        }
+        for (name, value) in &self.quirk_overrides {
+            settings.quirks.set(name, *value);
+        }
+        if let Some(clock_hz) = self.clock_hz {
+            settings.clock_hz = clock_hz;
+        }
+        if self.vsync {
+            settings.vsync = true;
+        }
+        if self.show_stats {
+            settings.show_stats = true;
+        }
+        if self.debugger {
+            settings.debugger = true;
+        }
+        if let Some(fast_forward) = self.fast_forward {
+            settings.fast_forward = fast_forward;
+        }
+        if let Some(use_database) = self.use_database {
+            settings.use_database = use_database;
+        }
+        if self.database_dir.is_some() {
+            settings.database_dir = self.database_dir.clone();
+        }
+        if self.overrides_path.is_some() {
+            settings.overrides_path = self.overrides_path.clone();
+        }
+    }
+}
+
+// Parse a quirk override given as name=on or name=off
+fn parse_quirk(quirk: &str) -> (String, bool) {
+    let (name, value) = match quirk.split_once('=') {
+        Some((name, "on")) => (name, true),
+        Some((name, "off")) => (name, false),
+        _ => panic!("Invalid quirk {}, use name=on or name=off", quirk),
+    };
+    if !Quirks::new().set(name, value) {
+        panic!("Unknown quirk {}", name);
+    }
+    (String::from(name), value)
+}
@@ -0,0 +1,76 @@
+//Module Todo:
+// N/A
+
+// Static analysis of a rom: basic blocks, call graph, sprite data and findings
+// Usage: chip8-analyze ROM [--platform chip8|schip|xochip] [--symbols FILE] [--dot FILE]
+
+use std::{
+    env,
+    fs,
+    path::Path,
+    process,
+};
+
+use chip_8::{
+    analyze,
+    cartridge::Cartridge,
+    quirks::Platform,
+    ram::Ram,
+    symbols::{self, Symbols},
+};
+
+fn main() {
+    let mut rom_path = None;
+    let mut platform = None;
+    let mut symbols_path = None;
+    let mut dot_path = None;
+
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--platform" => {
+                let name = iter.next()
+                    .expect("--platform requires chip8, schip or xochip");
+                platform = Some(Platform::from_arg(&name)
+                    .unwrap_or_else(|| panic!("Unknown platform {}", name)));
+            },
+            "--symbols" => symbols_path = Some(iter.next().expect("--symbols requires a symbol file")),
+            "--dot" => dot_path = Some(iter.next().expect("--dot requires an output path")),
+            _ if arg.starts_with("--") => panic!("Unknown argument {}", arg),
+            _ => rom_path = Some(arg),
+        }
+    }
+    let Some(rom_path) = rom_path else {
+        eprintln!("Usage: chip8-analyze ROM [--platform chip8|schip|xochip] [--symbols FILE] [--dot FILE]");
+        process::exit(2);
+    };
+
+    let mut ram = Ram::new();
+    let mut cartridge = Cartridge::new();
+    if let Err(e) = cartridge.load_rom(&mut ram, &rom_path) {
+        eprintln!("Failed to load {}: {}", rom_path, e);
+        process::exit(1);
+    }
+
+    let symbols_path = symbols_path.map(Into::into).or_else(|| symbols::default_path(&rom_path));
+    let symbols = match symbols_path {
+        Some(path) => Symbols::load(&path).unwrap_or_else(|e| {
+            eprintln!("Failed to load symbols {}: {}", path.display(), e);
+            process::exit(1);
+        }),
+        None => Symbols::default(),
+    };
+
+    // The platform follows the rom's extension unless given
+    let platform = platform.unwrap_or(cartridge.platform);
+    let analysis = analyze::analyze(&ram.mem, cartridge.len(), platform);
+    print!("{}", analysis.report(&symbols));
+
+    if let Some(dot_path) = dot_path {
+        if let Err(e) = fs::write(Path::new(&dot_path), analysis.dot(&symbols)) {
+            eprintln!("Failed to write {}: {}", dot_path, e);
+            process::exit(1);
+        }
+        eprintln!("Control flow graph written to {}", dot_path);
+    }
+}
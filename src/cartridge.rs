@@ -1,28 +1,608 @@
 //Module Todo:
 // Remove ram.mem[0x1FF] = 1; once done with test rom suite
-// Improve rom load so you can specify a path to load from
 // Change module/struct name to Game?
 
-use std::fs;
-use crate::{ram::Ram, ROM_START};
+use std::{
+    collections::HashMap,
+    fmt,
+    fs,
+    io::{self, Cursor, Read},
+    path::Path,
+};
+
+use serde_json::Value;
+
+use crate::{
+    ram::Ram,
+    quirks::Platform,
+    database::GameSettings,
+    palette::Palette,
+    octo,
+    screenshot,
+    ROM_START,
+};
+
+const MAX_ZIP_ENTRY: u64 = 0x100000; // Room for hex text or a cart of the largest rom
+
+// Reasons a rom can fail to load
+#[derive(Debug)]
+pub enum RomError {
+    Io(io::Error),
+    Empty,
+    TooLarge { size: usize, max: usize, platform: Platform },
+    ExceedsRam { size: usize, max: usize }, // Fits the platform but not this emulator's ram
+    InvalidHex { line: usize, reason: String },
+    Zip(String),
+    OctoCart(String),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::Io(e) => write!(f, "{}", e),
+            RomError::Empty => write!(f, "rom contains no program data"),
+            RomError::TooLarge { size, max, platform } => write!(f,
+                "rom is {} bytes but only {} bytes fit in {} memory after {:#X}",
+                size, max, platform.name(), ROM_START),
+            RomError::ExceedsRam { size, max } => write!(f,
+                "rom is {} bytes but this emulator only has room for {} bytes after {:#X}",
+                size, max, ROM_START),
+            RomError::InvalidHex { line, reason } => write!(f,
+                "invalid hex on line {}: {}", line, reason),
+            RomError::Zip(reason) => write!(f, "invalid zip archive: {}", reason),
+            RomError::OctoCart(reason) => write!(f, "invalid Octo cartridge: {}", reason),
+        }
+    }
+}
+
+impl From<io::Error> for RomError {
+    fn from(e: io::Error) -> Self {
+        RomError::Io(e)
+    }
+}
+
+// Program bytes decoded from a rom file
+struct DecodedRom {
+    rom: Vec<u8>,
+    platform: Platform,
+    settings: Option<GameSettings>, // Settings embedded in the file, such as Octo options
+}
+
+impl DecodedRom {
+    fn binary(rom: Vec<u8>, platform: Platform) -> Self {
+        Self {
+            rom,
+            platform,
+            settings: None,
+        }
+    }
+}
 
 pub struct Cartridge {
     rom: Vec<u8>,
+    path: String,
+    pub platform: Platform,
+    pub embedded_settings: Option<GameSettings>,
+}
+
+impl Default for Cartridge {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Cartridge {
     pub fn new() -> Self {
         Self {
             rom: Vec::new(),
+            path: String::new(),
+            platform: Platform::Chip8,
+            embedded_settings: None,
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn len(&self) -> usize {
+        self.rom.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rom.is_empty()
+    }
+
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    // Read a rom from disk, validate it and copy it into ram
+    // The path is kept so the rom can be read again later
+    pub fn load_rom(&mut self, ram: &mut Ram, path: &str) -> Result<(), RomError> {
+        let data = fs::read(path)?;
+        let DecodedRom { rom, platform, settings } = decode_rom(path, &data)?;
+
+        // The platform's memory model sets the limit, then the rom must also fit in ram
+        let max = platform.memory_size() - ROM_START;
+        let ram_max = ram.mem.len() - ROM_START;
+        if rom.is_empty() {
+            return Err(RomError::Empty);
+        }
+        if rom.len() > max {
+            return Err(RomError::TooLarge { size: rom.len(), max, platform });
         }
+        if rom.len() > ram_max {
+            return Err(RomError::ExceedsRam { size: rom.len(), max: ram_max });
+        }
+
+        self.path = String::from(path);
+        self.rom = rom;
+        self.platform = platform;
+        self.embedded_settings = settings;
+        self.write_to_ram(ram);
+        Ok(())
     }
 
-    //At some point pass the rom file path as an input variable. Currently it's static
-    pub fn load_rom(&mut self, ram: &mut Ram) {
-        self.rom = (fs::read("rom/chip8-test-suite.ch8"))
-            .expect("No rom file or invalid path specified");
+    // Copy the loaded rom into ram starting at ROM_START
+    pub fn write_to_ram(&self, ram: &mut Ram) {
         for i in 0..self.rom.len() {
           ram.mem[ROM_START + i] = self.rom[i];
         }
     }
-}
\ No newline at end of file
+}
+
+fn extension(name: &str) -> String {
+    Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase()
+}
+
+// Turn file contents into program bytes based on the file name and contents
+// Returns the program and the platform detected from the extension
+fn decode_rom(name: &str, data: &[u8]) -> Result<DecodedRom, RomError> {
+    let extension = extension(name);
+
+    if let Some(platform) = Platform::from_extension(&extension) {
+        return Ok(DecodedRom::binary(data.to_vec(), platform));
+    }
+
+    match extension.as_str() {
+        "zip" => decode_zip(data),
+        "gif" => decode_octo_cart(name, data),
+        "hex" | "txt" => {
+            let text = std::str::from_utf8(data).map_err(|_| RomError::InvalidHex {
+                line: 0,
+                reason: String::from("file is not text"),
+            })?;
+            let rom = if text.trim_start().starts_with(':') {
+                decode_intel_hex(text)?
+            } else {
+                decode_hex_text(text)?
+            };
+            Ok(DecodedRom::binary(rom, Platform::Chip8))
+        },
+        // Unknown extensions are loaded as plain Chip 8 binaries
+        _ => Ok(DecodedRom::binary(data.to_vec(), Platform::Chip8)),
+    }
+}
+
+// Load the first rom found inside a zip archive
+fn decode_zip(data: &[u8]) -> Result<DecodedRom, RomError> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))
+        .map_err(|e| RomError::Zip(e.to_string()))?;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|e| RomError::Zip(e.to_string()))?;
+        let name = file.name().to_string();
+        let known = Platform::from_extension(&extension(&name)).is_some()
+            || matches!(extension(&name).as_str(), "hex" | "txt" | "gif");
+
+        if file.is_file() && known {
+            // Read at most one byte past the limit so a zip bomb can't exhaust memory
+            let mut contents = Vec::new();
+            (&mut file).take(MAX_ZIP_ENTRY + 1).read_to_end(&mut contents)?;
+            if contents.len() as u64 > MAX_ZIP_ENTRY {
+                return Err(RomError::Zip(format!("{} is larger than {} bytes", name, MAX_ZIP_ENTRY)));
+            }
+            return decode_rom(&name, &contents);
+        }
+    }
+
+    Err(RomError::Zip(String::from("no .ch8, .c8, .sc8, .xo8, .hex or .gif file found")))
+}
+
+// Extract the payload hidden in an Octo cartridge gif
+// Each pixel's palette index carries 2 payload bits, most significant pair first.
+// The payload is a 4 byte big endian length followed by that many bytes of json
+fn octo_cart_payload(data: &[u8]) -> Result<Value, RomError> {
+    let invalid = |reason: String| RomError::OctoCart(reason);
+
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = options.read_info(data).map_err(|e| invalid(e.to_string()))?;
+
+    let mut payload = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().map_err(|e| invalid(e.to_string()))? {
+        for pixels in frame.buffer.chunks_exact(4) {
+            payload.push(pixels.iter().fold(0, |byte, index| byte << 2 | (index & 0b11)));
+        }
+    }
+
+    if payload.len() < 4 {
+        return Err(invalid(String::from("no payload found")));
+    }
+    let size = u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]) as usize;
+    let json = payload.get(4..4 + size)
+        .ok_or_else(|| invalid(String::from("payload is truncated")))?;
+    serde_json::from_slice(json).map_err(|e| invalid(e.to_string()))
+}
+
+// Load an Octo cartridge: assemble its source and keep its options as settings
+fn decode_octo_cart(name: &str, data: &[u8]) -> Result<DecodedRom, RomError> {
+    let payload = octo_cart_payload(data)?;
+    let source = payload["program"].as_str()
+        .ok_or_else(|| RomError::OctoCart(String::from("payload has no program")))?;
+    let rom = octo::assemble(source).map_err(RomError::OctoCart)?;
+
+    let options = &payload["options"];
+    let flag = |key: &str| options[key].as_bool();
+    let colour = |key: &str| options[key].as_str().and_then(Palette::parse_colour);
+
+    // Octo quirk options mapped to chip-8-database quirk names
+    let mut quirks = HashMap::new();
+    let quirk_options = [
+        ("shiftQuirks", "shift", false),
+        ("loadStoreQuirks", "memoryLeaveIUnchanged", false),
+        ("jumpQuirks", "jump", false),
+        ("logicQuirks", "logic", false),
+        ("vBlankQuirks", "vblank", false),
+        ("clipQuirks", "wrap", true),
+    ];
+    for (option, quirk, inverted) in quirk_options {
+        if let Some(value) = flag(option) {
+            quirks.insert(String::from(quirk), value != inverted);
+        }
+    }
+
+    let xo_chip = flag("enableXO").unwrap_or(false)
+        || options["maxSize"].as_u64().is_some_and(|size| size > 3584);
+    let (platform, platform_id) = if xo_chip {
+        (Platform::XoChip, "xochip")
+    } else {
+        (Platform::Chip8, "octo")
+    };
+
+    let palette = match (colour("backgroundColor"), colour("fillColor")) {
+        (Some(background), Some(foreground)) => Some(Palette { background, foreground }),
+        _ => None,
+    };
+
+    let settings = GameSettings {
+        title: screenshot::rom_name(name).to_string(),
+        platform_id: String::from(platform_id),
+        platform: Some(platform),
+        quirks,
+        tickrate: options["tickrate"].as_u64().map(|tickrate| tickrate as u32),
+        palette,
+        keys: HashMap::new(),
+    };
+
+    Ok(DecodedRom {
+        rom,
+        platform,
+        settings: Some(settings),
+    })
+}
+
+fn parse_hex_byte(digits: &str, line: usize) -> Result<u8, RomError> {
+    u8::from_str_radix(digits, 16).map_err(|_| RomError::InvalidHex {
+        line,
+        reason: format!("{} is not a hex byte", digits),
+    })
+}
+
+// Decode Intel HEX records into a flat program
+// Addresses at or above ROM_START are treated as absolute memory addresses
+fn decode_intel_hex(text: &str) -> Result<Vec<u8>, RomError> {
+    let mut data: Vec<(usize, u8)> = Vec::new();
+    let mut base_addr = 0;
+
+    for (index, record) in text.lines().enumerate() {
+        let line = index + 1;
+        let record = record.trim();
+        if record.is_empty() {
+            continue;
+        }
+
+        let invalid = |reason: &str| RomError::InvalidHex { line, reason: String::from(reason) };
+        let digits = record.strip_prefix(':').ok_or_else(|| invalid("record must start with ':'"))?;
+        if digits.len() < 10 || digits.len() % 2 != 0 || !digits.is_ascii() {
+            return Err(invalid("record is malformed"));
+        }
+
+        let bytes = (0..digits.len()).step_by(2)
+            .map(|i| parse_hex_byte(&digits[i..i + 2], line))
+            .collect::<Result<Vec<u8>, RomError>>()?;
+        let count = bytes[0] as usize;
+        if bytes.len() != count + 5 {
+            return Err(invalid("byte count doesn't match record length"));
+        }
+        if bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+            return Err(invalid("checksum mismatch"));
+        }
+
+        let addr = (bytes[1] as usize) << 8 | bytes[2] as usize;
+        let payload = &bytes[4..4 + count];
+        match bytes[3] {
+            // Data
+            0x00 => {
+                for (i, byte) in payload.iter().enumerate() {
+                    data.push((base_addr + addr + i, *byte));
+                }
+            },
+            // End of file
+            0x01 => break,
+            // Extended segment address
+            0x02 if count == 2 => base_addr = ((payload[0] as usize) << 8 | payload[1] as usize) << 4,
+            // Extended linear address
+            0x04 if count == 2 => base_addr = ((payload[0] as usize) << 8 | payload[1] as usize) << 16,
+            // Start addresses don't matter for Chip 8
+            0x03 | 0x05 => {},
+            _ => return Err(invalid("unsupported record type")),
+        }
+    }
+
+    let Some(start) = data.iter().map(|(addr, _)| *addr).min() else {
+        return Err(RomError::Empty);
+    };
+    let offset = if start >= ROM_START { ROM_START } else { 0 };
+    let end = data.iter().map(|(addr, _)| *addr).max().unwrap_or(0) + 1 - offset;
+
+    // Bound the buffer by the largest memory model before allocating so bad addresses
+    // can't exhaust memory
+    let max = Platform::XoChip.memory_size() - ROM_START;
+    if end > max {
+        return Err(RomError::TooLarge { size: end, max, platform: Platform::XoChip });
+    }
+    let mut rom = vec![0; end];
+    for (addr, byte) in data {
+        rom[addr - offset] = byte;
+    }
+    Ok(rom)
+}
+
+// Decode a plain hex text dump such as "00E0 A22A 600C"
+// Bytes may be separated by whitespace or commas, prefixed with 0x,
+// and anything after # or ; on a line is a comment
+fn decode_hex_text(text: &str) -> Result<Vec<u8>, RomError> {
+    let mut rom = Vec::new();
+
+    for (index, raw_line) in text.lines().enumerate() {
+        let line = index + 1;
+        let content = raw_line.split(['#', ';']).next().unwrap_or("");
+
+        for token in content.split(|c: char| c.is_whitespace() || c == ',') {
+            let token = token.strip_prefix("0x")
+                .or_else(|| token.strip_prefix("0X"))
+                .unwrap_or(token);
+            if token.is_empty() {
+                continue;
+            }
+            if token.len() % 2 != 0 || !token.is_ascii() {
+                return Err(RomError::InvalidHex {
+                    line,
+                    reason: format!("{} has an odd number of digits", token),
+                });
+            }
+            for i in (0..token.len()).step_by(2) {
+                rom.push(parse_hex_byte(&token[i..i + 2], line)?);
+            }
+        }
+    }
+
+    Ok(rom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cpu::Cpu,
+        input::Keypad,
+        quirks::Quirks,
+    };
+
+    // Write a rom to a temp file named for the test and load it
+    fn load(name: &str, data: &[u8]) -> Result<Cartridge, RomError> {
+        let path = std::env::temp_dir().join(name);
+        fs::write(&path, data).unwrap();
+        let mut cartridge = Cartridge::new();
+        cartridge.load_rom(&mut Ram::new(), path.to_str().unwrap())?;
+        Ok(cartridge)
+    }
+
+    // Build a zip archive in memory from named entries
+    fn zip_of(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in entries {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+            std::io::Write::write_all(&mut zip, data).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    // One Intel HEX record with its checksum
+    fn record(addr: u16, kind: u8, data: &[u8]) -> String {
+        let mut bytes = vec![data.len() as u8, (addr >> 8) as u8, addr as u8, kind];
+        bytes.extend_from_slice(data);
+        let sum = bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        bytes.push(sum.wrapping_neg());
+        bytes.iter().fold(String::from(":"), |text, byte| format!("{}{:02X}", text, byte))
+    }
+
+    const END: &str = ":00000001FF";
+
+    // Intel HEX text from data records, ending with an end of file record
+    fn intel_hex(records: &[String]) -> String {
+        records.iter().map(String::as_str).chain([END]).collect::<Vec<_>>().join("\n")
+    }
+
+    #[test]
+    fn intel_hex_data_is_placed_relative_to_rom_start() {
+        let text = intel_hex(&[record(0x204, 0, &[0x12, 0x00]), record(0x200, 0, &[0x00, 0xE0])]);
+        assert_eq!(decode_intel_hex(&text).unwrap(), [0x00, 0xE0, 0x00, 0x00, 0x12, 0x00]);
+
+        // Addresses below ROM_START are offsets into the rom
+        let text = intel_hex(&[record(0x0002, 0, &[0x12, 0x00])]);
+        assert_eq!(decode_intel_hex(&text).unwrap(), [0x00, 0x00, 0x12, 0x00]);
+    }
+
+    #[test]
+    fn intel_hex_follows_extended_address_records() {
+        // Segment 0x20 moves address 0 to 0x200
+        let text = intel_hex(&[record(0, 2, &[0x00, 0x20]), record(0, 0, &[0x00, 0xE0])]);
+        assert_eq!(decode_intel_hex(&text).unwrap(), [0x00, 0xE0]);
+
+        let text = intel_hex(&[record(0, 4, &[0x00, 0x00]), record(0x200, 0, &[0x00, 0xE0])]);
+        assert_eq!(decode_intel_hex(&text).unwrap(), [0x00, 0xE0]);
+
+        // Linear address 1 puts the data at 64K, past every memory model
+        let text = intel_hex(&[record(0, 4, &[0x00, 0x01]), record(0, 0, &[0x00, 0xE0])]);
+        assert!(matches!(decode_intel_hex(&text), Err(RomError::TooLarge { .. })));
+    }
+
+    #[test]
+    fn intel_hex_checks_each_record() {
+        let mut bad = record(0x200, 0, &[0x00, 0xE0]);
+        bad.replace_range(bad.len() - 2.., "00");
+        let text = intel_hex(&[record(0x202, 0, &[0x12, 0x02]), bad]);
+        assert!(matches!(decode_intel_hex(&text),
+            Err(RomError::InvalidHex { line: 2, reason }) if reason == "checksum mismatch"));
+
+        let text = format!("{}\n:0200", record(0x200, 0, &[0x00, 0xE0]));
+        assert!(matches!(decode_intel_hex(&text), Err(RomError::InvalidHex { line: 2, .. })));
+    }
+
+    // Encode an Octo cartridge the way Octo does: a 160x128 label image whose palette
+    // indices also carry the payload two bits at a time in their low bits
+    fn octo_cart(json: &str) -> Vec<u8> {
+        let (width, height) = (160, 128);
+        let mut payload = (json.len() as u32).to_be_bytes().to_vec();
+        payload.extend_from_slice(json.as_bytes());
+        assert!(payload.len() * 4 <= width * height);
+
+        // A checkered label in the high bits, the payload in the low bits
+        let mut pixels: Vec<u8> = (0..width * height)
+            .map(|i| ((i % width / 8 + i / width / 8) % 2 * 4) as u8)
+            .collect();
+        let bits = payload.iter()
+            .flat_map(|byte| (0..4).rev().map(move |pair| byte >> (pair * 2) & 0b11));
+        for (pixel, bits) in pixels.iter_mut().zip(bits) {
+            *pixel |= bits;
+        }
+
+        let palette: Vec<u8> = (0..8).flat_map(|index| [if index < 4 { 0x11 } else { 0xEE }; 3])
+            .collect();
+        let mut gif = Vec::new();
+        let mut encoder = gif::Encoder::new(&mut gif, width as u16, height as u16, &palette)
+            .unwrap();
+        let frame = gif::Frame::from_indexed_pixels(width as u16, height as u16, pixels, None);
+        encoder.write_frame(&frame).unwrap();
+        drop(encoder);
+        gif
+    }
+
+    #[test]
+    fn octo_cart_gif_assembles_and_runs() {
+        let source = "# Adds 4 twice to a calculated start\n\
+            :macro add-twice reg n { reg += n reg += n }\n\
+            :calc START { 1 + 2 }\n\
+            : main\n  v0 := START\n  add-twice v0 4\n  loop again";
+        let json = serde_json::json!({
+            "program": source,
+            "options": {
+                "tickrate": 20,
+                "shiftQuirks": true,
+                "clipQuirks": true,
+                "fillColor": "#FF6600",
+                "backgroundColor": "#000000",
+            },
+        });
+        let cart = octo_cart(&json.to_string());
+        let cartridge = load("chip_8_cartridge_cart.gif", &cart).unwrap();
+        assert_eq!(cartridge.rom(), octo::assemble(source).unwrap());
+        assert_eq!(cartridge.platform, Platform::Chip8);
+
+        let settings = cartridge.embedded_settings.as_ref().unwrap();
+        assert_eq!(settings.title, "chip_8_cartridge_cart");
+        assert_eq!(settings.tickrate, Some(20));
+        assert_eq!((settings.quirks["shift"], settings.quirks["wrap"]), (true, false));
+        let foreground = settings.palette.as_ref().map(|palette| palette.foreground);
+        assert_eq!(foreground, Some([0xFF, 0x66, 0]));
+
+        let mut ram = Ram::new();
+        cartridge.write_to_ram(&mut ram);
+        let mut cpu = Cpu::new(Quirks::new());
+        let mut keypad = Keypad::new();
+        for _tick in 0..8 {
+            cpu.tick(&mut ram, &mut keypad);
+        }
+        assert_eq!(cpu.v()[0], 11);
+    }
+
+    #[test]
+    fn empty_roms_are_refused() {
+        assert!(matches!(load("chip_8_cartridge_empty.ch8", &[]), Err(RomError::Empty)));
+        assert!(matches!(load("chip_8_cartridge_empty.txt", b"; nothing\n"), Err(RomError::Empty)));
+        assert!(matches!(load("chip_8_cartridge_empty.hex", END.as_bytes()), Err(RomError::Empty)));
+    }
+
+    #[test]
+    fn zip_loads_the_first_rom_inside() {
+        let rom = [0x00, 0xE0, 0x12, 0x02];
+        let archive = zip_of(&[("README.md", b"Pong"), ("roms/pong.sc8", &rom)]);
+        let cartridge = load("chip_8_cartridge_pong.zip", &archive).unwrap();
+        assert_eq!((cartridge.rom(), cartridge.platform), (&rom[..], Platform::SuperChip));
+
+        let text = intel_hex(&[record(0x200, 0, &[0x00, 0xE0])]);
+        let hex = zip_of(&[("pong.hex", text.as_bytes())]);
+        assert_eq!(load("chip_8_cartridge_hex.zip", &hex).unwrap().rom(), [0x00, 0xE0]);
+
+        let notes = zip_of(&[("README.md", b"Pong")]);
+        assert!(matches!(load("chip_8_cartridge_notes.zip", &notes), Err(RomError::Zip(_))));
+    }
+
+    #[test]
+    fn zip_entries_past_the_limit_are_refused() {
+        let bomb = zip_of(&[("bomb.ch8", &vec![0; MAX_ZIP_ENTRY as usize + 1])]);
+        let error = load("chip_8_cartridge_bomb.zip", &bomb).err().unwrap();
+        let named = matches!(&error, RomError::Zip(reason) if reason.contains("bomb.ch8"));
+        assert!(named, "{}", error);
+    }
+
+    #[test]
+    fn hex_text_takes_one_prefix_per_token() {
+        assert_eq!(decode_hex_text("0x00E0, 0XA2 2A ; clear\n# comment\n600C").unwrap(),
+            [0x00, 0xE0, 0xA2, 0x2A, 0x60, 0x0C]);
+        assert!(matches!(decode_hex_text("0x0x12"), Err(RomError::InvalidHex { line: 1, .. })));
+        assert!(matches!(decode_hex_text("12\nA2A"), Err(RomError::InvalidHex { line: 2, .. })));
+    }
+
+    #[test]
+    fn size_limit_follows_the_platform_memory_model() {
+        let fits = vec![0; 0x1000 - ROM_START];
+        assert_eq!(load("chip_8_cartridge_fits.ch8", &fits).unwrap().len(), fits.len());
+
+        let chip8 = load("chip_8_cartridge_large.ch8", &[0; 0x1000 - ROM_START + 1]);
+        assert!(matches!(chip8, Err(RomError::TooLarge { max: 0xE00, platform: Platform::Chip8, .. })));
+
+        // Xo-chip allows 64K, this emulator's 4K ram doesn't
+        let xo_chip = load("chip_8_cartridge_large.xo8", &[0; 0x1000 - ROM_START + 1]);
+        assert!(matches!(xo_chip, Err(RomError::ExceedsRam { max: 0xE00, .. })));
+        let xo_chip = load("chip_8_cartridge_huge.xo8", &[0; 0x10000]);
+        assert!(matches!(xo_chip, Err(RomError::TooLarge { platform: Platform::XoChip, .. })));
+    }
+}
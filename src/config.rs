@@ -0,0 +1,332 @@
+//Module Todo:
+// N/A
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    frame::RenderMode,
+    palette::Palette,
+    quirks::Quirks,
+    scheduler::{self, Speed, DEFAULT_CLOCK_HZ},
+};
+
+const APP_DIR: &str = "chip_8";
+const CONFIG_FILE_NAME: &str = "config.toml";
+const DEFAULT_ROM: &str = "rom/chip8-test-suite.ch8";
+const DEFAULT_SCALE: u32 = 20;
+
+// Chip 8 keys and the host key names they are read from by default
+pub const DEFAULT_KEYMAP: [(usize, &str); 16] = [
+    (0x1, "1"), (0x2, "2"), (0x3, "3"), (0xC, "4"),
+    (0x4, "Q"), (0x5, "W"), (0x6, "E"), (0xD, "R"),
+    (0x7, "A"), (0x8, "S"), (0x9, "D"), (0xE, "F"),
+    (0xA, "Z"), (0x0, "X"), (0xB, "C"), (0xF, "V"),
+];
+
+// Quirk names written by --print-config, in chip-8-database naming
+const QUIRK_NAMES: [&str; 7] = [
+    "logic", "shift", "memoryLeaveIUnchanged", "memoryIncrementByX", "jump", "vblank", "wrap",
+];
+
+// Effective emulator settings after layering defaults, the config file,
+// the rom database and command line flags
+#[derive(Clone)]
+pub struct Settings {
+    // Video
+    pub scale: u32,
+    pub render_mode: RenderMode,
+    pub vsync: bool,
+    pub palette: Palette,
+    pub window_position: Option<(i32, i32)>, // Centered when unset
+    pub show_stats: bool, // FPS and instructions per second on the osd
+    pub debugger: bool, // Widen the window with debugger panels
+    // Audio
+    pub volume: u8, // Percent, reserved until the buzzer is implemented
+    // Input
+    pub keymap: Vec<(usize, String)>, // Chip 8 key and host key name
+    // Emulation
+    pub clock_hz: u32,
+    pub quirks: Quirks,
+    pub fast_forward: Speed,
+    pub use_database: bool,
+    // Paths
+    pub rom_path: String,
+    pub database_dir: Option<String>,
+    pub overrides_path: Option<String>,
+    pub screenshot_dir: String,
+    pub recording_dir: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        Self {
+            scale: DEFAULT_SCALE,
+            render_mode: RenderMode::Normal,
+            vsync: false,
+            palette: Palette::new(),
+            window_position: None,
+            show_stats: false,
+            debugger: false,
+            volume: 100,
+            keymap: DEFAULT_KEYMAP.iter()
+                .map(|(key, name)| (*key, String::from(*name)))
+                .collect(),
+            clock_hz: DEFAULT_CLOCK_HZ,
+            quirks: Quirks::new(),
+            fast_forward: Speed::Uncapped,
+            use_database: true,
+            rom_path: String::from(DEFAULT_ROM),
+            database_dir: None,
+            overrides_path: None,
+            screenshot_dir: String::from("."),
+            recording_dir: String::from("."),
+        }
+    }
+
+    // Set the host key for a Chip 8 key, replacing its previous mapping
+    pub fn map_key(&mut self, chip8_key: usize, name: &str) {
+        self.keymap.retain(|(key, _)| *key != chip8_key);
+        self.keymap.push((chip8_key, String::from(name)));
+        self.keymap.sort();
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Toml(PathBuf, toml::de::Error),
+    Invalid(PathBuf, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            ConfigError::Toml(path, e) => write!(f, "{}: {}", path.display(), e),
+            ConfigError::Invalid(path, reason) => write!(f, "{}: {}", path.display(), reason),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct VideoConfig {
+    scale: Option<u32>,
+    render_mode: Option<String>, // normal, phosphor[:N], blend or vblank
+    vsync: Option<bool>,
+    background: Option<String>, // "#rrggbb"
+    foreground: Option<String>,
+    window_x: Option<i32>,
+    window_y: Option<i32>,
+    show_stats: Option<bool>,
+    debugger: Option<bool>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AudioConfig {
+    volume: Option<u8>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputConfig {
+    keymap: BTreeMap<String, String>, // Chip 8 hex key to host key name
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmulationConfig {
+    clock_hz: Option<u32>,
+    fast_forward: Option<f64>, // Speed multiple, 0 for uncapped
+    use_database: Option<bool>,
+    quirks: BTreeMap<String, bool>, // chip-8-database quirk names
+}
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    rom: Option<String>,
+    database: Option<String>,
+    overrides: Option<String>,
+    screenshots: Option<String>,
+    recordings: Option<String>,
+}
+
+// Contents of config.toml, every setting is optional
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    video: VideoConfig,
+    audio: AudioConfig,
+    input: InputConfig,
+    emulation: EmulationConfig,
+    paths: PathsConfig,
+}
+
+impl ConfigFile {
+    // config.toml in the user config directory, such as ~/.config/chip_8
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(APP_DIR).join(CONFIG_FILE_NAME))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+        toml::from_str(&text)
+            .map_err(|e| ConfigError::Toml(path.to_path_buf(), e))
+    }
+
+    // Layer the file's settings over the current settings
+    pub fn apply(&self, path: &Path, settings: &mut Settings) -> Result<(), ConfigError> {
+        let invalid = |reason: String| ConfigError::Invalid(path.to_path_buf(), reason);
+
+        let video = &self.video;
+        if let Some(scale) = video.scale {
+            if scale == 0 {
+                return Err(invalid(String::from("video.scale must be at least 1")));
+            }
+            settings.scale = scale;
+        }
+        if let Some(mode) = &video.render_mode {
+            settings.render_mode = RenderMode::from_arg(mode)
+                .ok_or_else(|| invalid(format!("unknown video.render_mode {}", mode)))?;
+        }
+        if let Some(vsync) = video.vsync {
+            settings.vsync = vsync;
+        }
+        if let Some(colour) = &video.background {
+            settings.palette.background = Palette::parse_colour(colour)
+                .ok_or_else(|| invalid(format!("invalid video.background {}", colour)))?;
+        }
+        if let Some(colour) = &video.foreground {
+            settings.palette.foreground = Palette::parse_colour(colour)
+                .ok_or_else(|| invalid(format!("invalid video.foreground {}", colour)))?;
+        }
+        match (video.window_x, video.window_y) {
+            (Some(x), Some(y)) => settings.window_position = Some((x, y)),
+            (None, None) => {},
+            _ => return Err(invalid(String::from("set both video.window_x and video.window_y"))),
+        }
+        if let Some(show_stats) = video.show_stats {
+            settings.show_stats = show_stats;
+        }
+        if let Some(debugger) = video.debugger {
+            settings.debugger = debugger;
+        }
+
+        if let Some(volume) = self.audio.volume {
+            settings.volume = volume.min(100);
+        }
+
+        for (key, name) in &self.input.keymap {
+            let chip8_key = usize::from_str_radix(key, 16).ok()
+                .filter(|key| *key <= 0xF)
+                .ok_or_else(|| invalid(format!("input.keymap key {} is not 0 to F", key)))?;
+            settings.map_key(chip8_key, name);
+        }
+
+        let emulation = &self.emulation;
+        if let Some(clock_hz) = emulation.clock_hz {
+            if !scheduler::valid_clock_hz(clock_hz) {
+                return Err(invalid(format!("emulation.clock_hz must be 1 to {}", scheduler::MAX_CLOCK_HZ)));
+            }
+            settings.clock_hz = clock_hz;
+        }
+        if let Some(scale) = emulation.fast_forward {
+            settings.fast_forward = Speed::from_scale(scale)
+                .ok_or_else(|| invalid(format!("emulation.fast_forward must be 0 or {} to {}",
+                    scheduler::MIN_SPEED_SCALE, scheduler::MAX_SPEED_SCALE)))?;
+        }
+        if let Some(use_database) = emulation.use_database {
+            settings.use_database = use_database;
+        }
+        for (name, value) in &emulation.quirks {
+            if !settings.quirks.set(name, *value) {
+                return Err(invalid(format!("unknown quirk emulation.quirks.{}", name)));
+            }
+        }
+
+        let paths = &self.paths;
+        if let Some(rom) = &paths.rom {
+            settings.rom_path = rom.clone();
+        }
+        if paths.database.is_some() {
+            settings.database_dir = paths.database.clone();
+        }
+        if paths.overrides.is_some() {
+            settings.overrides_path = paths.overrides.clone();
+        }
+        if let Some(dir) = &paths.screenshots {
+            settings.screenshot_dir = dir.clone();
+        }
+        if let Some(dir) = &paths.recordings {
+            settings.recording_dir = dir.clone();
+        }
+
+        Ok(())
+    }
+
+    // Describe settings as a complete config file
+    pub fn from_settings(settings: &Settings) -> Self {
+        let colour = |rgb: [u8; 3]| format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2]);
+
+        Self {
+            video: VideoConfig {
+                scale: Some(settings.scale),
+                render_mode: Some(settings.render_mode.to_arg()),
+                vsync: Some(settings.vsync),
+                background: Some(colour(settings.palette.background)),
+                foreground: Some(colour(settings.palette.foreground)),
+                window_x: settings.window_position.map(|(x, _)| x),
+                window_y: settings.window_position.map(|(_, y)| y),
+                show_stats: Some(settings.show_stats),
+                debugger: Some(settings.debugger),
+            },
+            audio: AudioConfig {
+                volume: Some(settings.volume),
+            },
+            input: InputConfig {
+                keymap: settings.keymap.iter()
+                    .map(|(key, name)| (format!("{:X}", key), name.clone()))
+                    .collect(),
+            },
+            emulation: EmulationConfig {
+                clock_hz: Some(settings.clock_hz),
+                fast_forward: Some(match settings.fast_forward {
+                    Speed::Scaled(scale) => scale,
+                    Speed::Uncapped => 0.0,
+                }),
+                use_database: Some(settings.use_database),
+                quirks: QUIRK_NAMES.iter()
+                    .filter_map(|name| Some((String::from(*name), settings.quirks.get(name)?)))
+                    .collect(),
+            },
+            paths: PathsConfig {
+                rom: Some(settings.rom_path.clone()),
+                database: settings.database_dir.clone(),
+                overrides: settings.overrides_path.clone(),
+                screenshots: Some(settings.screenshot_dir.clone()),
+                recordings: Some(settings.recording_dir.clone()),
+            },
+        }
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("Failed to serialize config")
+    }
+}
@@ -0,0 +1,185 @@
+//Module Todo:
+// N/A
+
+use std::{
+    fmt::Write as _,
+    fs::{self, File},
+    io::{self, BufWriter},
+};
+
+use crate::{
+    disasm,
+    ram::RAM_SIZE,
+    symbols::Symbols,
+    ROM_START,
+};
+
+const HEATMAP_COLUMNS: usize = 64; // Bytes per heatmap row
+const HEATMAP_CELL: usize = 8; // Image pixels per byte
+const ROM_COLOUR: [u8; 3] = [48, 48, 64]; // Rom bytes that never ran
+const EMPTY_COLOUR: [u8; 3] = [0, 0, 0];
+
+// Whether an opcode conditionally skips the next instruction
+fn is_skip(opcode: u16) -> bool {
+    matches!((opcode >> 12, opcode & 0x000F, opcode & 0x00FF),
+        (0x3 | 0x4, _, _) | (0x5 | 0x9, 0x0, _) | (0xE, _, 0x9E | 0xA1))
+}
+
+// Which addresses executed and which way each skip went
+// Reports go to PREFIX.asm, PREFIX.info and PREFIX.png
+pub struct Coverage {
+    prefix: String,
+    counts: Vec<u64>, // Executions per address
+    taken: Vec<u64>, // Skips taken per address
+    not_taken: Vec<u64>,
+}
+
+impl Coverage {
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            counts: vec![0; RAM_SIZE],
+            taken: vec![0; RAM_SIZE],
+            not_taken: vec![0; RAM_SIZE],
+        }
+    }
+
+    pub fn record(&mut self, pc: usize, opcode: u16, pc_after: usize) {
+        let pc = pc % RAM_SIZE;
+        self.counts[pc] += 1;
+        if is_skip(opcode) {
+            if pc_after == pc + 4 {
+                self.taken[pc] += 1;
+            } else {
+                self.not_taken[pc] += 1;
+            }
+        }
+    }
+
+    // Addresses listed in the reports: every executed address, plus each even rom address
+    // not already covered by an executed instruction
+    fn listing(&self, rom_end: usize) -> Vec<usize> {
+        (0..RAM_SIZE)
+            .filter(|&addr| self.counts[addr] > 0 || ((ROM_START..rom_end).contains(&addr)
+                && addr.is_multiple_of(2) && self.counts[addr - 1] == 0))
+            .collect()
+    }
+
+    fn branch_text(&self, addr: usize) -> String {
+        format!("taken {}, not taken {}", self.taken[addr], self.not_taken[addr])
+    }
+
+    fn annotated(&self, mem: &[u8], listing: &[usize], symbols: &Symbols) -> String {
+        let executed = listing.iter().filter(|&&addr| self.counts[addr] > 0).count();
+        let (branches, branches_hit) = self.branch_totals(mem, listing);
+        let mut text = format!("; {}/{} instructions executed, {}/{} branch directions taken\n",
+            executed, listing.len(), branches_hit, branches);
+
+        for &addr in listing {
+            if let Some(label) = symbols.label(addr) {
+                let _ = writeln!(text, "{}:", label);
+            }
+            let opcode = disasm::opcode_at(mem, addr);
+            let count = match self.counts[addr] {
+                0 => String::from("-"),
+                count => count.to_string(),
+            };
+            let mut line = format!("{:>10}  {:03X}  {:04X}  {:<16}", count, addr, opcode,
+                disasm::disassemble_with(opcode, symbols));
+            if is_skip(opcode) && self.counts[addr] > 0 {
+                line.push_str(&self.branch_text(addr));
+            }
+            let _ = writeln!(text, "{}", line.trim_end());
+        }
+        text
+    }
+
+    // Skip directions, two per skip instruction, and how many of them happened
+    fn branch_totals(&self, mem: &[u8], listing: &[usize]) -> (usize, usize) {
+        listing.iter()
+            .filter(|&&addr| is_skip(disasm::opcode_at(mem, addr)))
+            .fold((0, 0), |(total, hit), &addr| {
+                let directions = (self.taken[addr] > 0) as usize + (self.not_taken[addr] > 0) as usize;
+                (total + 2, hit + directions)
+            })
+    }
+
+    // An lcov tracefile with rom addresses standing in for line numbers
+    fn lcov(&self, mem: &[u8], listing: &[usize], rom_path: &str) -> String {
+        let mut text = format!("TN:\nSF:{}\n", rom_path);
+        for &addr in listing {
+            if is_skip(disasm::opcode_at(mem, addr)) {
+                let count = |count: u64| if self.counts[addr] == 0 {
+                    String::from("-")
+                } else {
+                    count.to_string()
+                };
+                let _ = writeln!(text, "BRDA:{},0,0,{}", addr, count(self.taken[addr]));
+                let _ = writeln!(text, "BRDA:{},0,1,{}", addr, count(self.not_taken[addr]));
+            }
+        }
+        let (branches, branches_hit) = self.branch_totals(mem, listing);
+        let _ = writeln!(text, "BRF:{}\nBRH:{}", branches, branches_hit);
+        for &addr in listing {
+            let _ = writeln!(text, "DA:{},{}", addr, self.counts[addr]);
+        }
+        let executed = listing.iter().filter(|&&addr| self.counts[addr] > 0).count();
+        let _ = writeln!(text, "LF:{}\nLH:{}\nend_of_record", listing.len(), executed);
+        text
+    }
+
+    // Executed bytes shaded from dark red to yellow on a log scale, 64 bytes per row
+    fn heatmap(&self, rom_end: usize) -> Vec<u8> {
+        let max = self.counts.iter().copied().max().unwrap_or(0).max(1) as f64;
+        let colours: Vec<[u8; 3]> = (0..RAM_SIZE)
+            .map(|addr| match self.counts[addr] {
+                0 if (ROM_START..rom_end).contains(&addr) => ROM_COLOUR,
+                0 => EMPTY_COLOUR,
+                count => {
+                    let heat = (count as f64).ln_1p() / max.ln_1p();
+                    [(128.0 + 127.0 * (heat * 2.0).min(1.0)) as u8, (255.0 * heat) as u8, 0]
+                },
+            })
+            .collect();
+
+        let width = HEATMAP_COLUMNS * HEATMAP_CELL;
+        let height = RAM_SIZE / HEATMAP_COLUMNS * HEATMAP_CELL;
+        let mut rgb = Vec::with_capacity(width * height * 3);
+        for y in 0..height {
+            for x in 0..width {
+                let addr = y / HEATMAP_CELL * HEATMAP_COLUMNS + x / HEATMAP_CELL;
+                rgb.extend_from_slice(&colours[addr]);
+            }
+        }
+        rgb
+    }
+
+    fn save_heatmap(&self, rom_end: usize) -> Result<(), png::EncodingError> {
+        let path = format!("{}.png", self.prefix);
+        let mut encoder = png::Encoder::new(
+            BufWriter::new(File::create(path)?),
+            (HEATMAP_COLUMNS * HEATMAP_CELL) as u32,
+            (RAM_SIZE / HEATMAP_COLUMNS * HEATMAP_CELL) as u32,
+        );
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.heatmap(rom_end))?;
+        Ok(())
+    }
+
+    // Write every report, disassembling from the current memory
+    pub fn write_reports(&self, mem: &[u8], symbols: &Symbols, rom_path: &str, rom_len: usize)
+        -> io::Result<()> {
+        let rom_end = (ROM_START + rom_len).min(RAM_SIZE);
+        let listing = self.listing(rom_end);
+        fs::write(format!("{}.asm", self.prefix), self.annotated(mem, &listing, symbols))?;
+        fs::write(format!("{}.info", self.prefix), self.lcov(mem, &listing, rom_path))?;
+        self.save_heatmap(rom_end).map_err(io::Error::other)
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+}
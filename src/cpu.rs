@@ -1,15 +1,18 @@
 //Module Todo:
 // Implement base quirks from test suite
-// Continue implementing opcodes and match statements
-// Build timer functions
+
+use std::fmt;
 
 use crate::{
     ram::Ram,
     input::Keypad,
+    quirks::Quirks,
     ROM_START,
 };
 
 const OPCODE_INTERVAL: usize = 2;
+const FONT_SPRITE_BYTES: usize = 5; // Font digits sit at the start of ram, 5 bytes each
+const RANDOM_SEED: u32 = 0x2545_F491; // Fixed so runs compared in lockstep match
 
 #[derive(Clone, Copy)]
 enum ProgramCounter {
@@ -18,6 +21,26 @@ enum ProgramCounter {
     Jump(usize),
 }
 
+// Why the last tick couldn't run an instruction, the cpu stays put until it can
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fault {
+    PcOutOfRange(usize),
+    UnknownOpcode { pc: usize, opcode: u16 },
+    StackOverflow(usize), // Pc of the 2nnn with all 16 stack entries in use
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::PcOutOfRange(pc) => write!(f, "Pc {:03X} is outside ram", pc),
+            Fault::UnknownOpcode { pc, opcode } => {
+                write!(f, "Unknown opcode {:04X} at {:03X}", opcode, pc)
+            },
+            Fault::StackOverflow(pc) => write!(f, "Stack overflow calling from {:03X}", pc),
+        }
+    }
+}
+
 pub struct Cpu {
     pc: usize,
     sp: usize, //May not need due to .push() and .pop()
@@ -26,11 +49,15 @@ pub struct Cpu {
     stack: [u16; 16], //Keep stack an array for now. Use vector if issues arise.
     pub vram: [u8; 32 * 64 * 4], // RGBA VRAM (Height: 32, Width: 64, RGBA: 4)
     dt: u8, // Delay Timer
-    // st: u8, //Todo: Implement Sound Timer
+    pub quirks: Quirks,
+    vblank: bool, // Set at each frame boundary, cleared by dxyn when display_wait is on
+    st: u8, // Sound Timer, nothing is played yet
+    random: u32, // Xorshift state for cxkk
+    fault: Option<Fault>, // Set by a tick that couldn't run, cleared by the next tick
 }
 
 impl Cpu {
-    pub fn new() -> Self {
+    pub fn new(quirks: Quirks) -> Self {
         Self {
             pc: ROM_START,
             sp: 0,
@@ -39,15 +66,69 @@ impl Cpu {
             stack: [0; 16],
             vram: [0; 8192], // RGBA VRAM
             dt: 0,
-            // st: 0,
+            quirks,
+            vblank: false,
+            st: 0,
+            random: RANDOM_SEED,
+            fault: None,
         }
     }
 
-    fn debug(&self, current_opcode: &u16) {
-        println!("Current Opcode: {:X?}", current_opcode);
-        println!("Next PC: {:X?}, SP: {:X?}, I: {:X?}", self.pc, self.sp, self.i);
-        println!("V registers: {:X?}", self.v);
-        println!("Stack: {:X?}\n", self.stack);
+    // Clear registers, stack, timers and vram and restart at ROM_START
+    // Quirks are kept
+    pub fn reset(&mut self) {
+        *self = Self::new(self.quirks);
+    }
+
+    // Register and stack access for debuggers
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn sp(&self) -> usize {
+        self.sp
+    }
+
+    pub fn i(&self) -> usize {
+        self.i
+    }
+
+    pub fn v(&self) -> &[u8; 16] {
+        &self.v
+    }
+
+    pub fn dt(&self) -> u8 {
+        self.dt
+    }
+
+    pub fn st(&self) -> u8 {
+        self.st
+    }
+
+    pub fn fault(&self) -> Option<Fault> {
+        self.fault
+    }
+
+    // Return addresses of the active subroutine calls, outermost first
+    pub fn call_stack(&self) -> &[u16] {
+        &self.stack[..self.sp]
+    }
+
+    // Register writes for debuggers, values are masked to the register width
+    pub fn jump_to(&mut self, addr: usize) {
+        self.set_pc(ProgramCounter::Jump(addr & 0xFFF));
+    }
+
+    pub fn set_sp(&mut self, sp: usize) {
+        self.sp = sp.min(self.stack.len());
+    }
+
+    pub fn set_dt(&mut self, dt: u8) {
+        self.dt = dt;
+    }
+
+    pub fn set_st(&mut self, st: u8) {
+        self.st = st;
     }
 
     fn set_pc(&mut self, addr: ProgramCounter) {
@@ -58,7 +139,7 @@ impl Cpu {
         };
     }
 
-    fn set_i(&mut self, addr: usize) {
+    pub fn set_i(&mut self, addr: usize) {
         self.i = addr;
     }
 
@@ -66,31 +147,61 @@ impl Cpu {
         self.v[addr]
     }
 
-    fn write_v(&mut self, addr: usize, data: u8) {
+    pub fn write_v(&mut self, addr: usize, data: u8) {
         self.v[addr] = data;
     }
 
-    pub fn tick(&mut self, ram: &mut Ram, keypad: &mut Keypad) {
-        let current_opcode = self.fetch_opcode(ram);
-        self.execute_opcode(ram, keypad, &current_opcode);
+    // Signal the start of a new 60Hz frame and count down the timers
+    pub fn vblank(&mut self) {
+        self.vblank = true;
         if self.dt > 0 {
             self.dt -= 1;
         }
+        if self.st > 0 {
+            self.st -= 1;
+        }
+    }
+
+    // Run the instruction at pc, returning false if it stalled or faulted without executing
+    // A fault leaves pc on the instruction so a debugger can fix it and carry on
+    pub fn tick(&mut self, ram: &mut Ram, keypad: &mut Keypad) -> bool {
+        self.fault = None;
+        let Some(current_opcode) = self.fetch_opcode(ram) else {
+            self.fault = Some(Fault::PcOutOfRange(self.pc));
+            return false;
+        };
+
+        // With display wait, dxyn stalls until the next frame boundary
+        if self.quirks.display_wait && current_opcode & 0xF000 == 0xD000 {
+            if !self.vblank {
+                self.set_pc(ProgramCounter::Prev);
+                return false;
+            }
+            self.vblank = false;
+        }
+
+        self.execute_opcode(ram, keypad, &current_opcode);
+        if self.fault.is_some() {
+            self.set_pc(ProgramCounter::Prev);
+        }
+        self.fault.is_none()
     }
 
-    pub fn fetch_opcode(&mut self, ram: &Ram) -> u16 {
-        let opcode = (ram.mem[self.pc] as u16) << 8 | (ram.mem[self.pc + 1] as u16);
+    // Read the opcode at pc and move past it, None if it runs off the end of ram
+    pub fn fetch_opcode(&mut self, ram: &Ram) -> Option<u16> {
+        let bytes = ram.mem.get(self.pc..self.pc + 2)?;
+        let opcode = (bytes[0] as u16) << 8 | (bytes[1] as u16);
         self.set_pc(ProgramCounter::Next);
-        opcode
+        Some(opcode)
     }
 
     pub fn execute_opcode(&mut self, ram: &mut Ram, keypad: &mut Keypad, current_opcode: &u16) {
         //Represent the nibbles of the current instruction as a series of tuple values 
         let opcode_nibbles = (
             //Use bitwise and to zero out everything other than the focus nibble
-            (current_opcode & 0xF000) >> 12 as u8,
-            (current_opcode & 0x0F00) >> 8 as u8,
-            (current_opcode & 0x00F0) >> 4 as u8,
+            (current_opcode & 0xF000) >> 12,
+            (current_opcode & 0x0F00) >> 8,
+            (current_opcode & 0x00F0) >> 4,
             (current_opcode & 0x000F) as u8,
         );
 
@@ -117,26 +228,30 @@ impl Cpu {
             (0x08,    _,    _, 0x03) => self.opcode_8xy3(x, y),
             (0x08,    _,    _, 0x04) => self.opcode_8xy4(x, y),
             (0x08,    _,    _, 0x05) => self.opcode_8xy5(x, y),
-            (0x08,    _,    _, 0x06) => self.opcode_8xy6(x),
+            (0x08,    _,    _, 0x06) => self.opcode_8xy6(x, y),
             (0x08,    _,    _, 0x07) => self.opcode_8xy7(x, y),
-            (0x08,    _,    _, 0x0E) => self.opcode_8xye(x),
+            (0x08,    _,    _, 0x0E) => self.opcode_8xye(x, y),
             (0x09,    _,    _, 0x00) => self.opcode_9xy0(x, y),
             (0x0A,    _,    _,    _) => self.opcode_annn(nnn),
             (0x0B,    _,    _,    _) => self.opcode_bnnn(nnn, x),
+            (0x0C,    _,    _,    _) => self.opcode_cxkk(x, kk),
             (0x0D,    _,    _,    _) => self.opcode_dxyn(ram, x, y, n),
             (0x0E,    _, 0x09, 0x0E) => self.opcode_ex9e(keypad, x),
             (0x0E,    _, 0x0A, 0x01) => self.opcode_exa1(keypad, x),
             (0x0F,    _, 0x00, 0x07) => self.opcode_fx07(x),
             (0x0F,    _, 0x00, 0x0A) => self.opcode_fx0a(keypad, x),
             (0x0F,    _, 0x01, 0x05) => self.opcode_fx15(x),
+            (0x0F,    _, 0x01, 0x08) => self.opcode_fx18(x),
             (0x0F,    _, 0x01, 0x0E) => self.opcode_fx1e(x),
+            (0x0F,    _, 0x02, 0x09) => self.opcode_fx29(x),
             (0x0F,    _, 0x03, 0x03) => self.opcode_fx33(ram, x),
             (0x0F,    _, 0x05, 0x05) => self.opcode_fx55(ram, x),
             (0x0F,    _, 0x06, 0x05) => self.opcode_fx65(ram, x),
-            _ => panic!("Unknown opcode {:X?} at PC {:X?}", current_opcode, self.pc),
+            _ => self.fault = Some(Fault::UnknownOpcode {
+                pc: self.pc - OPCODE_INTERVAL,
+                opcode: *current_opcode,
+            }),
         };
-
-        self.debug(&current_opcode);
     }
 
     //All Chip 8 opcodes are defined below as functions
@@ -169,6 +284,10 @@ impl Cpu {
 
     // Call subroutine at nnn
     fn opcode_2nnn(&mut self, nnn: usize) {
+        if self.sp == self.stack.len() {
+            self.fault = Some(Fault::StackOverflow(self.pc - OPCODE_INTERVAL));
+            return;
+        }
         self.stack[self.sp] = self.pc as u16;
         self.sp += 1;
         self.pc = nnn;
@@ -210,41 +329,48 @@ impl Cpu {
         self.write_v(x, self.read_v(y)); 
     }
 
-    // Set vx = vx bitwise or vy, then reset vf to 0
+    // Set vx = vx bitwise or vy, then reset vf to 0 if the vf_reset quirk is on
     fn opcode_8xy1(&mut self, x: usize, y: usize) {
         self.write_v(x, self.read_v(x) | self.read_v(y));
-        self.write_v(0xF, 0);
+        if self.quirks.vf_reset {
+            self.write_v(0xF, 0);
+        }
     }
 
-    // Set vx = vx bitwise and vy, then reset vf to 0
+    // Set vx = vx bitwise and vy, then reset vf to 0 if the vf_reset quirk is on
     fn opcode_8xy2(&mut self, x: usize, y: usize) {
         self.write_v(x, self.read_v(x) & self.read_v(y));
-        self.write_v(0xF, 0);
+        if self.quirks.vf_reset {
+            self.write_v(0xF, 0);
+        }
     }
 
-    // Set vx = vx bitwise xor vy, then reset vf to 0
+    // Set vx = vx bitwise xor vy, then reset vf to 0 if the vf_reset quirk is on
     fn opcode_8xy3(&mut self, x: usize, y: usize) {
         self.write_v(x, self.read_v(x) ^ self.read_v(y));
-        self.write_v(0xF, 0);
+        if self.quirks.vf_reset {
+            self.write_v(0xF, 0);
+        }
     }
 
     // Set vx = vx + vy and set vf = carry bit
     fn opcode_8xy4(&mut self, x: usize, y: usize) {
         let (v_sum, carry_flag) = self.read_v(x).overflowing_add(self.read_v(y));
-        self.write_v(0xF, carry_flag as u8);
         self.write_v(x, v_sum);
+        self.write_v(0xF, carry_flag as u8);
     }
 
     // Set vx = vx - vy and set vf = carry bit
     fn opcode_8xy5(&mut self, x: usize, y: usize) {
         let (v_diff, carry_flag) = self.read_v(x).overflowing_sub(self.read_v(y));
-        self.write_v(0xF, !carry_flag as u8);
         self.write_v(x, v_diff);
+        self.write_v(0xF, !carry_flag as u8);
     }
 
-    // Set vx = vx right shift 1 bit and set vf = carry bit
-    fn opcode_8xy6(&mut self, x: usize) {
-        let vreg = self.read_v(x);
+    // Set vx = vy right shift 1 bit and set vf = carry bit
+    // With the shift_vx quirk, vx is shifted in place instead
+    fn opcode_8xy6(&mut self, x: usize, y: usize) {
+        let vreg = self.read_v(if self.quirks.shift_vx { x } else { y });
         self.write_v(x, vreg >> 1);
         self.write_v(0xF, vreg & 0b1);
     }
@@ -252,13 +378,14 @@ impl Cpu {
     // Set vx = vy - vx and set vf = carry bit
     fn opcode_8xy7(&mut self, x: usize, y: usize) {
         let (v_diff, carry_flag) = self.read_v(y).overflowing_sub(self.read_v(x));
-        self.write_v(0xF, !carry_flag as u8);
         self.write_v(x, v_diff);
+        self.write_v(0xF, !carry_flag as u8);
     }
     
-    // Set vx = vx left shift 1 bit and set vf = carry bit
-    fn opcode_8xye(&mut self, x: usize) {
-        let vreg = self.read_v(x);
+    // Set vx = vy left shift 1 bit and set vf = carry bit
+    // With the shift_vx quirk, vx is shifted in place instead
+    fn opcode_8xye(&mut self, x: usize, y: usize) {
+        let vreg = self.read_v(if self.quirks.shift_vx { x } else { y });
         self.write_v(x, vreg << 1);
         self.write_v(0xF, (vreg >> 7) & 0b1);
     }
@@ -275,53 +402,67 @@ impl Cpu {
         self.set_i(nnn);
     }
 
-    // Jump to address nnn + value of vx
+    // Jump to address nnn + value of v0
+    // With the jump_vx quirk, jump to xnn + value of vx instead
     fn opcode_bnnn(&mut self, nnn: usize, x: usize) {
-        let addr = nnn + self.read_v(x) as usize;
+        let vreg = if self.quirks.jump_vx { x } else { 0 };
+        let addr = nnn + self.read_v(vreg) as usize;
         self.set_pc(ProgramCounter::Jump(addr));
     }
 
+    // Set vx = random byte bitwise and kk
+    fn opcode_cxkk(&mut self, x: usize, kk: u8) {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+        self.write_v(x, (self.random >> 24) as u8 & kk);
+    }
+
     // Write sprite from ram to vram
+    // The start coordinate wraps, the rest of the sprite clips at the screen edges
+    // unless the wrap_sprites quirk is on
     fn opcode_dxyn(&mut self, ram: &Ram, x: usize, y: usize, n: usize) {
-        let x_coord = self.read_v(x) as usize;
-        let y_coord = self.read_v(y) as usize;
+        let x_coord = self.read_v(x) as usize % 64;
+        let y_coord = self.read_v(y) as usize % 32;
+        let mut erased = false;
 
         // RGBA VRAM
         for byte in 0..n { // sprite height
+            let screen_y = y_coord + byte;
+            if screen_y >= 32 && !self.quirks.wrap_sprites {
+                break;
+            }
+            let screen_row = (screen_y % 32) * 64 * 4;
+            let sprite_byte = ram.read_ram(self.i + byte);
+
             for bit in 0..8 { // sprite width
+                let screen_x = x_coord + bit;
+                if screen_x >= 64 && !self.quirks.wrap_sprites {
+                    break;
+                }
+                let rgba_pixel = (screen_x % 64) * 4;
+                let sprite_bit = sprite_byte >> (7 - bit) & 0b1;
+
                 for rgba in 0..4 { // pixel bit expanded to 4 rgba pixels
-                    let screen_row = ((y_coord + byte) * 64 * 4) % (32 * 64 * 4);
-                    let rgba_pixel = ((x_coord + bit) * 4) % (64 * 4);
                     let rgba_byte = 3 - rgba;
                     let vram_index = screen_row + rgba_pixel + rgba_byte;
 
                     // Set VRAM A bytes of RGBA to on
                     if rgba == 3 {
                         self.vram[vram_index] = 0xFF;
-                    // Toggle VRAM RGB bytes using RGB of RGBA 
-                    } else {
-                        let sprite_bit = ram.mem[self.i + byte] >> (7 -bit) &0b1;
-
-                        // Vram byte will toggle
-                        if sprite_bit > 0 {
-                            // If vram byte will toggle 1 to 0, set vf = 1
-                            if self.vram[vram_index] > 0 {
-                                self.write_v(0xF, 1);
-                            // If vram byte will toggle 0 to 1, set vf = 0
-                            } else {
-                                self.write_v(0xF, 0);
-                            }
-                            self.vram[vram_index] =
-                                self.vram[vram_index] ^ (sprite_bit * 0xFF);
-                        // Vram byte will not toggle
-                        } else {
-                            // Set vf = 0
-                            self.write_v(0xF, 0);
+                    // Toggle VRAM RGB bytes using RGB of RGBA
+                    } else if sprite_bit > 0 {
+                        // If vram byte will toggle 1 to 0, set vf = 1 once drawing is done
+                        if self.vram[vram_index] > 0 {
+                            erased = true;
                         }
+                        self.vram[vram_index] ^= 0xFF;
                     }
                 }
             }
         }
+
+        self.write_v(0xF, erased as u8);
     }
 
     // If key with value of vx is pressed, skip the next opcode
@@ -399,12 +540,22 @@ impl Cpu {
         self.dt = self.read_v(x);
     }
 
+    // Set st = vx
+    fn opcode_fx18(&mut self, x: usize) {
+        self.st = self.read_v(x);
+    }
+
     // Set i = i + vx
     // If i overflows ram (0xFFF), set vf = 1 (Add when needed)
     fn opcode_fx1e(&mut self, x: usize) {
         self.i += self.read_v(x) as usize;
     }
 
+    // Set i = location of the font sprite for the low digit of vx
+    fn opcode_fx29(&mut self, x: usize) {
+        self.set_i((self.read_v(x) & 0xF) as usize * FONT_SPRITE_BYTES);
+    }
+
     // Store BCD representation of vx in memory locations i, i+1, and i+2
     fn opcode_fx33(&self, ram: &mut Ram, x: usize) {
         let val = self.read_v(x);
@@ -418,10 +569,11 @@ impl Cpu {
     }
 
     // Store registers V0 through Vx in memory starting at location I
-    fn opcode_fx55(&self, ram: &mut Ram, x: usize) {
+    fn opcode_fx55(&mut self, ram: &mut Ram, x: usize) {
         for vreg in 0..=x {
             ram.write_ram(self.i + vreg, self.read_v(vreg));
         }
+        self.increment_i_after_memory(x);
     }
 
     // Store values from memory starting at location i in registers v0 through vx
@@ -429,5 +581,141 @@ impl Cpu {
         for vreg in 0..=x {
             self.write_v(vreg, ram.read_ram(self.i + vreg));
         }
+        self.increment_i_after_memory(x);
+    }
+
+    // Fx55 and fx65 leave i past the last register on the original interpreter
+    fn increment_i_after_memory(&mut self, x: usize) {
+        if !self.quirks.memory_leave_i {
+            let increment = if self.quirks.memory_increment_by_x { x } else { x + 1 };
+            self.set_i(self.i + increment);
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPRITE: usize = 0x300;
+
+    // A cpu about to draw a solid 8x4 sprite at x, y
+    fn setup(wrap_sprites: bool, x: u8, y: u8) -> (Cpu, Ram, Keypad) {
+        let mut quirks = Quirks::new();
+        quirks.wrap_sprites = wrap_sprites;
+        let mut cpu = Cpu::new(quirks);
+        let mut ram = Ram::new();
+        ram.mem[SPRITE..SPRITE + 4].fill(0xFF);
+        cpu.set_i(SPRITE);
+        cpu.write_v(0, x);
+        cpu.write_v(1, y);
+        (cpu, ram, Keypad::new())
+    }
+
+    fn draw(cpu: &mut Cpu, ram: &mut Ram, keypad: &mut Keypad) {
+        cpu.execute_opcode(ram, keypad, &0xD014);
+    }
+
+    fn lit(cpu: &Cpu, x: usize, y: usize) -> bool {
+        cpu.vram[(y * 64 + x) * 4 + 3] > 0
+    }
+
+    #[test]
+    fn dxyn_clips_columns_and_rows_at_the_edges() {
+        let (mut cpu, mut ram, mut keypad) = setup(false, 60, 30);
+        draw(&mut cpu, &mut ram, &mut keypad);
+
+        for (x, y) in [(60, 30), (63, 30), (60, 31), (63, 31)] {
+            assert!(lit(&cpu, x, y), "{},{} should be drawn", x, y);
+        }
+        // Neither the clipped columns nor the clipped rows reappear on the far side
+        for (x, y) in [(0, 30), (3, 31), (60, 0), (63, 1), (0, 0), (3, 1)] {
+            assert!(!lit(&cpu, x, y), "{},{} should be clipped", x, y);
+        }
+        assert_eq!(cpu.v()[0xF], 0);
+    }
+
+    #[test]
+    fn dxyn_wraps_columns_and_rows_with_the_wrap_quirk() {
+        let (mut cpu, mut ram, mut keypad) = setup(true, 60, 30);
+        draw(&mut cpu, &mut ram, &mut keypad);
+
+        for (x, y) in [(60, 30), (63, 31), (0, 30), (3, 31), (60, 0), (63, 1), (0, 0), (3, 1)] {
+            assert!(lit(&cpu, x, y), "{},{} should be drawn", x, y);
+        }
+        assert!(!lit(&cpu, 4, 0));
+        assert!(!lit(&cpu, 0, 2));
+    }
+
+    #[test]
+    fn dxyn_start_coordinate_wraps_without_the_quirk() {
+        let (mut cpu, mut ram, mut keypad) = setup(false, 64 + 2, 32 + 3);
+        draw(&mut cpu, &mut ram, &mut keypad);
+        assert!(lit(&cpu, 2, 3));
+        assert!(lit(&cpu, 9, 6));
+    }
+
+    #[test]
+    fn dxyn_sets_vf_once_on_collision() {
+        let (mut cpu, mut ram, mut keypad) = setup(false, 10, 10);
+        draw(&mut cpu, &mut ram, &mut keypad);
+        assert_eq!(cpu.v()[0xF], 0);
+
+        // Every pixel collides, vf is still just 1
+        draw(&mut cpu, &mut ram, &mut keypad);
+        assert_eq!(cpu.v()[0xF], 1);
+        assert!(!lit(&cpu, 10, 10));
+
+        // Drawing onto blank pixels clears it again
+        draw(&mut cpu, &mut ram, &mut keypad);
+        assert_eq!(cpu.v()[0xF], 0);
+    }
+
+    #[test]
+    fn fx29_points_i_at_the_font_digit() {
+        let (mut cpu, mut ram, mut keypad) = setup(false, 0x1B, 0);
+        cpu.execute_opcode(&mut ram, &mut keypad, &0xF029);
+        assert_eq!(cpu.i(), 0xB * FONT_SPRITE_BYTES);
+    }
+
+    #[test]
+    fn faults_stop_on_the_instruction_until_it_can_run() {
+        let (mut cpu, mut ram, mut keypad) = setup(false, 0, 0);
+        ram.mem[ROM_START..ROM_START + 2].copy_from_slice(&[0xF0, 0xFF]);
+        for _ in 0..2 {
+            assert!(!cpu.tick(&mut ram, &mut keypad));
+            assert_eq!(cpu.fault(), Some(Fault::UnknownOpcode { pc: ROM_START, opcode: 0xF0FF }));
+            assert_eq!(cpu.pc(), ROM_START);
+        }
+
+        // Patching the opcode lets it run
+        ram.mem[ROM_START + 1] = 0x07;
+        assert!(cpu.tick(&mut ram, &mut keypad));
+        assert_eq!((cpu.fault(), cpu.pc()), (None, ROM_START + 2));
+
+        cpu.jump_to(0xFFF);
+        assert!(!cpu.tick(&mut ram, &mut keypad));
+        assert_eq!((cpu.fault(), cpu.pc()), (Some(Fault::PcOutOfRange(0xFFF)), 0xFFF));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn calls_past_the_stack_fault() {
+        let (mut cpu, mut ram, mut keypad) = setup(false, 0, 0);
+        // A subroutine at 200 that calls itself
+        ram.mem[ROM_START..ROM_START + 2].copy_from_slice(&[0x22, 0x00]);
+        for _ in 0..16 {
+            assert!(cpu.tick(&mut ram, &mut keypad));
+        }
+        assert!(!cpu.tick(&mut ram, &mut keypad));
+        assert_eq!(cpu.fault(), Some(Fault::StackOverflow(ROM_START)));
+        assert_eq!((cpu.pc(), cpu.sp()), (ROM_START, 16));
+    }
+
+    #[test]
+    fn cxkk_masks_the_random_byte() {
+        let (mut cpu, mut ram, mut keypad) = setup(false, 0, 0);
+        for _ in 0..32 {
+            cpu.execute_opcode(&mut ram, &mut keypad, &0xC00F);
+            assert_eq!(cpu.v()[0] & 0xF0, 0);
+        }
+    }
+}
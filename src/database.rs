@@ -0,0 +1,300 @@
+//Module Todo:
+// Vendor the program list with data/update-database.sh, it ships empty until then
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use sha1::{Digest, Sha1};
+
+use crate::{
+    palette::Palette,
+    quirks::{Platform, Quirks},
+};
+
+// Bundled copies of the chip-8-database files, refreshed with data/update-database.sh
+const BUNDLED_PROGRAMS: &str = include_str!("../data/programs.json");
+const BUNDLED_HASHES: &str = include_str!("../data/sha1-hashes.json");
+const BUNDLED_PLATFORMS: &str = include_str!("../data/platforms.json");
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    roms: HashMap<String, RomEntry>,
+}
+
+// Per rom settings as stored in programs.json and the user override file
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct RomEntry {
+    title: Option<String>, // Only used by the override file
+    #[serde(default)]
+    platforms: Vec<String>,
+    tickrate: Option<u32>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, HashMap<String, bool>>,
+    colors: Option<Colours>,
+    #[serde(default)]
+    keys: HashMap<String, usize>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+struct Colours {
+    #[serde(default)]
+    pixels: Vec<String>, // Background first, then foreground
+}
+
+impl RomEntry {
+    // Layer another entry on top of this one, the other entry's fields win
+    fn merge(&mut self, other: &RomEntry) {
+        if other.title.is_some() {
+            self.title = other.title.clone();
+        }
+        if !other.platforms.is_empty() {
+            self.platforms = other.platforms.clone();
+        }
+        if other.tickrate.is_some() {
+            self.tickrate = other.tickrate;
+        }
+        for (platform, quirks) in &other.quirky_platforms {
+            self.quirky_platforms.entry(platform.clone()).or_default().extend(quirks.clone());
+        }
+        if other.colors.is_some() {
+            self.colors = other.colors.clone();
+        }
+        self.keys.extend(other.keys.clone());
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PlatformInfo {
+    id: String,
+    default_tickrate: Option<u32>,
+    #[serde(default)]
+    quirks: HashMap<String, bool>,
+}
+
+// Settings recommended for a rom found in the database
+pub struct GameSettings {
+    pub title: String,
+    pub platform_id: String,
+    pub platform: Option<Platform>,
+    pub quirks: HashMap<String, bool>, // chip-8-database quirk names
+    pub tickrate: Option<u32>, // Instructions per frame
+    pub palette: Option<Palette>,
+    pub keys: HashMap<String, usize>, // Key hints such as "up" mapped to Chip 8 keys
+}
+
+impl GameSettings {
+    pub fn apply_quirks(&self, quirks: &mut Quirks) {
+        for (name, value) in &self.quirks {
+            quirks.set(name, *value);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DatabaseError {
+    Io(PathBuf, io::Error),
+    Json(PathBuf, serde_json::Error),
+}
+
+impl fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DatabaseError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            DatabaseError::Json(path, e) => write!(f, "{}: {}", path.display(), e),
+        }
+    }
+}
+
+// Rom metadata in the chip-8-database format, keyed by sha1 of the rom
+pub struct Database {
+    programs: Vec<Program>,
+    hashes: HashMap<String, usize>, // Rom sha1 to index in programs
+    platforms: Vec<PlatformInfo>,
+    overrides: HashMap<String, RomEntry>, // User settings by rom sha1
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T, DatabaseError> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| DatabaseError::Io(path.to_path_buf(), e))?;
+    serde_json::from_str(&text)
+        .map_err(|e| DatabaseError::Json(path.to_path_buf(), e))
+}
+
+impl Database {
+    // The database compiled into the emulator
+    pub fn bundled() -> Self {
+        Self::from_json(BUNDLED_PROGRAMS, BUNDLED_HASHES, BUNDLED_PLATFORMS)
+            .expect("Bundled chip-8-database is invalid")
+    }
+
+    // Parse the programs.json, sha1-hashes.json and platforms.json contents
+    fn from_json(programs: &str, hashes: &str, platforms: &str) -> serde_json::Result<Self> {
+        Ok(Self {
+            programs: serde_json::from_str(programs)?,
+            hashes: serde_json::from_str(hashes)?,
+            platforms: serde_json::from_str(platforms)?,
+            overrides: HashMap::new(),
+        })
+    }
+
+    // Load programs.json, sha1-hashes.json and platforms.json from a
+    // chip-8-database checkout, used instead of the bundled copy
+    pub fn load_dir(dir: &Path) -> Result<Self, DatabaseError> {
+        Ok(Self {
+            programs: read_json(&dir.join("programs.json"))?,
+            hashes: read_json(&dir.join("sha1-hashes.json"))?,
+            platforms: read_json(&dir.join("platforms.json"))?,
+            overrides: HashMap::new(),
+        })
+    }
+
+    // Load a user override file mapping rom sha1 to settings in the programs.json
+    // rom format, plus an optional title. Overrides take precedence over the database
+    pub fn load_overrides(&mut self, path: &Path) -> Result<(), DatabaseError> {
+        self.overrides = read_json(path)?;
+        Ok(())
+    }
+
+    // Look up the recommended settings for a rom by its sha1
+    pub fn lookup(&self, sha1: &str) -> Option<GameSettings> {
+        let program = self.hashes.get(sha1).and_then(|index| self.programs.get(*index));
+        let override_entry = self.overrides.get(sha1);
+        if program.is_none() && override_entry.is_none() {
+            return None;
+        }
+
+        let mut entry = program
+            .and_then(|program| program.roms.get(sha1))
+            .cloned()
+            .unwrap_or_default();
+        if let Some(override_entry) = override_entry {
+            entry.merge(override_entry);
+        }
+
+        let platform_id = entry.platforms.first()
+            .cloned()
+            .unwrap_or_else(|| String::from("originalChip8"));
+        let platform_info = self.platforms.iter().find(|info| info.id == platform_id);
+
+        // Platform quirk defaults, then any rom specific quirks for that platform
+        let mut quirks = platform_info
+            .map(|info| info.quirks.clone())
+            .unwrap_or_default();
+        if let Some(rom_quirks) = entry.quirky_platforms.get(&platform_id) {
+            quirks.extend(rom_quirks.clone());
+        }
+
+        let palette = entry.colors.as_ref().and_then(|colours| {
+            Some(Palette {
+                background: Palette::parse_colour(colours.pixels.first()?)?,
+                foreground: Palette::parse_colour(colours.pixels.get(1)?)?,
+            })
+        });
+
+        Some(GameSettings {
+            title: entry.title.clone()
+                .or_else(|| program.map(|program| program.title.clone()))
+                .unwrap_or_else(|| String::from(sha1)),
+            platform: platform_from_id(&platform_id),
+            tickrate: entry.tickrate.or(platform_info.and_then(|info| info.default_tickrate)),
+            platform_id,
+            quirks,
+            palette,
+            keys: entry.keys,
+        })
+    }
+}
+
+// Map a chip-8-database platform id onto the platforms the emulator knows
+fn platform_from_id(id: &str) -> Option<Platform> {
+    match id {
+        "originalChip8" | "hybridVIP" | "modernChip8" | "chip8x" | "chip48" => Some(Platform::Chip8),
+        "superchip1" | "superchip" => Some(Platform::SuperChip),
+        "xochip" => Some(Platform::XoChip),
+        _ => None,
+    }
+}
+
+// Lowercase hex sha1 of rom data, as used for database keys
+pub fn sha1_hex(data: &[u8]) -> String {
+    Sha1::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAMS: &str = r##"[
+        {"title": "Pong", "roms": {"aaaa": {"platforms": ["superchip"], "tickrate": 20,
+            "quirkyPlatforms": {"superchip": {"wrap": true}},
+            "colors": {"pixels": ["#000000", "#00ff00"]}, "keys": {"up": 1}}}},
+        {"title": "Maze", "roms": {"bbbb": {"platforms": ["originalChip8"]}}}
+    ]"##;
+    const HASHES: &str = r#"{"aaaa": 0, "bbbb": 1}"#;
+
+    fn database() -> Database {
+        Database::from_json(PROGRAMS, HASHES, BUNDLED_PLATFORMS).unwrap()
+    }
+
+    #[test]
+    fn bundled_copy_parses() {
+        let bundled = Database::bundled();
+        assert!(bundled.hashes.values().all(|index| *index < bundled.programs.len()));
+        assert!(bundled.platforms.iter().any(|info| info.id == "originalChip8"));
+    }
+
+    #[test]
+    fn lookup_finds_rom_settings_by_sha1() {
+        let database = database();
+        assert!(database.lookup("cccc").is_none());
+        assert_eq!(sha1_hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+
+        let pong = database.lookup("aaaa").unwrap();
+        assert_eq!((pong.title.as_str(), pong.platform), ("Pong", Some(Platform::SuperChip)));
+        assert_eq!(pong.tickrate, Some(20));
+        assert_eq!(pong.keys.get("up"), Some(&1));
+        let palette = pong.palette.unwrap();
+        assert_eq!((palette.background, palette.foreground), ([0, 0, 0], [0, 0xFF, 0]));
+
+        // Platform quirk defaults, with the rom's own quirks on top
+        let mut quirks = Quirks::new();
+        pong.apply_quirks(&mut quirks);
+        assert_eq!((quirks.get("shift"), quirks.get("wrap"), quirks.get("logic")),
+            (Some(true), Some(true), Some(false)));
+
+        // Without a rom tickrate the platform default applies
+        let maze = database.lookup("bbbb").unwrap();
+        assert_eq!((maze.platform, maze.tickrate), (Some(Platform::Chip8), Some(15)));
+    }
+
+    #[test]
+    fn overrides_take_precedence() {
+        let mut database = database();
+        let path = std::env::temp_dir().join("chip_8_database_overrides.json");
+        fs::write(&path, r#"{
+            "aaaa": {"title": "My Pong", "tickrate": 40, "quirkyPlatforms": {"superchip": {"wrap": false}}},
+            "dddd": {"title": "Homebrew", "platforms": ["xochip"]}
+        }"#).unwrap();
+        database.load_overrides(&path).unwrap();
+
+        let pong = database.lookup("aaaa").unwrap();
+        assert_eq!((pong.title.as_str(), pong.tickrate), ("My Pong", Some(40)));
+        assert_eq!((pong.platform, pong.quirks.get("wrap")), (Some(Platform::SuperChip), Some(&false)));
+        assert!(pong.palette.is_some());
+
+        let homebrew = database.lookup("dddd").unwrap();
+        assert_eq!((homebrew.title.as_str(), homebrew.platform), ("Homebrew", Some(Platform::XoChip)));
+    }
+}
@@ -0,0 +1,124 @@
+//Module Todo:
+// N/A
+
+use std::collections::BTreeSet;
+
+use crate::{
+    machine::Machine,
+    smc::SmcMode,
+};
+
+// Breakpoints and stepping shared by the debugger frontends
+pub struct Debugger {
+    pub breakpoints: BTreeSet<usize>,
+    stopped_at: Option<usize>, // Pc execution last stopped at, run without breaking again
+    ticks_left: u32, // Ticks still to run in a frame a stop interrupted
+}
+
+impl Debugger {
+    pub fn new(breakpoints: &[usize]) -> Self {
+        Self {
+            breakpoints: breakpoints.iter().copied().collect(),
+            stopped_at: None,
+            ticks_left: 0,
+        }
+    }
+
+    // Add a breakpoint, or remove it if already set, returning whether it is now set
+    pub fn toggle_breakpoint(&mut self, addr: usize) -> bool {
+        if self.breakpoints.remove(&addr) {
+            false
+        } else {
+            self.breakpoints.insert(addr);
+            true
+        }
+    }
+
+    // Run one frame worth of ticks, stopping before any instruction at a breakpoint, on
+    // a cpu fault, or after an instruction the self modifying code check breaks on
+    // After a stop, the rest of the interrupted frame runs before a new one starts
+    // Returns why execution stopped, if it did
+    pub fn run_frame(&mut self, machine: &mut Machine, ticks: u32) -> Option<String> {
+        let ticks = if self.ticks_left > 0 {
+            self.ticks_left
+        } else {
+            machine.start_frame();
+            ticks
+        };
+        self.ticks_left = 0;
+        for tick in 0..ticks {
+            let pc = machine.cpu.pc();
+            // Resuming from a stop runs the instruction there before checking again
+            let resuming = tick == 0 && self.stopped_at == Some(pc);
+            if !resuming && self.breakpoints.contains(&pc) {
+                self.stopped_at = Some(pc);
+                self.ticks_left = ticks - tick;
+                return Some(format!("Breakpoint at {:03X}", pc));
+            }
+            machine.step();
+
+            // The faulting instruction is tried again on resume, in case it was fixed
+            if let Some(fault) = machine.cpu.fault() {
+                self.stopped_at = None;
+                self.ticks_left = ticks - tick;
+                return Some(fault.to_string());
+            }
+
+            let smc_break = machine.smc.as_ref()
+                .is_some_and(|smc| smc.mode == SmcMode::Break && smc.has_reports());
+            if smc_break {
+                self.stopped_at = Some(machine.cpu.pc());
+                self.ticks_left = ticks - tick - 1;
+                return Some(machine.take_smc_reports().join(", "));
+            }
+        }
+
+        self.stopped_at = None;
+        None
+    }
+
+    // Run a single instruction, ignoring breakpoints
+    // Steps count toward the interrupted frame, if any
+    pub fn step(&mut self, machine: &mut Machine) {
+        machine.step();
+        self.ticks_left = self.ticks_left.saturating_sub(1);
+        self.stopped_at = Some(machine.cpu.pc());
+    }
+}
+
+// Parse a breakpoint address given as hex, with or without a 0x prefix
+pub fn parse_address(text: &str) -> Option<usize> {
+    let hex = text.strip_prefix("0x").unwrap_or(text);
+    usize::from_str_radix(hex, 16).ok().filter(|addr| *addr < 0x1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Quirks;
+
+    fn machine_with(name: &str, rom: &[u8]) -> Machine {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, rom).unwrap();
+        Machine::new(Quirks::new(), path.to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn resuming_finishes_the_interrupted_frame() {
+        // v0 counts instructions run, then the program loops on the jump at 208
+        let mut machine = machine_with("chip_8_debugger_resume.ch8",
+            &[0x70, 0x01, 0x70, 0x01, 0x70, 0x01, 0x70, 0x01, 0x12, 0x08]);
+        machine.cpu.set_dt(10);
+        let mut debugger = Debugger::new(&[0x204]);
+
+        assert_eq!(debugger.run_frame(&mut machine, 4), Some(String::from("Breakpoint at 204")));
+        assert_eq!((machine.cpu.dt(), machine.cpu.v()[0]), (9, 2));
+
+        // The two ticks left of the frame run without the timers ticking again
+        assert_eq!(debugger.run_frame(&mut machine, 4), None);
+        assert_eq!((machine.cpu.dt(), machine.cpu.v()[0], machine.cpu.pc()), (9, 4, 0x208));
+
+        assert_eq!(debugger.run_frame(&mut machine, 4), None);
+        assert_eq!(machine.cpu.dt(), 8);
+    }
+}
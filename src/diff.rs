@@ -0,0 +1,326 @@
+//Module Todo:
+// N/A
+
+use std::{
+    collections::VecDeque,
+    fmt,
+};
+
+use crate::{
+    disasm,
+    input::Keypad,
+    machine::Machine,
+    scheduler::Scheduler,
+    symbols::Symbols,
+    trace::{Registers, TraceRecord},
+};
+
+const CONTEXT_RECORDS: usize = 8; // Instructions shown from each run before a divergence
+const MAX_RAM_DIFFERENCES: usize = 8;
+
+// The first point where two runs disagree, with the instructions leading up to it
+pub struct Divergence {
+    at: String,
+    differences: Vec<String>,
+    names: [String; 2],
+    context: [Vec<String>; 2], // Oldest first, ending with the instruction that diverged
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "First divergence at {}", self.at)?;
+        for difference in &self.differences {
+            writeln!(f, "  {}", difference)?;
+        }
+        for (name, context) in self.names.iter().zip(&self.context) {
+            writeln!(f, "{}:", name)?;
+            for line in context {
+                writeln!(f, "{}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// A Chip 8 key held down in both runs over an inclusive range of frames
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct KeyPress {
+    pub key: usize,
+    pub first_frame: u64,
+    pub last_frame: u64,
+}
+
+impl KeyPress {
+    // Parse KEY@FRAME or KEY@FIRST-LAST, with the key as a hex digit
+    pub fn parse(text: &str) -> Option<Self> {
+        let (key, frames) = text.split_once('@')?;
+        let key = usize::from_str_radix(key, 16).ok().filter(|key| *key < 0x10)?;
+        let (first, last) = frames.split_once('-').unwrap_or((frames, frames));
+        let (first_frame, last_frame) = (first.parse().ok()?, last.parse().ok()?);
+        (first_frame <= last_frame).then_some(Self { key, first_frame, last_frame })
+    }
+
+    fn held(&self, frame: u64) -> bool {
+        (self.first_frame..=self.last_frame).contains(&frame)
+    }
+}
+
+// Hold the scripted keys for a frame and release the rest
+fn press_keys(keypad: &mut Keypad, keys: &[KeyPress], frame: u64) {
+    keypad.reset_keypad();
+    for press in keys.iter().filter(|press| press.held(frame)) {
+        keypad.keypad[press.key] = true;
+        keypad.key_pressed = true;
+    }
+}
+
+// Recent records of one run, dropping the oldest past the context size
+#[derive(Default)]
+struct History(VecDeque<TraceRecord>);
+
+impl History {
+    fn push(&mut self, record: TraceRecord) {
+        if self.0.len() == CONTEXT_RECORDS {
+            self.0.pop_front();
+        }
+        self.0.push_back(record);
+    }
+
+    fn lines(&self, symbols: &Symbols) -> Vec<String> {
+        self.0.iter().map(|record| record.to_text(symbols)).collect()
+    }
+}
+
+fn register_differences(a: &Registers, b: &Registers, differences: &mut Vec<String>) {
+    for x in 0..16 {
+        if a.v[x] != b.v[x] {
+            differences.push(format!("V{:X} {:02X} / {:02X}", x, a.v[x], b.v[x]));
+        }
+    }
+    if a.i != b.i {
+        differences.push(format!("I {:03X} / {:03X}", a.i, b.i));
+    }
+    if a.sp != b.sp {
+        differences.push(format!("SP {:X} / {:X}", a.sp, b.sp));
+    }
+    if a.dt != b.dt {
+        differences.push(format!("DT {:02X} / {:02X}", a.dt, b.dt));
+    }
+    if a.st != b.st {
+        differences.push(format!("ST {:02X} / {:02X}", a.st, b.st));
+    }
+}
+
+// Everything that differs between two machines, pc first and ram last
+fn machine_differences(a: &Machine, b: &Machine) -> Vec<String> {
+    let mut differences = Vec::new();
+    if a.cpu.pc() != b.cpu.pc() {
+        differences.push(format!("PC {:03X} / {:03X}", a.cpu.pc(), b.cpu.pc()));
+    }
+    register_differences(&Registers::of(&a.cpu), &Registers::of(&b.cpu), &mut differences);
+    if a.cpu.call_stack() != b.cpu.call_stack() {
+        differences.push(format!("Stack {:03X?} / {:03X?}", a.cpu.call_stack(), b.cpu.call_stack()));
+    }
+
+    let ram: Vec<usize> = (0..a.ram.mem.len())
+        .filter(|&addr| a.ram.mem[addr] != b.ram.mem[addr])
+        .collect();
+    for &addr in ram.iter().take(MAX_RAM_DIFFERENCES) {
+        differences.push(format!("RAM {:03X} {:02X} / {:02X}", addr, a.ram.mem[addr], b.ram.mem[addr]));
+    }
+    if ram.len() > MAX_RAM_DIFFERENCES {
+        differences.push(format!("... and {} more ram bytes", ram.len() - MAX_RAM_DIFFERENCES));
+    }
+    differences
+}
+
+// Run one instruction and return its record
+fn step_recorded(machine: &mut Machine) -> TraceRecord {
+    let pc = machine.cpu.pc();
+    let opcode = disasm::opcode_at(&machine.ram.mem, pc);
+    let before = Registers::of(&machine.cpu);
+    let cycle = machine.cycles;
+    machine.step();
+    TraceRecord {
+        cycle,
+        pc,
+        opcode,
+        changes: before.changes(&Registers::of(&machine.cpu)),
+    }
+}
+
+// Run two machines in lockstep with the same frame timing and scripted key presses,
+// comparing the full cpu state and ram after every instruction
+pub fn lockstep(machines: [&mut Machine; 2], names: [String; 2], keys: &[KeyPress],
+    scheduler: &mut Scheduler, frames: u64) -> Option<Divergence> {
+    let [a, b] = machines;
+    let mut history = [History::default(), History::default()];
+
+    for frame in 0..frames {
+        let ticks = scheduler.ticks_for_frame();
+        for machine in [&mut *a, &mut *b] {
+            press_keys(&mut machine.keypad, keys, frame);
+            machine.start_frame();
+        }
+        for _tick in 0..ticks {
+            let cycle = a.cycles;
+            history[0].push(step_recorded(a));
+            history[1].push(step_recorded(b));
+
+            let differences = machine_differences(a, b);
+            if !differences.is_empty() {
+                return Some(Divergence {
+                    at: format!("cycle {} (frame {})", cycle, frame),
+                    differences,
+                    names,
+                    context: [history[0].lines(&a.symbols), history[1].lines(&b.symbols)],
+                });
+            }
+        }
+    }
+    None
+}
+
+// Compare two recorded traces record by record
+// Traces only hold pc, opcode and register changes, so stack and ram are not compared
+pub fn compare_traces(traces: [&[TraceRecord]; 2], names: [String; 2], symbols: &Symbols)
+    -> Option<Divergence> {
+    let [a, b] = traces;
+    let mut registers = [Registers::default(), Registers::default()];
+    let mut history = [History::default(), History::default()];
+
+    for index in 0..a.len().max(b.len()) {
+        let (record_a, record_b) = match (a.get(index), b.get(index)) {
+            (Some(record_a), Some(record_b)) => (record_a, record_b),
+            (record_a, _) => {
+                let (ended, other) = if record_a.is_none() { (0, 1) } else { (1, 0) };
+                return Some(Divergence {
+                    at: format!("record {}", index),
+                    differences: vec![format!("{} ends while {} continues", names[ended], names[other])],
+                    context: [history[0].lines(symbols), history[1].lines(symbols)],
+                    names,
+                });
+            },
+        };
+
+        let mut differences = Vec::new();
+        if record_a.pc != record_b.pc {
+            differences.push(format!("PC {:03X} / {:03X}", record_a.pc, record_b.pc));
+        }
+        if record_a.opcode != record_b.opcode {
+            differences.push(format!("Opcode {:04X} / {:04X}", record_a.opcode, record_b.opcode));
+        }
+        record_a.apply(&mut registers[0]);
+        record_b.apply(&mut registers[1]);
+        register_differences(&registers[0], &registers[1], &mut differences);
+
+        history[0].push(record_a.clone());
+        history[1].push(record_b.clone());
+        if !differences.is_empty() {
+            return Some(Divergence {
+                at: format!("record {} (cycle {} / {})", index, record_a.cycle, record_b.cycle),
+                differences,
+                names,
+                context: [history[0].lines(symbols), history[1].lines(symbols)],
+            });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Quirks;
+
+    // Wait for key 5, then shift v1 into v0, which the shift quirk changes
+    const WAIT_THEN_SHIFT: [u8; 14] = [
+        0x60, 0x05, // 200 v0 := 5
+        0xE0, 0x9E, // 202 skip if key v0 is down
+        0x12, 0x02, // 204 jump 202
+        0x61, 0x81, // 206 v1 := 0x81
+        0x60, 0x03, // 208 v0 := 3
+        0x80, 0x16, // 20A v0 := v1 >> 1, or v0 >> 1 with the quirk
+        0x12, 0x0C, // 20C jump 20C
+    ];
+
+    // Run the rom in lockstep against itself with the shift quirk off then on
+    fn lockstep_shift(name: &str, keys: &[KeyPress]) -> (Option<Divergence>, [Machine; 2]) {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, WAIT_THEN_SHIFT).unwrap();
+        let path = path.to_str().unwrap();
+        let [mut no_shift, mut shift] = [Quirks::new(); 2];
+        no_shift.set("shift", false);
+        shift.set("shift", true);
+        let mut a = Machine::new(no_shift, path).unwrap();
+        let mut b = Machine::new(shift, path).unwrap();
+
+        let names = [String::from("a"), String::from("b")];
+        let mut scheduler = Scheduler::new(600);
+        let divergence = lockstep([&mut a, &mut b], names, keys, &mut scheduler, 10);
+        (divergence, [a, b])
+    }
+
+    fn record(cycle: u64, pc: usize, opcode: u16, changes: &[(u8, u16)]) -> TraceRecord {
+        TraceRecord { cycle, pc, opcode, changes: changes.to_vec() }
+    }
+
+    fn names() -> [String; 2] {
+        [String::from("a.txt"), String::from("b.txt")]
+    }
+
+    #[test]
+    fn key_presses_parse_as_a_key_and_frames() {
+        let press = |key, first_frame, last_frame| Some(KeyPress { key, first_frame, last_frame });
+        assert_eq!(KeyPress::parse("5@3"), press(5, 3, 3));
+        assert_eq!(KeyPress::parse("a@10-20"), press(0xA, 10, 20));
+        for invalid in ["10@1", "5", "5@x", "5@4-3", "@1"] {
+            assert_eq!(KeyPress::parse(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn lockstep_runs_agree_until_input_reaches_the_quirk() {
+        let (divergence, [a, _]) = lockstep_shift("chip_8_diff_no_keys.ch8", &[]);
+        assert!(divergence.is_none());
+        assert!(matches!(a.cpu.pc(), 0x202 | 0x204), "{:03X}", a.cpu.pc());
+
+        let keys = [KeyPress { key: 5, first_frame: 3, last_frame: 3 }];
+        let (divergence, [a, b]) = lockstep_shift("chip_8_diff_keys.ch8", &keys);
+        let report = divergence.unwrap().to_string();
+        assert!(report.starts_with("First divergence at cycle 34 (frame 3)\n  V0 40 / 01\n"),
+            "{}", report);
+        // Both runs stop just past the instruction that diverged, which ends both histories
+        assert_eq!((a.cpu.pc(), b.cpu.pc()), (0x20C, 0x20C));
+        assert!(report.contains("a:\n") && report.contains("b:\n"));
+        assert_eq!(report.matches(" 20A 8016 ").count(), 2, "{}", report);
+    }
+
+    #[test]
+    fn matching_traces_have_no_divergence() {
+        let trace = [record(0, 0x200, 0x6005, &[(0, 5)]), record(1, 0x202, 0x1202, &[])];
+        assert!(compare_traces([&trace, &trace], names(), &Symbols::default()).is_none());
+    }
+
+    #[test]
+    fn traces_diverge_on_registers_they_reach_differently() {
+        // The shift quirk seen through traces: the same instruction leaves different values
+        let common = [record(0, 0x200, 0x6181, &[(1, 0x81)]), record(1, 0x202, 0x6003, &[(0, 3)])];
+        let a = [&common[..], &[record(2, 0x204, 0x8016, &[(0, 0x40)])]].concat();
+        let b = [&common[..], &[record(2, 0x204, 0x8016, &[(0, 0x01)])]].concat();
+        let report = compare_traces([&a, &b], names(), &Symbols::default()).unwrap().to_string();
+        assert!(report.starts_with("First divergence at record 2 (cycle 2 / 2)\n  V0 40 / 01\n"),
+            "{}", report);
+    }
+
+    #[test]
+    fn traces_diverge_on_pc_and_length() {
+        let a = [record(0, 0x200, 0x1204, &[]), record(1, 0x204, 0x00E0, &[])];
+        let b = [record(0, 0x200, 0x1206, &[]), record(1, 0x206, 0x00E0, &[])];
+        let report = compare_traces([&a, &b], names(), &Symbols::default()).unwrap().to_string();
+        assert!(report.contains("  Opcode 1204 / 1206\n"), "{}", report);
+
+        let report = compare_traces([&a, &a[..1]], names(), &Symbols::default()).unwrap();
+        assert!(report.to_string().contains("  b.txt ends while a.txt continues\n"), "{}", report);
+    }
+}
@@ -0,0 +1,76 @@
+//Module Todo:
+// N/A
+
+use crate::symbols::Symbols;
+
+// Disassemble a Chip 8 opcode into a mnemonic, with hex operands prefixed by #
+// Opcodes that aren't Chip 8 instructions are shown as data words
+pub fn disassemble(opcode: u16) -> String {
+    let nibbles = (
+        (opcode & 0xF000) >> 12,
+        (opcode & 0x0F00) >> 8,
+        (opcode & 0x00F0) >> 4,
+        opcode & 0x000F,
+    );
+    let nnn = opcode & 0x0FFF;
+    let kk = opcode & 0x00FF;
+    let x = nibbles.1;
+    let y = nibbles.2;
+    let n = nibbles.3;
+
+    match nibbles {
+        (0x0, 0x0, 0xE, 0x0) => String::from("CLS"),
+        (0x0, 0x0, 0xE, 0xE) => String::from("RET"),
+        (0x0,   _,   _,   _) => format!("SYS #{:03X}", nnn),
+        (0x1,   _,   _,   _) => format!("JP #{:03X}", nnn),
+        (0x2,   _,   _,   _) => format!("CALL #{:03X}", nnn),
+        (0x3,   _,   _,   _) => format!("SE V{:X}, #{:02X}", x, kk),
+        (0x4,   _,   _,   _) => format!("SNE V{:X}, #{:02X}", x, kk),
+        (0x5,   _,   _, 0x0) => format!("SE V{:X}, V{:X}", x, y),
+        (0x6,   _,   _,   _) => format!("LD V{:X}, #{:02X}", x, kk),
+        (0x7,   _,   _,   _) => format!("ADD V{:X}, #{:02X}", x, kk),
+        (0x8,   _,   _, 0x0) => format!("LD V{:X}, V{:X}", x, y),
+        (0x8,   _,   _, 0x1) => format!("OR V{:X}, V{:X}", x, y),
+        (0x8,   _,   _, 0x2) => format!("AND V{:X}, V{:X}", x, y),
+        (0x8,   _,   _, 0x3) => format!("XOR V{:X}, V{:X}", x, y),
+        (0x8,   _,   _, 0x4) => format!("ADD V{:X}, V{:X}", x, y),
+        (0x8,   _,   _, 0x5) => format!("SUB V{:X}, V{:X}", x, y),
+        (0x8,   _,   _, 0x6) => format!("SHR V{:X}, V{:X}", x, y),
+        (0x8,   _,   _, 0x7) => format!("SUBN V{:X}, V{:X}", x, y),
+        (0x8,   _,   _, 0xE) => format!("SHL V{:X}, V{:X}", x, y),
+        (0x9,   _,   _, 0x0) => format!("SNE V{:X}, V{:X}", x, y),
+        (0xA,   _,   _,   _) => format!("LD I, #{:03X}", nnn),
+        (0xB,   _,   _,   _) => format!("JP V0, #{:03X}", nnn),
+        (0xC,   _,   _,   _) => format!("RND V{:X}, #{:02X}", x, kk),
+        (0xD,   _,   _,   _) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        (0xE,   _, 0x9, 0xE) => format!("SKP V{:X}", x),
+        (0xE,   _, 0xA, 0x1) => format!("SKNP V{:X}", x),
+        (0xF,   _, 0x0, 0x7) => format!("LD V{:X}, DT", x),
+        (0xF,   _, 0x0, 0xA) => format!("LD V{:X}, K", x),
+        (0xF,   _, 0x1, 0x5) => format!("LD DT, V{:X}", x),
+        (0xF,   _, 0x1, 0x8) => format!("LD ST, V{:X}", x),
+        (0xF,   _, 0x1, 0xE) => format!("ADD I, V{:X}", x),
+        (0xF,   _, 0x2, 0x9) => format!("LD F, V{:X}", x),
+        (0xF,   _, 0x3, 0x3) => format!("LD B, V{:X}", x),
+        (0xF,   _, 0x5, 0x5) => format!("LD [I], V{:X}", x),
+        (0xF,   _, 0x6, 0x5) => format!("LD V{:X}, [I]", x),
+        _ => format!("DW #{:04X}", opcode),
+    }
+}
+
+// Disassemble with the targets of jumps, calls and ld i shown as labels where known
+pub fn disassemble_with(opcode: u16, symbols: &Symbols) -> String {
+    let text = disassemble(opcode);
+    let addr = (opcode & 0x0FFF) as usize;
+    match (opcode >> 12, symbols.label(addr)) {
+        (0x1 | 0x2 | 0xA | 0xB, Some(label)) => text.replace(&format!("#{:03X}", addr), label),
+        _ => text,
+    }
+}
+
+// Read the big endian opcode at addr, treating bytes past the end of memory as 0
+pub fn opcode_at(mem: &[u8], addr: usize) -> u16 {
+    let high = mem.get(addr).copied().unwrap_or(0) as u16;
+    let low = mem.get(addr + 1).copied().unwrap_or(0) as u16;
+    high << 8 | low
+}
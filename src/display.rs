@@ -2,81 +2,128 @@
 // N/A
 
 use sdl2::{
-    render::{Canvas, Texture},
+    render::{BlendMode, Canvas, Texture},
     video::Window,
     pixels::Color,
+    rect::Rect,
 };
 
+use std::path::{Path, PathBuf};
+
 use crate::{
-    cpu::Cpu,
+    config::Settings,
+    debugger::Debugger,
+    frame::Compositor,
+    machine::Machine,
+    osd::Osd,
+    panels::Panels,
+    screenshot,
     CHIP8_WIDTH,
     CHIP8_HEIGHT,
 };
 
-const SCALE_FACTOR: u32 = 20;
-const DISPLAY_WIDTH: u32 = CHIP8_WIDTH * SCALE_FACTOR;
-const DISPLAY_HEIGHT: u32 = CHIP8_HEIGHT * SCALE_FACTOR;
-
-pub const FONT_SET: [u8; 80] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-];
+const OSD_PIXELS_PER_SCALE: u32 = 8; // Display scale per osd and panel text pixel
 
 pub struct Display {
     pub canvas: Canvas<Window>,
+    pub compositor: Compositor,
+    pub osd: Osd,
+    pub panels: Option<Panels>, // Debugger panels beside the game
+    scale: u32, // Window pixels per Chip 8 pixel
 }
 
 impl Display {
-    pub fn new(sdl_context: &sdl2::Sdl) -> Self {
+    pub fn new(sdl_context: &sdl2::Sdl, settings: &Settings) -> Self {
         let video_subsystem = sdl_context
             .video()
             .expect("Failed to initialize the video subsystem");
-        let window = video_subsystem
-            .window("Chip 8", DISPLAY_WIDTH, DISPLAY_HEIGHT)
-            .position_centered()
+
+        // The debugger layout widens the window to fit its panels
+        let game_width = CHIP8_WIDTH * settings.scale;
+        let game_height = CHIP8_HEIGHT * settings.scale;
+        let panels = settings.debugger
+            .then(|| Panels::new(game_width, game_height, Self::text_pixel(settings.scale)));
+        let (width, height) = panels.as_ref()
+            .map_or((game_width, game_height), Panels::window_size);
+
+        let mut window_builder = video_subsystem
+            .window("Chip 8", width, height);
+        match settings.window_position {
+            Some((x, y)) => window_builder.position(x, y),
+            None => window_builder.position_centered(),
+        };
+        let window = window_builder
             .build()
             .expect("Failed to build a new window");
-        let mut canvas = window
-            .into_canvas()
+        let mut canvas_builder = window.into_canvas();
+        if settings.vsync {
+            canvas_builder = canvas_builder.present_vsync();
+        }
+        let mut canvas = canvas_builder
             .build()
             .expect("Failed to build canvas");
 
         canvas.set_draw_color(Color::RGB(0, 0, 0));
         canvas.clear();
         canvas.present();
+        canvas.set_blend_mode(BlendMode::Blend);
 
         Self {
             canvas,
+            compositor: Compositor::new(settings.render_mode, settings.palette),
+            osd: Osd::new(settings.show_stats),
+            panels,
+            scale: settings.scale,
         }
     }
 
-    pub fn draw(&mut self, cpu: &Cpu, texture: &mut Texture) {
+    // Window pixels per osd and panel font pixel, following the display scale
+    fn text_pixel(scale: u32) -> u32 {
+        (scale / OSD_PIXELS_PER_SCALE).max(1)
+    }
+
+    // Last composed RGBA frame
+    pub fn frame(&self) -> &[u8] {
+        self.compositor.output()
+    }
+
+    // Save the last presented frame as a png at native or display scale
+    pub fn save_screenshot(&self, dir: &str, rom_path: &str, frame_count: u64, native: bool)
+        -> Result<PathBuf, png::EncodingError> {
+        let scale = if native { 1 } else { self.scale };
+        let path = Path::new(dir).join(screenshot::screenshot_path(rom_path, frame_count, scale));
+        screenshot::save_png(self.compositor.output(), scale, &path)?;
+        Ok(path)
+    }
+
+    pub fn draw(&mut self, machine: &Machine, debugger: &Debugger, texture: &mut Texture) {
         self.canvas.set_draw_color(Color::RGB(0, 0, 0));
         self.canvas.clear();
         self.canvas.set_draw_color(Color::RGB(255, 255, 255));
 
-        // Update texture with RGBA VRAM contents
-        texture.update(None, cpu.vram.as_slice(), CHIP8_WIDTH as usize * 4)
+        // Update texture with the frame composed from VRAM, showing any change made since the
+        // last emulated frame
+        self.compositor.refresh(&machine.cpu);
+        texture.update(None, self.compositor.output(), CHIP8_WIDTH as usize * 4)
             .expect("Failed to update texture");
 
-        // Copy current texture contents to canvas
-        self.canvas.copy(texture, None, None)
+        // Copy current texture contents to the game area of the canvas
+        let game_area = match &self.panels {
+            Some(panels) => panels.game_area(),
+            None => {
+                let (width, height) = self.canvas.output_size()
+                    .expect("Failed to get canvas size");
+                Rect::new(0, 0, width, height)
+            },
+        };
+        self.canvas.copy(texture, None, game_area)
             .expect("Failed to copy texture to canvas");
-        
+
+        if let Some(panels) = &mut self.panels {
+            panels.draw(&mut self.canvas, machine, debugger);
+        }
+        self.osd.draw(&mut self.canvas, game_area, Self::text_pixel(self.scale));
+
         self.canvas.present();
     }
 }
\ No newline at end of file
@@ -0,0 +1,277 @@
+//Module Todo:
+// N/A
+
+use crate::{
+    cpu::Cpu,
+    palette::Palette,
+    CHIP8_WIDTH,
+    CHIP8_HEIGHT,
+};
+
+pub const FRAME_SIZE: usize = (CHIP8_WIDTH * CHIP8_HEIGHT * 4) as usize; // RGBA frame
+const PHOSPHOR_DEFAULT_FRAMES: u8 = 4;
+const VBLANK_HOLD_FRAMES: u8 = 2; // Longest a frame is held waiting for a redraw
+
+// Anti-flicker rendering modes for the display path
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RenderMode {
+    Normal, // Present vram as is
+    Phosphor(u8), // Pixels fade out over N frames after turning off
+    Blend, // Average the last two frames
+    Vblank, // Only latch vram at frame end if it didn't just lose pixels without gaining any
+}
+
+impl RenderMode {
+    // Parse a command line mode: normal, phosphor[:N], blend or vblank
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg.split_once(':') {
+            Some(("phosphor", frames)) => match frames.parse::<u8>() {
+                Ok(frames) if frames > 0 => Some(RenderMode::Phosphor(frames)),
+                _ => None,
+            },
+            Some(_) => None,
+            None => match arg {
+                "normal" => Some(RenderMode::Normal),
+                "phosphor" => Some(RenderMode::Phosphor(PHOSPHOR_DEFAULT_FRAMES)),
+                "blend" => Some(RenderMode::Blend),
+                "vblank" => Some(RenderMode::Vblank),
+                _ => None,
+            },
+        }
+    }
+
+    // Command line form of the mode, the inverse of from_arg
+    pub fn to_arg(self) -> String {
+        match self {
+            RenderMode::Normal => String::from("normal"),
+            RenderMode::Phosphor(frames) => format!("phosphor:{}", frames),
+            RenderMode::Blend => String::from("blend"),
+            RenderMode::Vblank => String::from("vblank"),
+        }
+    }
+}
+
+// Builds presentable frames from vram, shared by every frontend
+pub struct Compositor {
+    pub render_mode: RenderMode,
+    pub palette: Palette,
+    frame: [u8; FRAME_SIZE], // Grey RGBA frame built from vram
+    output: [u8; FRAME_SIZE], // Frame coloured with the palette
+    prev_vram: [u8; FRAME_SIZE], // Vram from the previous frame for blending
+    held: u8, // Frames vblank mode has kept the last latched frame
+}
+
+impl Compositor {
+    pub fn new(render_mode: RenderMode, palette: Palette) -> Self {
+        Self {
+            render_mode,
+            palette,
+            frame: [0; FRAME_SIZE],
+            output: [0; FRAME_SIZE],
+            prev_vram: [0; FRAME_SIZE],
+            held: 0,
+        }
+    }
+
+    // Build the next frame from vram based on the active render mode, once per emulated frame
+    pub fn compose(&mut self, cpu: &Cpu) {
+        match self.render_mode {
+            RenderMode::Normal => self.frame = cpu.vram,
+            RenderMode::Phosphor(frames) => {
+                let fade = 0xFF_u8.div_ceil(frames);
+                for (i, byte) in self.frame.iter_mut().enumerate() {
+                    // Lit pixels are full brightness, unlit pixels fade each frame
+                    *byte = if cpu.vram[i] > 0 {
+                        cpu.vram[i]
+                    } else {
+                        byte.saturating_sub(fade)
+                    };
+                }
+            }
+            RenderMode::Blend => {
+                for (i, byte) in self.frame.iter_mut().enumerate() {
+                    *byte = ((cpu.vram[i] as u16 + self.prev_vram[i] as u16) / 2) as u8;
+                }
+            }
+            RenderMode::Vblank => {
+                // Keep the last complete frame while a sprite is erased but not yet redrawn
+                if self.only_erased(&cpu.vram) && self.held < VBLANK_HOLD_FRAMES {
+                    self.held += 1;
+                } else {
+                    self.frame = cpu.vram;
+                    self.held = 0;
+                }
+            }
+        }
+        self.prev_vram = cpu.vram;
+        self.palette.apply(&self.frame, &mut self.output);
+    }
+
+    // Show vram changed outside a full frame, by stepping or a reset, without advancing fades
+    pub fn refresh(&mut self, cpu: &Cpu) {
+        if cpu.vram == self.prev_vram {
+            return;
+        }
+        match self.render_mode {
+            RenderMode::Normal | RenderMode::Vblank => self.frame = cpu.vram,
+            RenderMode::Phosphor(_) => {
+                for (byte, new) in self.frame.iter_mut().zip(cpu.vram) {
+                    if new > 0 {
+                        *byte = new;
+                    }
+                }
+            }
+            RenderMode::Blend => {
+                for (i, byte) in self.frame.iter_mut().enumerate() {
+                    *byte = ((cpu.vram[i] as u16 + self.prev_vram[i] as u16) / 2) as u8;
+                }
+            }
+        }
+        self.palette.apply(&self.frame, &mut self.output);
+    }
+
+    // Whether vram turned pixels of the current frame off without turning any on
+    fn only_erased(&self, vram: &[u8; FRAME_SIZE]) -> bool {
+        let mut erased = false;
+        for (old, new) in self.frame.chunks_exact(4).zip(vram.chunks_exact(4)) {
+            match (old[3] > 0, new[3] > 0) {
+                (false, true) => return false,
+                (true, false) => erased = true,
+                _ => {},
+            }
+        }
+        erased
+    }
+
+    // Brightness of a pixel in the last composed frame before colouring
+    pub fn level(&self, x: usize, y: usize) -> u8 {
+        // The R byte of each RGBA pixel carries the level
+        self.frame[(y * CHIP8_WIDTH as usize + x) * 4 + 3]
+    }
+
+    // Last composed RGBA frame
+    #[cfg(feature = "sdl")]
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Quirks;
+
+    // Light the pixel at x, y in an RGBA vram
+    fn light(cpu: &mut Cpu, x: usize, y: usize) {
+        let index = (y * CHIP8_WIDTH as usize + x) * 4;
+        cpu.vram[index..index + 4].fill(0xFF);
+    }
+
+    #[test]
+    fn vblank_holds_an_erase_until_the_redraw() {
+        let mut compositor = Compositor::new(RenderMode::Vblank, Palette::new());
+        let mut cpu = Cpu::new(Quirks::new());
+
+        light(&mut cpu, 10, 10);
+        compositor.compose(&cpu);
+        assert_eq!(compositor.level(10, 10), 0xFF);
+
+        // The sprite is erased, the old frame stays up
+        cpu.vram = [0; FRAME_SIZE];
+        compositor.compose(&cpu);
+        assert_eq!(compositor.level(10, 10), 0xFF);
+
+        // Redrawn one pixel over, the new frame is shown
+        light(&mut cpu, 11, 10);
+        compositor.compose(&cpu);
+        assert_eq!(compositor.level(10, 10), 0);
+        assert_eq!(compositor.level(11, 10), 0xFF);
+    }
+
+    #[test]
+    fn vblank_shows_a_collision_that_draws_new_pixels() {
+        let mut compositor = Compositor::new(RenderMode::Vblank, Palette::new());
+        let mut cpu = Cpu::new(Quirks::new());
+
+        light(&mut cpu, 10, 10);
+        compositor.compose(&cpu);
+
+        cpu.vram = [0; FRAME_SIZE];
+        light(&mut cpu, 20, 20);
+        compositor.compose(&cpu);
+        assert_eq!(compositor.level(10, 10), 0);
+        assert_eq!(compositor.level(20, 20), 0xFF);
+    }
+
+    #[test]
+    fn vblank_stops_holding_an_erase_that_is_never_redrawn() {
+        let mut compositor = Compositor::new(RenderMode::Vblank, Palette::new());
+        let mut cpu = Cpu::new(Quirks::new());
+
+        light(&mut cpu, 10, 10);
+        compositor.compose(&cpu);
+        cpu.vram = [0; FRAME_SIZE];
+        for _frame in 0..=VBLANK_HOLD_FRAMES {
+            compositor.compose(&cpu);
+        }
+        assert_eq!(compositor.level(10, 10), 0);
+    }
+
+    #[test]
+    fn phosphor_fades_one_step_per_frame() {
+        let mut compositor = Compositor::new(RenderMode::Phosphor(4), Palette::new());
+        let mut cpu = Cpu::new(Quirks::new());
+
+        light(&mut cpu, 10, 10);
+        compositor.compose(&cpu);
+        assert_eq!(compositor.level(10, 10), 0xFF);
+
+        cpu.vram = [0; FRAME_SIZE];
+        compositor.compose(&cpu);
+        assert_eq!(compositor.level(10, 10), 0xFF - 0x40);
+        for _frame in 0..3 {
+            compositor.compose(&cpu);
+        }
+        assert_eq!(compositor.level(10, 10), 0);
+    }
+
+    #[test]
+    fn refresh_shows_new_pixels_without_fading() {
+        let mut compositor = Compositor::new(RenderMode::Phosphor(4), Palette::new());
+        let mut cpu = Cpu::new(Quirks::new());
+
+        light(&mut cpu, 10, 10);
+        compositor.compose(&cpu);
+        cpu.vram = [0; FRAME_SIZE];
+        light(&mut cpu, 11, 10);
+        for _present in 0..10 {
+            compositor.refresh(&cpu);
+        }
+        assert_eq!(compositor.level(10, 10), 0xFF);
+        assert_eq!(compositor.level(11, 10), 0xFF);
+    }
+
+    #[test]
+    fn blend_averages_the_last_two_frames() {
+        let mut compositor = Compositor::new(RenderMode::Blend, Palette::new());
+        let mut cpu = Cpu::new(Quirks::new());
+
+        light(&mut cpu, 10, 10);
+        compositor.compose(&cpu);
+        assert_eq!(compositor.level(10, 10), 0x7F);
+
+        cpu.vram = [0; FRAME_SIZE];
+        light(&mut cpu, 11, 10);
+        compositor.compose(&cpu);
+        assert_eq!(compositor.level(10, 10), 0x7F);
+        assert_eq!(compositor.level(11, 10), 0x7F);
+
+        // Presenting again while paused keeps the blend
+        compositor.refresh(&cpu);
+        assert_eq!(compositor.level(10, 10), 0x7F);
+
+        compositor.compose(&cpu);
+        assert_eq!(compositor.level(10, 10), 0);
+        assert_eq!(compositor.level(11, 10), 0xFF);
+    }
+}
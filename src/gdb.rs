@@ -0,0 +1,568 @@
+//Module Todo:
+// N/A
+
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+};
+
+use crate::{
+    debugger::Debugger,
+    machine::Machine,
+};
+
+// Registers in gdb order: v0 to vf, i, pc, sp, dt, st
+// Values are sent big endian like Chip 8 memory
+const REGISTER_SIZES: [usize; 21] = [
+    1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, // V0 to VF
+    2, 2, // I and PC
+    1, 1, 1, // SP, DT and ST
+];
+const I_REGISTER: usize = 16;
+const PC_REGISTER: usize = 17;
+const SP_REGISTER: usize = 18;
+const DT_REGISTER: usize = 19;
+const ST_REGISTER: usize = 20;
+const LAST_PC: usize = 0xFFE; // Highest pc an instruction can be fetched from
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4; // Reported for cpu faults such as an unknown opcode
+const SIGTRAP: u8 = 5;
+const INTERRUPT: u8 = 0x03; // Sent by gdb outside a packet to stop the target
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chip8.cpu">
+    <reg name="v0" bitsize="8" type="uint8"/>
+    <reg name="v1" bitsize="8" type="uint8"/>
+    <reg name="v2" bitsize="8" type="uint8"/>
+    <reg name="v3" bitsize="8" type="uint8"/>
+    <reg name="v4" bitsize="8" type="uint8"/>
+    <reg name="v5" bitsize="8" type="uint8"/>
+    <reg name="v6" bitsize="8" type="uint8"/>
+    <reg name="v7" bitsize="8" type="uint8"/>
+    <reg name="v8" bitsize="8" type="uint8"/>
+    <reg name="v9" bitsize="8" type="uint8"/>
+    <reg name="va" bitsize="8" type="uint8"/>
+    <reg name="vb" bitsize="8" type="uint8"/>
+    <reg name="vc" bitsize="8" type="uint8"/>
+    <reg name="vd" bitsize="8" type="uint8"/>
+    <reg name="ve" bitsize="8" type="uint8"/>
+    <reg name="vf" bitsize="8" type="uint8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="dt" bitsize="8" type="uint8"/>
+    <reg name="st" bitsize="8" type="uint8"/>
+  </feature>
+</target>
+"#;
+
+// Gdb remote serial protocol server on a localhost tcp port
+// Polled by the frontends each loop so the game keeps drawing while gdb is attached
+pub struct GdbServer {
+    listener: TcpListener,
+    client: Option<TcpStream>,
+    input: Vec<u8>, // Bytes received and not yet handled
+    halted: bool, // Stopped by gdb, frontends must not run frames
+}
+
+impl GdbServer {
+    pub fn new(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener,
+            client: None,
+            input: Vec::new(),
+            halted: false,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.listener.local_addr().map_or(0, |addr| addr.port())
+    }
+
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn attached(&self) -> bool {
+        self.client.is_some()
+    }
+
+    // Accept a client and handle every packet received since the last poll,
+    // returning whether any were handled. A dropped connection lets the game run on
+    pub fn poll(&mut self, machine: &mut Machine, debugger: &mut Debugger) -> io::Result<bool> {
+        if self.client.is_none() {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    stream.set_nodelay(true)?;
+                    self.client = Some(stream);
+                    self.input.clear();
+                    // Gdb expects the target to be stopped when it attaches
+                    self.halted = true;
+                },
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(false),
+                Err(e) => return Err(e),
+            }
+        }
+
+        match self.receive().and_then(|()| self.handle_input(machine, debugger)) {
+            Ok(handled) => Ok(handled),
+            Err(e) => {
+                self.disconnect();
+                Err(e)
+            },
+        }
+    }
+
+    // Tell gdb the target stopped at a breakpoint or on a fault while it was running
+    pub fn report_stop(&mut self, machine: &Machine) {
+        if self.client.is_some() && !self.halted {
+            self.halted = true;
+            if self.send_stop(stop_signal(machine)).is_err() {
+                self.disconnect();
+            }
+        }
+    }
+
+    fn disconnect(&mut self) {
+        self.client = None;
+        self.input.clear();
+        self.halted = false;
+    }
+
+    fn receive(&mut self) -> io::Result<()> {
+        let Some(client) = &mut self.client else {
+            return Ok(());
+        };
+
+        let mut buffer = [0; 4096];
+        loop {
+            match client.read(&mut buffer) {
+                Ok(0) => {
+                    self.disconnect();
+                    return Ok(());
+                },
+                Ok(count) => self.input.extend_from_slice(&buffer[..count]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // Split the input into interrupts, acks and $packet#checksum frames
+    fn handle_input(&mut self, machine: &mut Machine, debugger: &mut Debugger) -> io::Result<bool> {
+        let mut handled = false;
+        while let Some(&first) = self.input.first() {
+            match first {
+                INTERRUPT => {
+                    self.input.remove(0);
+                    if !self.halted {
+                        self.halted = true;
+                        self.send_stop(SIGINT)?;
+                    }
+                    handled = true;
+                },
+                b'$' => {
+                    let Some(end) = self.input.iter().position(|&byte| byte == b'#') else {
+                        return Ok(handled);
+                    };
+                    // Wait for both checksum digits
+                    if self.input.len() < end + 3 {
+                        return Ok(handled);
+                    }
+                    let packet: Vec<u8> = self.input[1..end].to_vec();
+                    let checksum = std::str::from_utf8(&self.input[end + 1..end + 3]).ok()
+                        .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                    self.input.drain(..end + 3);
+
+                    if checksum != Some(checksum_of(&packet)) {
+                        self.write(b"-")?;
+                        continue;
+                    }
+                    self.write(b"+")?;
+                    let packet = String::from_utf8_lossy(&packet).into_owned();
+                    if let Some(reply) = self.handle_packet(&packet, machine, debugger) {
+                        self.send(&reply)?;
+                    }
+                    handled = true;
+                    if self.client.is_none() {
+                        return Ok(handled);
+                    }
+                },
+                // Acks from gdb and stray bytes need no handling
+                _ => {
+                    self.input.remove(0);
+                },
+            }
+        }
+        Ok(handled)
+    }
+
+    // Reply to one packet, or None when the reply comes later as a stop
+    fn handle_packet(&mut self, packet: &str, machine: &mut Machine, debugger: &mut Debugger)
+        -> Option<String> {
+        let (command, body) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+        let reply = match command {
+            "?" => stop_reply(stop_signal(machine)),
+            "g" => (0..REGISTER_SIZES.len())
+                .map(|register| encode_register(machine, register))
+                .collect(),
+            "G" => {
+                let mut offset = 0;
+                let mut values = Vec::with_capacity(REGISTER_SIZES.len());
+                for size in REGISTER_SIZES {
+                    let Some(value) = body.get(offset..offset + size * 2).and_then(parse_hex) else {
+                        return Some(error(1));
+                    };
+                    values.push(value);
+                    offset += size * 2;
+                }
+                // Registers before a refused value are still written, as on a real target
+                let written = values.into_iter().enumerate()
+                    .all(|(register, value)| write_register(machine, register, value));
+                if written { ok() } else { error(1) }
+            },
+            "p" => match parse_hex(body) {
+                Some(register) if register < REGISTER_SIZES.len() => encode_register(machine, register),
+                _ => error(1),
+            },
+            "P" => {
+                let parsed = body.split_once('=')
+                    .and_then(|(register, value)| Some((parse_hex(register)?, parse_hex(value)?)));
+                match parsed {
+                    Some((register, value)) if write_register(machine, register, value) => ok(),
+                    _ => error(1),
+                }
+            },
+            "m" => match parse_range(body, machine) {
+                Some((addr, length)) => machine.ram.mem[addr..addr + length].iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect(),
+                None => error(1),
+            },
+            "M" => {
+                let parsed = body.split_once(':')
+                    .and_then(|(range, data)| Some((parse_range(range, machine)?, decode_hex(data)?)));
+                match parsed {
+                    Some(((addr, length), data)) if data.len() == length => {
+                        for (offset, byte) in data.into_iter().enumerate() {
+                            machine.ram.write_ram(addr + offset, byte);
+                        }
+                        ok()
+                    },
+                    _ => error(1),
+                }
+            },
+            "c" | "s" => {
+                // An address to resume at must hold a whole instruction
+                if !body.is_empty() {
+                    match parse_hex(body) {
+                        Some(addr) if addr <= LAST_PC => machine.cpu.jump_to(addr),
+                        _ => return Some(error(1)),
+                    }
+                }
+                if command == "c" {
+                    self.halted = false;
+                    return None;
+                }
+                debugger.step(machine);
+                stop_reply(stop_signal(machine))
+            },
+            "Z" | "z" => {
+                // Software and hardware breakpoints are the same on an emulator
+                let mut fields = body.split(',');
+                let kind = fields.next();
+                let addr = fields.next().and_then(parse_hex);
+                match (kind, addr) {
+                    (Some("0" | "1"), Some(addr)) if addr < machine.ram.mem.len() => {
+                        if command == "Z" {
+                            debugger.breakpoints.insert(addr);
+                        } else {
+                            debugger.breakpoints.remove(&addr);
+                        }
+                        ok()
+                    },
+                    (Some("0" | "1"), _) => error(1),
+                    _ => String::new(),
+                }
+            },
+            "H" => ok(),
+            "T" => ok(),
+            "D" => {
+                let _ = self.send(&ok());
+                self.disconnect();
+                return None;
+            },
+            "k" => {
+                self.disconnect();
+                return None;
+            },
+            "q" => self.handle_query(packet),
+            _ => String::new(),
+        };
+        Some(reply)
+    }
+
+    fn handle_query(&self, packet: &str) -> String {
+        if packet.starts_with("qSupported") {
+            return String::from("PacketSize=4000;qXfer:features:read+");
+        }
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let Some((offset, length)) = range.split_once(',')
+                .and_then(|(offset, length)| Some((parse_hex(offset)?, parse_hex(length)?))) else {
+                return error(1);
+            };
+            let xml = TARGET_XML.as_bytes();
+            let start = offset.min(xml.len());
+            let end = (start + length).min(xml.len());
+            let marker = if end == xml.len() { 'l' } else { 'm' };
+            return format!("{}{}", marker, String::from_utf8_lossy(&xml[start..end]));
+        }
+        match packet {
+            "qAttached" => String::from("1"),
+            "qC" => String::from("QC1"),
+            "qfThreadInfo" => String::from("m1"),
+            "qsThreadInfo" => String::from("l"),
+            _ => String::new(),
+        }
+    }
+
+    fn send_stop(&mut self, signal: u8) -> io::Result<()> {
+        self.send(&stop_reply(signal))
+    }
+
+    fn send(&mut self, reply: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", reply, checksum_of(reply.as_bytes()));
+        self.write(packet.as_bytes())
+    }
+
+    // Writes block briefly so a full socket buffer doesn't split a packet
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let Some(client) = &mut self.client else {
+            return Ok(());
+        };
+        client.set_nonblocking(false)?;
+        let result = client.write_all(bytes);
+        client.set_nonblocking(true)?;
+        result
+    }
+}
+
+fn checksum_of(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |sum: u8, byte| sum.wrapping_add(*byte))
+}
+
+// Faults stop the cpu like an illegal instruction, anything else is a trap
+fn stop_signal(machine: &Machine) -> u8 {
+    if machine.cpu.fault().is_some() { SIGILL } else { SIGTRAP }
+}
+
+fn stop_reply(signal: u8) -> String {
+    format!("S{:02x}", signal)
+}
+
+fn ok() -> String {
+    String::from("OK")
+}
+
+fn error(code: u8) -> String {
+    format!("E{:02x}", code)
+}
+
+fn parse_hex(hex: &str) -> Option<usize> {
+    usize::from_str_radix(hex, 16).ok()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+// Parse "addr,length", rejecting ranges outside ram
+fn parse_range(range: &str, machine: &Machine) -> Option<(usize, usize)> {
+    let (addr, length) = range.split_once(',')?;
+    let (addr, length) = (parse_hex(addr)?, parse_hex(length)?);
+    (addr.checked_add(length)? <= machine.ram.mem.len()).then_some((addr, length))
+}
+
+fn read_register(machine: &Machine, register: usize) -> usize {
+    let cpu = &machine.cpu;
+    match register {
+        0..=15 => cpu.v()[register] as usize,
+        I_REGISTER => cpu.i(),
+        PC_REGISTER => cpu.pc(),
+        SP_REGISTER => cpu.sp(),
+        DT_REGISTER => cpu.dt() as usize,
+        ST_REGISTER => cpu.st() as usize,
+        _ => 0,
+    }
+}
+
+// Set a register, returning false for values the cpu can't run with
+// I is masked to ram, a pc past the last instruction or a full stack is refused
+fn write_register(machine: &mut Machine, register: usize, value: usize) -> bool {
+    let cpu = &mut machine.cpu;
+    match register {
+        0..=15 => cpu.write_v(register, value as u8),
+        I_REGISTER => cpu.set_i(value & 0xFFF),
+        PC_REGISTER if value <= LAST_PC => cpu.jump_to(value),
+        SP_REGISTER if value < 16 => cpu.set_sp(value),
+        DT_REGISTER => cpu.set_dt(value as u8),
+        ST_REGISTER => cpu.set_st(value as u8),
+        _ => return false,
+    }
+    true
+}
+
+fn encode_register(machine: &Machine, register: usize) -> String {
+    let width = REGISTER_SIZES[register] * 2;
+    let mask = (1 << (width * 4)) - 1;
+    format!("{:0width$x}", read_register(machine, register) & mask, width = width)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::quirks::Quirks;
+
+    // A gdb client on localhost, with the machine and server polled like a frontend
+    struct Session {
+        server: GdbServer,
+        client: TcpStream,
+        machine: Machine,
+        debugger: Debugger,
+        received: Vec<u8>,
+    }
+
+    impl Session {
+        fn new(name: &str, rom: &[u8]) -> Self {
+            let path = std::env::temp_dir().join(name);
+            std::fs::write(&path, rom).unwrap();
+            let server = GdbServer::new(0).unwrap();
+            let client = TcpStream::connect((Ipv4Addr::LOCALHOST, server.port())).unwrap();
+            client.set_read_timeout(Some(Duration::from_millis(10))).unwrap();
+            Self {
+                server,
+                client,
+                machine: Machine::new(Quirks::new(), path.to_str().unwrap()).unwrap(),
+                debugger: Debugger::new(&[]),
+                received: Vec::new(),
+            }
+        }
+
+        // Run a frame unless gdb has the target halted, as the frontends do
+        fn poll(&mut self) {
+            self.server.poll(&mut self.machine, &mut self.debugger).unwrap();
+            if !self.server.halted() && self.debugger.run_frame(&mut self.machine, 10).is_some() {
+                self.server.report_stop(&self.machine);
+            }
+        }
+
+        // Wait for the next reply packet, skipping acks
+        fn reply(&mut self) -> String {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            loop {
+                while self.received.first() == Some(&b'+') {
+                    self.received.remove(0);
+                }
+                if let Some(end) = self.received.iter().position(|&byte| byte == b'#') {
+                    if self.received.len() >= end + 3 {
+                        let packet: Vec<u8> = self.received.drain(..end + 3).collect();
+                        assert_eq!(packet[0], b'$');
+                        return String::from_utf8(packet[1..end].to_vec()).unwrap();
+                    }
+                }
+                assert!(Instant::now() < deadline, "no reply from the server");
+                self.poll();
+                let mut buffer = [0; 1024];
+                match self.client.read(&mut buffer) {
+                    Ok(count) => self.received.extend_from_slice(&buffer[..count]),
+                    Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {},
+                    Err(e) => panic!("{}", e),
+                }
+            }
+        }
+
+        fn send(&mut self, packet: &str) -> String {
+            let frame = format!("${}#{:02x}", packet, checksum_of(packet.as_bytes()));
+            self.client.write_all(frame.as_bytes()).unwrap();
+            self.reply()
+        }
+    }
+
+    #[test]
+    fn drives_a_session_over_tcp() {
+        // v0 += 1 at 200, jump back to 200 from 202
+        let mut session = Session::new("chip_8_gdb_session.ch8", &[0x70, 0x01, 0x12, 0x00]);
+
+        assert_eq!(session.send("?"), "S05");
+        let registers = session.send("g");
+        assert_eq!(registers.len(), REGISTER_SIZES.iter().sum::<usize>() * 2);
+        assert_eq!(&registers[32..40], "00000200"); // I then pc
+        assert_eq!(session.send("p11"), "0200");
+        assert_eq!(session.send("m200,4"), "70011200");
+
+        assert_eq!(session.send("s"), "S05");
+        assert_eq!(session.send("p11"), "0202");
+        assert_eq!(session.send("p0"), "01");
+
+        assert_eq!(session.send("Z0,200,2"), "OK");
+        assert_eq!(session.send("c"), "S05");
+        assert_eq!(session.send("p11"), "0200");
+        assert_eq!(session.send("p0"), "01");
+    }
+
+    #[test]
+    fn resume_addresses_must_hold_an_instruction() {
+        let mut session = Session::new("chip_8_gdb_resume.ch8", &[0x12, 0x00]);
+        assert_eq!(session.send("?"), "S05");
+
+        assert_eq!(session.send("sfff"), "E01");
+        assert_eq!(session.send("c1000"), "E01");
+        assert_eq!(session.send("sxyz"), "E01");
+        assert_eq!(session.send("p11"), "0200");
+        assert_eq!(session.send("s200"), "S05");
+    }
+
+    #[test]
+    fn faults_stop_with_sigill_and_can_be_fixed() {
+        // Jump to 204, where an unknown opcode waits
+        let rom = [0x12, 0x04, 0x00, 0x00, 0xF0, 0xFF];
+        let mut session = Session::new("chip_8_gdb_fault.ch8", &rom);
+        assert_eq!(session.send("?"), "S05");
+
+        assert_eq!(session.send("c"), "S04");
+        assert_eq!(session.send("?"), "S04");
+        assert_eq!(session.send("p11"), "0204");
+        assert_eq!(session.send("s"), "S04");
+
+        // Patch in a jump back to the start and carry on
+        assert_eq!(session.send("M204,2:1200"), "OK");
+        assert_eq!(session.send("s"), "S05");
+        assert_eq!(session.send("p11"), "0200");
+    }
+
+    #[test]
+    fn refuses_register_values_that_would_crash_the_cpu() {
+        let mut session = Session::new("chip_8_gdb_registers.ch8", &[0x12, 0x00]);
+        assert_eq!(session.send("?"), "S05");
+
+        assert_eq!(session.send("P10=ffff"), "OK");
+        assert_eq!(session.send("p10"), "0fff");
+        assert_eq!(session.send("P12=10"), "E01");
+        assert_eq!(session.send("P12=0f"), "OK");
+        assert_eq!(session.send("P11=fff"), "E01");
+        assert_eq!(session.send("P15=1"), "E01");
+    }
+}
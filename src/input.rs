@@ -1,16 +1,18 @@
 //Module Todo:
 // Update module name to "keypad" name?
 
-use sdl2::keyboard::Keycode;
-
-use std::collections::HashSet;
+use crate::config::DEFAULT_KEYMAP;
 
+// Host keys are named as sdl names them, such as "Q", "Up" or "Space",
+// so frontends without sdl can share the keymap
 pub struct Keypad {
     pub keypad: [bool; 16],
     pub key_pressed: bool,
-    keys: HashSet<Keycode>,
+    keys: Vec<String>, // Names of the host keys currently held
     pub key_held: bool,
     pub key_index: usize,
+    keymap: Vec<(String, usize)>, // Host keys read as Chip 8 keys
+    key_hints: Vec<(String, usize)>, // Extra host keys mapped to Chip 8 keys for a game
 }
 
 impl Keypad {
@@ -18,10 +20,47 @@ impl Keypad {
         Self {
             keypad: [false; 16],
             key_pressed: false,
-            keys: HashSet::new(),
+            keys: Vec::new(),
             key_held: false,
             key_index: 0,
+            keymap: DEFAULT_KEYMAP.iter()
+                .map(|(key, name)| (String::from(*name), *key))
+                .collect(),
+            key_hints: Vec::new(),
+        }
+    }
+
+    // Replace the keymap with host key names
+    pub fn set_keymap(&mut self, keymap: &[(usize, String)]) {
+        self.keymap = keymap.iter()
+            .map(|(chip8_key, name)| (name.clone(), *chip8_key))
+            .collect();
+    }
+
+    // Host key names in the keymap, for frontends to check they can read them
+    #[cfg(feature = "sdl")]
+    pub fn mapped_keys(&self) -> impl Iterator<Item = &str> {
+        self.keymap.iter().map(|(name, _)| name.as_str())
+    }
+
+    // Map a chip-8-database key hint (up, down, left, right, a, b) onto the
+    // arrow keys, space and left shift, returning false for unknown hints
+    pub fn set_key_hint(&mut self, hint: &str, chip8_key: usize) -> bool {
+        let name = match hint {
+            "up" => "Up",
+            "down" => "Down",
+            "left" => "Left",
+            "right" => "Right",
+            "a" => "Space",
+            "b" => "Left Shift",
+            _ => return false,
+        };
+        if chip8_key > 0xF {
+            return false;
         }
+        self.key_hints.retain(|(key, _)| key != name);
+        self.key_hints.push((String::from(name), chip8_key));
+        true
     }
 
     pub fn reset_keypad(&mut self) {
@@ -29,32 +68,17 @@ impl Keypad {
         self.key_pressed = false;
     }
 
-    pub fn update_keys(&mut self, pressed_keys: HashSet<Keycode>) {
+    pub fn update_keys(&mut self, pressed_keys: Vec<String>) {
         self.keys = pressed_keys;
     }
 
     pub fn update_keypad(&mut self) {
         for key in &self.keys {
-            // Match each key and return the Chip 8 hex value
-            let hex_key: Option<usize> = match key {
-                Keycode::Num1 => Some(0x1),
-                Keycode::Num2 => Some(0x2),
-                Keycode::Num3 => Some(0x3),
-                Keycode::Num4 => Some(0xC),
-                Keycode::Q    => Some(0x4),
-                Keycode::W    => Some(0x5),
-                Keycode::E    => Some(0x6),
-                Keycode::R    => Some(0xD),
-                Keycode::A    => Some(0x7),
-                Keycode::S    => Some(0x8),
-                Keycode::D    => Some(0x9),
-                Keycode::F    => Some(0xE),
-                Keycode::Z    => Some(0xA),
-                Keycode::X    => Some(0x0),
-                Keycode::C    => Some(0xB),
-                Keycode::V    => Some(0xF),
-                _ => None,
-            };
+            // Look up each key in the keymap, then the game's key hints
+            let hex_key: Option<usize> = self.keymap.iter()
+                .chain(self.key_hints.iter())
+                .find(|(mapped, _)| mapped.eq_ignore_ascii_case(key))
+                .map(|(_, chip8_key)| *chip8_key);
     
             // If valid key, set keypad[hexvalue] = true and key_pressed = true
             if let Some(i) = hex_key {
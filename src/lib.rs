@@ -0,0 +1,474 @@
+//Module Todo:
+// N/A
+
+#[cfg(feature = "sdl")]
+extern crate sdl2;
+
+use std::{
+    path::PathBuf,
+    process::{self, ExitCode},
+};
+
+mod cpu;
+pub mod cartridge;
+pub mod ram;
+#[cfg(feature = "sdl")]
+mod display;
+mod input;
+pub mod args;
+pub mod quirks;
+mod screenshot;
+mod recorder;
+mod scheduler;
+mod machine;
+mod watcher;
+mod palette;
+mod database;
+mod octo;
+pub mod config;
+#[cfg(feature = "sdl")]
+mod font;
+#[cfg(feature = "sdl")]
+mod osd;
+pub mod disasm;
+mod debugger;
+#[cfg(feature = "sdl")]
+mod panels;
+mod gdb;
+mod frame;
+#[cfg(feature = "sdl")]
+mod window;
+pub mod tui;
+mod trace;
+mod diff;
+mod profile;
+mod coverage;
+pub mod symbols;
+pub mod analyze;
+mod smc;
+mod lint;
+
+use args::Args;
+use recorder::Recorder;
+use scheduler::Scheduler;
+use machine::Machine;
+use database::Database;
+use config::{ConfigFile, Settings};
+use gdb::GdbServer;
+use trace::{TraceFilter, Tracer};
+use profile::Profiler;
+use coverage::Coverage;
+use symbols::Symbols;
+use smc::SmcDetector;
+use lint::{Lint, LintTracer};
+
+pub const ROM_START: usize = 0x200; //0x200 = 512
+pub const CHIP8_WIDTH: u32 = 64;
+pub const CHIP8_HEIGHT: u32 = 32;
+
+#[cfg(feature = "sdl")]
+pub fn run_window(args: &Args, settings: Settings) -> ExitCode {
+    window::run(args, settings)
+}
+
+#[cfg(not(feature = "sdl"))]
+pub fn run_window(_args: &Args, _settings: Settings) -> ExitCode {
+    eprintln!("Built without the sdl feature, use --tui or --headless");
+    ExitCode::FAILURE
+}
+
+// Start a recording, reporting failures rather than stopping emulation
+fn start_recording(path: &str) -> Option<Recorder> {
+    match Recorder::new(path) {
+        Ok(recorder) => {
+            eprintln!("Recording to {}", path);
+            Some(recorder)
+        },
+        Err(e) => {
+            eprintln!("Failed to start recording to {}: {}", path, e);
+            None
+        },
+    }
+}
+
+// Start an instruction trace, reporting failures rather than stopping emulation
+fn start_trace(path: &str, filter: TraceFilter) -> Option<Tracer> {
+    match Tracer::new(path, filter) {
+        Ok(tracer) => {
+            eprintln!("Tracing to {}", path);
+            Some(tracer)
+        },
+        Err(e) => {
+            eprintln!("Failed to start trace to {}: {}", path, e);
+            None
+        },
+    }
+}
+
+// Flush the trace before exiting, returning whether it was written in full
+fn finish_trace(machine: &mut Machine) -> bool {
+    match machine.tracer.take().map(Tracer::finish) {
+        Some(Err(e)) => {
+            eprintln!("Failed to finish trace: {}", e);
+            false
+        },
+        _ => true,
+    }
+}
+
+// Start the gdb server, reporting failures rather than stopping emulation
+fn start_gdb(port: u16) -> Option<GdbServer> {
+    match GdbServer::new(port) {
+        Ok(server) => {
+            eprintln!("Gdb server listening on localhost:{}", server.port());
+            Some(server)
+        },
+        Err(e) => {
+            eprintln!("Failed to start gdb server on port {}: {}", port, e);
+            None
+        },
+    }
+}
+
+// Write the profiler and coverage reports, returning a message for the frontend to show
+fn write_reports(machine: &Machine) -> String {
+    let mut messages = Vec::new();
+    if let Some(profiler) = &machine.profiler {
+        messages.push(match profiler.write_reports(&machine.ram.mem, &machine.symbols) {
+            Ok(()) => format!("Profile written to {}.txt", profiler.prefix()),
+            Err(e) => format!("Failed to write profile: {}", e),
+        });
+    }
+    if let Some(coverage) = &machine.coverage {
+        let cartridge = &machine.cartridge;
+        messages.push(match coverage.write_reports(&machine.ram.mem, &machine.symbols,
+            cartridge.path(), cartridge.len()) {
+            Ok(()) => format!("Coverage written to {}.asm", coverage.prefix()),
+            Err(e) => format!("Failed to write coverage: {}", e),
+        });
+    }
+    if messages.is_empty() {
+        return String::from("No reports, start with --profile or --coverage");
+    }
+    messages.join(", ")
+}
+
+fn stop_recording(recorder: Recorder) {
+    let frames = recorder.frame_count();
+    match recorder.finish() {
+        Ok(()) => eprintln!("Recording stopped after {} frames", frames),
+        Err(e) => eprintln!("Failed to finish recording: {}", e),
+    }
+}
+
+// Resolve settings from defaults, then the config file, then command line flags
+pub fn load_settings(args: &Args) -> Settings {
+    let mut settings = Settings::new();
+
+    // A missing default config file is fine, a missing --config file is not
+    let path = match &args.config_path {
+        Some(path) => Some(PathBuf::from(path)),
+        None => ConfigFile::default_path().filter(|path| path.exists()),
+    };
+    if let Some(path) = path {
+        let loaded = ConfigFile::load(&path)
+            .and_then(|config| config.apply(&path, &mut settings));
+        if let Err(e) = loaded {
+            eprintln!("Failed to load config {}", e);
+            process::exit(1);
+        }
+    }
+
+    args.apply(&mut settings);
+    settings
+}
+
+// Open the rom database, reporting problems and falling back to the bundled copy
+fn open_database(settings: &Settings) -> Database {
+    let mut database = match &settings.database_dir {
+        Some(dir) => Database::load_dir(dir.as_ref()).unwrap_or_else(|e| {
+            eprintln!("Failed to load rom database {}, using bundled copy", e);
+            Database::bundled()
+        }),
+        None => Database::bundled(),
+    };
+
+    if let Some(path) = &settings.overrides_path {
+        if let Err(e) = database.load_overrides(path.as_ref()) {
+            eprintln!("Failed to load rom overrides {}", e);
+        }
+    }
+    database
+}
+
+// Layer settings embedded in the rom and from the rom database, then reapply
+// command line flags so they still take priority
+fn apply_rom_settings(args: &Args, machine: &mut Machine, settings: &mut Settings) -> String {
+    // Settings embedded in the rom file come first, the database can refine them
+    let sha1 = database::sha1_hex(machine.cartridge.rom());
+    let embedded = machine.cartridge.embedded_settings.take();
+    let game = settings.use_database
+        .then(|| open_database(settings).lookup(&sha1))
+        .flatten();
+    for game in embedded.iter().chain(game.iter()) {
+        eprintln!("Applying settings for {} ({})", game.title, game.platform_id);
+        if let Some(platform) = game.platform {
+            machine.cartridge.platform = platform;
+        }
+        game.apply_quirks(&mut settings.quirks);
+        if let Some(tickrate) = game.tickrate {
+            settings.clock_hz = tickrate.saturating_mul(scheduler::FRAME_RATE as u32)
+                .clamp(1, scheduler::MAX_CLOCK_HZ);
+        }
+        if let Some(palette) = game.palette {
+            settings.palette = palette;
+        }
+        for (hint, key) in &game.keys {
+            machine.keypad.set_key_hint(hint, *key);
+        }
+    }
+
+    args.apply(settings);
+    sha1
+}
+
+// Build the machine, exiting with the load error if the rom is unusable
+fn load_machine(args: &Args, settings: &mut Settings) -> Machine {
+    let mut machine = match Machine::new(settings.quirks, &settings.rom_path) {
+        Ok(machine) => machine,
+        Err(e) => {
+            eprintln!("Failed to load {}: {}", settings.rom_path, e);
+            process::exit(1);
+        },
+    };
+
+    let sha1 = apply_rom_settings(args, &mut machine, settings);
+    machine.cpu.quirks = settings.quirks;
+    machine.keypad.set_keymap(&settings.keymap);
+    (machine.symbols, machine.symbols_path) = load_symbols(args.symbols_path.as_deref(), &settings.rom_path);
+    machine.tracer = args.trace_path.as_deref()
+        .and_then(|path| start_trace(path, args.trace_filter.clone()));
+    machine.profiler = args.profile_prefix.as_deref().map(Profiler::new);
+    machine.coverage = args.coverage_prefix.as_deref().map(Coverage::new);
+    machine.smc = args.smc_mode.map(SmcDetector::new);
+
+    eprintln!("Loaded {} ({} bytes, {}, sha1 {})", settings.rom_path,
+        machine.cartridge.len(), machine.cartridge.platform.name(), sha1);
+    machine
+}
+
+// Load the given symbol file, or the one beside the rom if there is one, along with
+// its path for reloading
+// A symbol file given on the command line must load, one found beside the rom may fail
+fn load_symbols(path: Option<&str>, rom_path: &str) -> (Symbols, Option<PathBuf>) {
+    let (path, required) = match path {
+        Some(path) => (PathBuf::from(path), true),
+        None => match symbols::default_path(rom_path) {
+            Some(path) => (path, false),
+            None => return (Symbols::default(), None),
+        },
+    };
+    match Symbols::load(&path) {
+        Ok(symbols) => {
+            eprintln!("Loaded {} symbols from {}", symbols.len(), path.display());
+            (symbols, Some(path))
+        },
+        Err(e) => {
+            eprintln!("Failed to load symbols {}: {}", path.display(), e);
+            if required {
+                process::exit(1);
+            }
+            (Symbols::default(), Some(path))
+        },
+    }
+}
+
+// Resolve --break labels and addresses against the loaded symbols
+fn resolve_breakpoints(args: &Args, machine: &Machine) -> Vec<usize> {
+    args.breakpoints.iter()
+        .map(|text| machine.symbols.resolve(text).unwrap_or_else(|| {
+            eprintln!("Unknown breakpoint {}, use a label or hex address", text);
+            process::exit(1);
+        }))
+        .collect()
+}
+
+// Print the effective settings for the rom as a config file
+pub fn print_config(args: &Args, mut settings: Settings) {
+    match Machine::new(settings.quirks, &settings.rom_path) {
+        Ok(mut machine) => {
+            apply_rom_settings(args, &mut machine, &mut settings);
+        },
+        Err(e) => {
+            println!("# Rom database settings not included, failed to load {}: {}",
+                settings.rom_path, e);
+        },
+    }
+    print!("{}", ConfigFile::from_settings(&settings).to_toml());
+}
+
+// Run without a window for a fixed number of frames, recording vram if requested
+pub fn run_headless(args: &Args, mut settings: Settings, frames: u64) -> ExitCode {
+    let mut machine = load_machine(args, &mut settings);
+    let mut coloured = [0; CHIP8_WIDTH as usize * CHIP8_HEIGHT as usize * 4];
+
+    let mut recorder = args.record_path.as_deref().and_then(start_recording);
+
+    let mut scheduler = Scheduler::new(settings.clock_hz);
+
+    // Headless runs as fast as possible while keeping the per frame tick count
+    for frame in 0..frames {
+        let ticks = scheduler.ticks_for_frame();
+        machine.run_frame(ticks);
+        for report in machine.take_smc_reports() {
+            eprintln!("{}", report);
+        }
+        if let Some(fault) = machine.cpu.fault() {
+            eprintln!("Stopped in frame {}: {}", frame, fault);
+            break;
+        }
+
+        if let Some(rec) = &mut recorder {
+            settings.palette.apply(&machine.cpu.vram, &mut coloured);
+            if let Err(e) = rec.add_frame(&coloured) {
+                eprintln!("Failed to record frame: {}", e);
+                recorder = None;
+            }
+        }
+    }
+
+    if let Some(rec) = recorder {
+        stop_recording(rec);
+    }
+    if machine.profiler.is_some() || machine.coverage.is_some() {
+        eprintln!("{}", write_reports(&machine));
+    }
+    let traced = finish_trace(&mut machine);
+    if traced && machine.cpu.fault().is_none() { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
+
+// Run the rom twice in lockstep, the second time with the --diff-quirk overrides and
+// both pressing the --diff-key keys, and report the first instruction after which the
+// two machines differ
+pub fn run_diff(args: &Args, mut settings: Settings, frames: u64) -> ExitCode {
+    let mut machine = load_machine(args, &mut settings);
+    let mut quirks = settings.quirks;
+    for (name, value) in &args.diff_quirk_overrides {
+        quirks.set(name, *value);
+    }
+    let mut other = match Machine::new(quirks, &settings.rom_path) {
+        Ok(other) => other,
+        Err(e) => {
+            eprintln!("Failed to load {}: {}", settings.rom_path, e);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let changed: Vec<String> = args.diff_quirk_overrides.iter()
+        .map(|(name, value)| format!("{}={}", name, if *value { "on" } else { "off" }))
+        .collect();
+    let names = [String::from("Configured run"), format!("Run with {}", changed.join(" "))];
+    let mut scheduler = Scheduler::new(settings.clock_hz);
+    let machines = [&mut machine, &mut other];
+    let divergence = diff::lockstep(machines, names, &args.diff_keys, &mut scheduler, frames);
+    let traced = finish_trace(&mut machine);
+    match divergence {
+        Some(divergence) => {
+            print!("{}", divergence);
+            ExitCode::FAILURE
+        },
+        None => {
+            println!("No divergence in {} frames ({} instructions)", frames, machine.cycles);
+            if traced { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+        },
+    }
+}
+
+// Report platform compatibility problems found statically, and during a headless run
+// when a frame count is given, failing if there are any
+pub fn run_lint(args: &Args, mut settings: Settings, frames: Option<u64>) -> ExitCode {
+    let mut machine = load_machine(args, &mut settings);
+    let mut lint = Lint::new(machine.cartridge.platform);
+    lint.check_rom(&machine.ram.mem, machine.cartridge.len());
+
+    if let Some(frames) = frames {
+        machine.lint = Some(LintTracer::new(lint.target));
+        let mut scheduler = Scheduler::new(settings.clock_hz);
+        for frame in 0..frames {
+            let ticks = scheduler.ticks_for_frame();
+            machine.run_frame(ticks);
+            if let Some(fault) = machine.cpu.fault() {
+                eprintln!("Stopped in frame {}: {}", frame, fault);
+                break;
+            }
+        }
+        if let Some(tracer) = machine.lint.take() {
+            lint.merge(tracer);
+        }
+    }
+
+    print!("{}", lint.report(&machine.symbols));
+    let clean = lint.is_empty() && machine.cpu.fault().is_none();
+    if finish_trace(&mut machine) && clean { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
+
+// Compare two trace files and report the first record where they differ
+pub fn diff_traces(args: &Args, a: &str, b: &str) -> ExitCode {
+    let read = |path: &str| trace::read_trace(path).map_err(|e| {
+        eprintln!("Failed to read trace {}: {}", path, e);
+    });
+    let (Ok(trace_a), Ok(trace_b)) = (read(a), read(b)) else {
+        return ExitCode::FAILURE;
+    };
+    let symbols = args.symbols_path.as_deref()
+        .map_or_else(Symbols::default, |path| load_symbols(Some(path), "").0);
+    match diff::compare_traces([&trace_a, &trace_b], [a.to_string(), b.to_string()], &symbols) {
+        Some(divergence) => {
+            print!("{}", divergence);
+            ExitCode::FAILURE
+        },
+        None => {
+            println!("No divergence in {} records", trace_a.len());
+            ExitCode::SUCCESS
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::quirks::Platform;
+
+    #[test]
+    fn database_settings_apply_under_command_line_flags() {
+        let dir = std::env::temp_dir();
+        let rom = [0x12, 0x00];
+        let rom_path = dir.join("chip_8_lib_database.ch8");
+        fs::write(&rom_path, rom).unwrap();
+        let overrides_path = dir.join("chip_8_lib_overrides.json");
+        fs::write(&overrides_path, format!(r##"{{"{}": {{"platforms": ["superchip"], "tickrate": 20,
+            "quirkyPlatforms": {{"superchip": {{"wrap": true}}}},
+            "colors": {{"pixels": ["#102030", "#405060"]}}}}}}"##, database::sha1_hex(&rom))).unwrap();
+
+        let apply = |flags: &[&str]| {
+            let mut settings = Settings::new();
+            settings.rom_path = rom_path.to_str().unwrap().to_string();
+            settings.overrides_path = Some(overrides_path.to_str().unwrap().to_string());
+            let mut machine = Machine::new(settings.quirks, &settings.rom_path).unwrap();
+            let args = Args::parse_from(flags.iter().map(|flag| flag.to_string()));
+            apply_rom_settings(&args, &mut machine, &mut settings);
+            (machine.cartridge.platform, settings)
+        };
+
+        let (platform, settings) = apply(&[]);
+        assert_eq!(platform, Platform::SuperChip);
+        assert_eq!((settings.quirks.get("shift"), settings.quirks.get("wrap")), (Some(true), Some(true)));
+        assert_eq!(settings.clock_hz, 20 * scheduler::FRAME_RATE as u32);
+        assert_eq!((settings.palette.background, settings.palette.foreground),
+            ([0x10, 0x20, 0x30], [0x40, 0x50, 0x60]));
+
+        let (_, settings) = apply(&["--clock", "900", "--quirk", "wrap=off"]);
+        assert_eq!((settings.clock_hz, settings.quirks.get("wrap")), (900, Some(false)));
+    }
+}
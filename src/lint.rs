@@ -0,0 +1,532 @@
+//Module Todo:
+// N/A
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+};
+
+use crate::{
+    analyze::{self, Analysis, Flow},
+    disasm,
+    quirks::{Platform, Quirks},
+    symbols::Symbols,
+    trace::Registers,
+    CHIP8_HEIGHT,
+    CHIP8_WIDTH,
+};
+
+const LOOKAHEAD: usize = 16; // Instructions followed looking for a use of vf or i
+const VF: u16 = 1 << 0xF;
+const I_LIMIT: usize = 0xFFF; // Highest address of a 4K platform
+
+// Kinds of compatibility problem, in report order
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Rule {
+    Opcode, // Needs a later platform
+    Shift, // 8xy6 and 8xye with x and y different
+    LogicVf, // Vf read after 8xy1, 8xy2 or 8xy3
+    Jump, // Bnnn with a non zero x
+    MemoryI, // I used after fx55 or fx65
+    SpriteEdge, // A sprite crossing the screen edge
+    IndexOverflow, // Fx1e moving i past FFF
+}
+
+impl Rule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::Opcode => "opcode",
+            Rule::Shift => "shift",
+            Rule::LogicVf => "logic-vf",
+            Rule::Jump => "jump",
+            Rule::MemoryI => "memory-i",
+            Rule::SpriteEdge => "sprite-edge",
+            Rule::IndexOverflow => "index-overflow",
+        }
+    }
+
+    // How a platform's quirks decide the behaviour, None when no quirk does
+    fn behaviour(&self, quirks: &Quirks) -> Option<u8> {
+        match self {
+            Rule::Shift => Some(quirks.shift_vx as u8),
+            Rule::LogicVf => Some(quirks.vf_reset as u8),
+            Rule::Jump => Some(quirks.jump_vx as u8),
+            Rule::MemoryI => Some(match (quirks.memory_leave_i, quirks.memory_increment_by_x) {
+                (true, _) => 0,
+                (false, true) => 1,
+                (false, false) => 2,
+            }),
+            Rule::SpriteEdge => Some(quirks.wrap_sprites as u8),
+            Rule::Opcode | Rule::IndexOverflow => None,
+        }
+    }
+
+    // Platforms that run the rom differently from the one it targets
+    // Past FFF, 4K platforms disagree on wrapping i and setting vf
+    fn affected(&self, target: Platform, opcode: u16) -> Vec<Platform> {
+        match self {
+            Rule::Opcode => {
+                let required = analyze::required_platform(opcode).unwrap_or(Platform::XoChip);
+                Platform::ALL.into_iter().filter(|&platform| platform < required).collect()
+            },
+            Rule::IndexOverflow => vec![Platform::Chip8, Platform::SuperChip],
+            _ => {
+                let expected = self.behaviour(&Quirks::for_platform(target));
+                Platform::ALL.into_iter()
+                    .filter(|&platform| self.behaviour(&Quirks::for_platform(platform)) != expected)
+                    .collect()
+            },
+        }
+    }
+}
+
+pub struct Finding {
+    pub addr: usize,
+    pub rule: Rule,
+    pub opcode: u16,
+    pub message: String,
+    pub affected: Vec<Platform>,
+    pub seen_at_runtime: bool,
+}
+
+// Mask of v registers from x to y, in either order
+fn register_range(x: u16, y: u16) -> u16 {
+    (x.min(y)..=x.max(y)).fold(0, |mask, v| mask | 1 << v)
+}
+
+// V registers an instruction reads, one bit per register
+fn reads_v(opcode: u16) -> u16 {
+    let (x, y) = ((opcode >> 8) & 0xF, (opcode >> 4) & 0xF);
+    match (opcode >> 12, opcode & 0xF, opcode & 0xFF) {
+        (0x3 | 0x4 | 0x7 | 0xE, _, _) => 1 << x,
+        (0x5, 0x2 | 0x3, _) => register_range(x, y),
+        (0x5 | 0x9 | 0xD, _, _) => 1 << x | 1 << y,
+        (0x8, 0x0, _) => 1 << y,
+        (0x8, _, _) => 1 << x | 1 << y,
+        (0xB, _, _) => 1 | 1 << x,
+        (0xF, _, 0x15 | 0x18 | 0x1E | 0x29 | 0x30 | 0x33 | 0x3A) => 1 << x,
+        (0xF, _, 0x55 | 0x75) => register_range(0, x),
+        _ => 0,
+    }
+}
+
+// V registers an instruction writes, including vf set as a flag
+fn writes_v(opcode: u16) -> u16 {
+    let (x, y) = ((opcode >> 8) & 0xF, (opcode >> 4) & 0xF);
+    match (opcode >> 12, opcode & 0xF, opcode & 0xFF) {
+        (0x5, 0x3, _) => register_range(x, y),
+        (0x6 | 0x7 | 0xC, _, _) => 1 << x,
+        (0x8, 0x0..=0x3, _) => 1 << x,
+        (0x8, _, _) => 1 << x | VF,
+        (0xD, _, _) => VF,
+        (0xF, _, 0x07 | 0x0A) => 1 << x,
+        (0xF, _, 0x65 | 0x85) => register_range(0, x),
+        _ => 0,
+    }
+}
+
+// Whether an instruction reads memory at i or moves i relative to itself
+fn uses_i(opcode: u16) -> bool {
+    matches!((opcode >> 12, opcode & 0xF, opcode & 0xFF),
+        (0x5, 0x2 | 0x3, _) | (0xD, _, _) | (0xF, _, 0x1E | 0x33 | 0x55 | 0x65))
+        || opcode == 0xF002
+}
+
+fn sets_i(opcode: u16) -> bool {
+    opcode >> 12 == 0xA || opcode == 0xF000
+        || matches!((opcode >> 12, opcode & 0xFF), (0xF, 0x29 | 0x30))
+}
+
+fn is_logic(opcode: u16) -> bool {
+    opcode >> 12 == 0x8 && matches!(opcode & 0xF, 0x1..=0x3)
+}
+
+fn is_memory(opcode: u16) -> bool {
+    opcode >> 12 == 0xF && matches!(opcode & 0xFF, 0x55 | 0x65)
+}
+
+// Sprite size in bytes wide and rows high
+fn sprite_size(opcode: u16, platform: Platform) -> (usize, usize) {
+    match (opcode & 0xF) as usize {
+        0 if platform > Platform::Chip8 => (2, 16),
+        n => (1, n),
+    }
+}
+
+// Whether a sprite drawn at vx, vy reaches past the right or bottom of the screen
+fn crosses_edge(opcode: u16, platform: Platform, vx: u8, vy: u8) -> bool {
+    let (width, height) = sprite_size(opcode, platform);
+    let x = vx as usize % CHIP8_WIDTH as usize;
+    let y = vy as usize % CHIP8_HEIGHT as usize;
+    height > 0 && (x + width * 8 > CHIP8_WIDTH as usize || y + height > CHIP8_HEIGHT as usize)
+}
+
+// Problems visible from the instruction alone
+fn check_instruction(opcode: u16) -> Option<(Rule, String)> {
+    let (x, y) = ((opcode >> 8) & 0xF, (opcode >> 4) & 0xF);
+    let platform = analyze::required_platform(opcode);
+    match (opcode >> 12, opcode & 0xF) {
+        _ if platform.is_some_and(|platform| platform > Platform::Chip8) => {
+            let platform = platform.expect("checked above");
+            Some((Rule::Opcode, format!("Needs {}", platform.name())))
+        },
+        (0x8, 0x6 | 0xE) if x != y => Some((Rule::Shift,
+            format!("Shifts V{:X} into V{:X}, or V{:X} in place", y, x, x))),
+        (0xB, _) if x != 0 => Some((Rule::Jump,
+            format!("Jumps by V0, or by V{:X}", x))),
+        _ => None,
+    }
+}
+
+// Lint findings keyed by address and rule, from a static pass and optionally from a run
+pub struct Lint {
+    pub target: Platform,
+    findings: BTreeMap<(usize, Rule), Finding>,
+}
+
+impl Lint {
+    pub fn new(target: Platform) -> Self {
+        Self {
+            target,
+            findings: BTreeMap::new(),
+        }
+    }
+
+    fn add(&mut self, addr: usize, opcode: u16, rule: Rule, message: String, runtime: bool) {
+        let affected = rule.affected(self.target, opcode);
+        if affected.is_empty() {
+            return;
+        }
+        let finding = self.findings.entry((addr, rule)).or_insert(Finding {
+            addr,
+            rule,
+            opcode,
+            message,
+            affected,
+            seen_at_runtime: false,
+        });
+        finding.seen_at_runtime |= runtime;
+    }
+
+    // Check every instruction reachable by static analysis
+    pub fn check_rom(&mut self, mem: &[u8], rom_len: usize) {
+        let analysis = analyze::analyze(mem, rom_len, self.target);
+        for instruction in analysis.instructions.values() {
+            let (addr, opcode) = (instruction.addr, instruction.opcode);
+            if let Some((rule, message)) = check_instruction(opcode) {
+                self.add(addr, opcode, rule, message, false);
+            }
+            if is_logic(opcode) {
+                if let Some(at) = find_after(&analysis, addr, |op| reads_v(op) & VF != 0,
+                    |op| writes_v(op) & VF != 0) {
+                    self.add(addr, opcode, Rule::LogicVf,
+                        format!("VF read at {:03X}, after VF is reset or left as it was", at), false);
+                }
+            }
+            if is_memory(opcode) {
+                if let Some(at) = find_after(&analysis, addr, uses_i, sets_i) {
+                    self.add(addr, opcode, Rule::MemoryI,
+                        format!("I used at {:03X}, after I is left or moved past the registers", at),
+                        false);
+                }
+            }
+        }
+        self.check_constants(&analysis);
+    }
+
+    // Sprite edges and i overflow where a block loads the values as constants
+    fn check_constants(&mut self, analysis: &Analysis) {
+        for block in &analysis.blocks {
+            let mut v: [Option<u8>; 16] = [None; 16];
+            let mut i: Option<usize> = None;
+            for instruction in analysis.instructions.range(block.start..block.end).map(|(_, ins)| ins) {
+                let opcode = instruction.opcode;
+                let (x, y) = (((opcode >> 8) & 0xF) as usize, ((opcode >> 4) & 0xF) as usize);
+                match opcode >> 12 {
+                    0xD => if let (Some(vx), Some(vy)) = (v[x], v[y]) {
+                        if crosses_edge(opcode, self.target, vx, vy) {
+                            self.add(instruction.addr, opcode, Rule::SpriteEdge,
+                                format!("Draws at {},{} across the screen edge, clipped or wrapped", vx, vy),
+                                false);
+                        }
+                    },
+                    0xF if opcode & 0xFF == 0x1E => if let (Some(i), Some(vx)) = (i, v[x]) {
+                        if i + vx as usize > I_LIMIT {
+                            self.add(instruction.addr, opcode, Rule::IndexOverflow,
+                                format!("Moves I from {:03X} to {:X}", i, i + vx as usize), false);
+                        }
+                    },
+                    _ => {},
+                }
+
+                let written = writes_v(opcode);
+                let constant = match opcode >> 12 {
+                    0x6 => Some((opcode & 0xFF) as u8),
+                    0x7 => v[x].map(|vx| vx.wrapping_add((opcode & 0xFF) as u8)),
+                    _ => None,
+                };
+                for (register, value) in v.iter_mut().enumerate() {
+                    if written & 1 << register != 0 {
+                        *value = if register == x { constant } else { None };
+                    }
+                }
+                i = match opcode >> 12 {
+                    0xA => Some((opcode & 0x0FFF) as usize),
+                    0xF if opcode & 0xFF == 0x1E => i.zip(v[x]).map(|(i, vx)| i + vx as usize),
+                    _ if sets_i(opcode) || is_memory(opcode) => None,
+                    _ => i,
+                };
+            }
+        }
+    }
+
+    // Findings from a run, sorted with the static ones
+    pub fn merge(&mut self, tracer: LintTracer) {
+        for finding in tracer.lint.findings.into_values() {
+            self.add(finding.addr, finding.opcode, finding.rule, finding.message, true);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    pub fn report(&self, symbols: &Symbols) -> String {
+        let mut text = format!("Compatibility of a {} rom, {} findings\n", self.target.name(),
+            self.findings.len());
+        for finding in self.findings.values() {
+            let affected: Vec<&str> = finding.affected.iter().map(Platform::name).collect();
+            let _ = writeln!(text, "  {:03X}  {:04X}  {:<16} {:<14} {}{}\n        affects {}",
+                finding.addr, finding.opcode, disasm::disassemble_with(finding.opcode, symbols),
+                finding.rule.name(), finding.message,
+                if finding.seen_at_runtime { " (seen at runtime)" } else { "" },
+                affected.join(", "));
+        }
+        text
+    }
+}
+
+// The first instruction within LOOKAHEAD of addr matching wanted, following fall through,
+// skips and jumps, and stopping at calls, returns or an instruction matching stop
+fn find_after(analysis: &Analysis, addr: usize, wanted: impl Fn(u16) -> bool,
+    stop: impl Fn(u16) -> bool) -> Option<usize> {
+    let mut instruction = analysis.instructions.get(&addr)?;
+    for _ in 0..LOOKAHEAD {
+        let next = match instruction.flow {
+            Flow::Next | Flow::Skip => instruction.addr + instruction.len,
+            Flow::Jump(target) => target,
+            _ => return None,
+        };
+        instruction = analysis.instructions.get(&next)?;
+        if wanted(instruction.opcode) {
+            return Some(instruction.addr);
+        }
+        if stop(instruction.opcode) {
+            return None;
+        }
+    }
+    None
+}
+
+// Checks instructions as they run, using the register values before each one
+pub struct LintTracer {
+    lint: Lint,
+    logic_at: Option<(usize, u16)>, // Last logic op whose vf may still be read
+    memory_at: Option<(usize, u16)>, // Last fx55 or fx65 whose i may still be used
+}
+
+impl LintTracer {
+    pub fn new(target: Platform) -> Self {
+        Self {
+            lint: Lint::new(target),
+            logic_at: None,
+            memory_at: None,
+        }
+    }
+
+    pub fn record(&mut self, pc: usize, opcode: u16, before: &Registers) {
+        let x = ((opcode >> 8) & 0xF) as usize;
+        let y = ((opcode >> 4) & 0xF) as usize;
+        if let Some((rule, message)) = check_instruction(opcode) {
+            self.lint.add(pc, opcode, rule, message, true);
+        }
+
+        if let Some((at, logic)) = self.logic_at {
+            if reads_v(opcode) & VF != 0 {
+                self.lint.add(at, logic, Rule::LogicVf,
+                    format!("VF read at {:03X}, after VF is reset or left as it was", pc), true);
+            }
+            if reads_v(opcode) & VF != 0 || writes_v(opcode) & VF != 0 {
+                self.logic_at = None;
+            }
+        }
+        if let Some((at, memory)) = self.memory_at {
+            if uses_i(opcode) {
+                self.lint.add(at, memory, Rule::MemoryI,
+                    format!("I used at {:03X}, after I is left or moved past the registers", pc), true);
+            }
+            if uses_i(opcode) || sets_i(opcode) {
+                self.memory_at = None;
+            }
+        }
+        if is_logic(opcode) {
+            self.logic_at = Some((pc, opcode));
+        }
+        if is_memory(opcode) {
+            self.memory_at = Some((pc, opcode));
+        }
+
+        match opcode >> 12 {
+            0xD if crosses_edge(opcode, self.lint.target, before.v[x], before.v[y]) => {
+                self.lint.add(pc, opcode, Rule::SpriteEdge,
+                    format!("Draws at {},{} across the screen edge, clipped or wrapped",
+                        before.v[x], before.v[y]), true);
+            },
+            0xF if opcode & 0xFF == 0x1E && before.i + before.v[x] as usize > I_LIMIT => {
+                self.lint.add(pc, opcode, Rule::IndexOverflow,
+                    format!("Moves I from {:03X} to {:X}", before.i, before.i + before.v[x] as usize),
+                    true);
+            },
+            _ => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        machine::Machine,
+        ROM_START,
+    };
+
+    fn checked(rom: &[u8], target: Platform) -> Lint {
+        let mut mem = vec![0; 0x1000];
+        mem[ROM_START..ROM_START + rom.len()].copy_from_slice(rom);
+        let mut lint = Lint::new(target);
+        lint.check_rom(&mem, rom.len());
+        lint
+    }
+
+    // Address, rule and affected platforms of each finding
+    fn found(lint: &Lint) -> Vec<(usize, Rule, Vec<Platform>)> {
+        lint.findings.values()
+            .map(|finding| (finding.addr, finding.rule, finding.affected.clone()))
+            .collect()
+    }
+
+    fn message(lint: &Lint, addr: usize) -> &str {
+        &lint.findings.values().find(|finding| finding.addr == addr).unwrap().message
+    }
+
+    #[test]
+    fn opcodes_from_later_platforms() {
+        // hires then halt
+        let lint = checked(&[0x00, 0xFF, 0x12, 0x02], Platform::Chip8);
+        assert_eq!(found(&lint), [(0x200, Rule::Opcode, vec![Platform::Chip8])]);
+        assert_eq!(message(&lint, 0x200), "Needs SUPER-CHIP");
+
+        // Every platform before the one the opcode needs is affected, whatever the target
+        let lint = checked(&[0xF0, 0x02, 0x12, 0x02], Platform::SuperChip);
+        let affected = vec![Platform::Chip8, Platform::SuperChip];
+        assert_eq!(found(&lint), [(0x200, Rule::Opcode, affected)]);
+    }
+
+    #[test]
+    fn shifts_between_two_registers() {
+        // v0 := v1 >> 1, v2 >>= 1 in place, halt
+        let lint = checked(&[0x80, 0x16, 0x82, 0x26, 0x12, 0x04], Platform::Chip8);
+        assert_eq!(found(&lint), [(0x200, Rule::Shift, vec![Platform::SuperChip])]);
+        assert_eq!(message(&lint, 0x200), "Shifts V1 into V0, or V0 in place");
+    }
+
+    #[test]
+    fn vf_read_after_a_logic_op() {
+        // v0 |= v1, skip if vf == 0, halt
+        let lint = checked(&[0x80, 0x11, 0x3F, 0x00, 0x12, 0x04, 0x12, 0x06], Platform::Chip8);
+        let affected = vec![Platform::SuperChip, Platform::XoChip];
+        assert_eq!(found(&lint), [(0x200, Rule::LogicVf, affected)]);
+        assert_eq!(message(&lint, 0x200), "VF read at 202, after VF is reset or left as it was");
+
+        // Writing vf before reading it hides the difference
+        let lint = checked(&[0x80, 0x11, 0x6F, 0x00, 0x3F, 0x00, 0x12, 0x06], Platform::Chip8);
+        assert!(lint.is_empty());
+    }
+
+    #[test]
+    fn bnnn_with_a_register() {
+        // jump0 206 with v1 under the jump quirk, halt at 206
+        let lint = checked(&[0xB1, 0x06, 0x00, 0x00, 0x00, 0x00, 0x12, 0x06], Platform::Chip8);
+        assert_eq!(found(&lint), [(0x200, Rule::Jump, vec![Platform::SuperChip])]);
+        assert_eq!(message(&lint, 0x200), "Jumps by V0, or by V1");
+    }
+
+    #[test]
+    fn i_used_after_save_or_load() {
+        // save v1, then draw from i, halt
+        let lint = checked(&[0xF1, 0x55, 0xD0, 0x15, 0x12, 0x04], Platform::Chip8);
+        assert_eq!(found(&lint), [(0x200, Rule::MemoryI, vec![Platform::SuperChip])]);
+        assert_eq!(message(&lint, 0x200),
+            "I used at 202, after I is left or moved past the registers");
+
+        // Setting i again first doesn't depend on where it was left
+        let reloaded = [0xF1, 0x55, 0xA3, 0x00, 0xD0, 0x15, 0x12, 0x06];
+        assert!(checked(&reloaded, Platform::Chip8).is_empty());
+    }
+
+    #[test]
+    fn sprites_across_the_screen_edge() {
+        // v0 := 60, v1 := 0, draw 8 wide from x 60, halt
+        let lint = checked(&[0x60, 0x3C, 0x61, 0x00, 0xD0, 0x15, 0x12, 0x06], Platform::Chip8);
+        assert_eq!(found(&lint), [(0x204, Rule::SpriteEdge, vec![Platform::XoChip])]);
+        assert_eq!(message(&lint, 0x204),
+            "Draws at 60,0 across the screen edge, clipped or wrapped");
+
+        // Inside the screen is fine
+        let inside = [0x60, 0x38, 0x61, 0x00, 0xD0, 0x15, 0x12, 0x06];
+        assert!(checked(&inside, Platform::Chip8).is_empty());
+    }
+
+    #[test]
+    fn index_moved_past_4k() {
+        // i := FFE, v0 := 5, i += v0, halt
+        let lint = checked(&[0xAF, 0xFE, 0x60, 0x05, 0xF0, 0x1E, 0x12, 0x06], Platform::XoChip);
+        assert_eq!(found(&lint),
+            [(0x204, Rule::IndexOverflow, vec![Platform::Chip8, Platform::SuperChip])]);
+        assert_eq!(message(&lint, 0x204), "Moves I from FFE to 1003");
+    }
+
+    #[test]
+    fn runtime_pass_finds_what_constants_miss() {
+        let rom = [
+            0x60, 0x30, // 200 v0 := 0x30
+            0x12, 0x06, // 202 jump 206, so the static pass loses v0
+            0x00, 0x00, // 204 never reached
+            0x70, 0x0C, // 206 v0 += 12, now 60
+            0xD0, 0x15, // 208 sprite v0 v1 5 across the right edge
+            0x80, 0x12, // 20A v0 &= v1
+            0x3F, 0x00, // 20C skip if vf == 0
+            0x12, 0x0E, // 20E jump 20E
+            0x12, 0x10, // 210 jump 210
+        ];
+        let path = std::env::temp_dir().join("chip_8_lint_runtime.ch8");
+        std::fs::write(&path, rom).unwrap();
+        let mut machine = Machine::new(Quirks::new(), path.to_str().unwrap()).unwrap();
+        let mut lint = Lint::new(Platform::Chip8);
+        lint.check_rom(&machine.ram.mem, machine.cartridge.len());
+        let affected = vec![Platform::SuperChip, Platform::XoChip];
+        assert_eq!(found(&lint), [(0x20A, Rule::LogicVf, affected)]);
+
+        machine.lint = Some(LintTracer::new(Platform::Chip8));
+        machine.run_frame(16);
+        lint.merge(machine.lint.take().unwrap());
+        let seen: Vec<(usize, Rule, bool)> = lint.findings.values()
+            .map(|finding| (finding.addr, finding.rule, finding.seen_at_runtime))
+            .collect();
+        assert_eq!(seen, [(0x208, Rule::SpriteEdge, true), (0x20A, Rule::LogicVf, true)]);
+        assert_eq!(message(&lint, 0x208),
+            "Draws at 60,0 across the screen edge, clipped or wrapped");
+        let report = lint.report(&Symbols::default());
+        assert!(report.contains("(seen at runtime)\n        affects XO-CHIP\n"), "{}", report);
+    }
+}
@@ -0,0 +1,221 @@
+//Module Todo:
+// N/A
+
+use std::path::PathBuf;
+
+use crate::{
+    coverage::Coverage,
+    cpu::Cpu,
+    disasm,
+    ram::Ram,
+    cartridge::{Cartridge, RomError},
+    input::Keypad,
+    profile::Profiler,
+    quirks::Quirks,
+    smc::SmcDetector,
+    lint::LintTracer,
+    symbols::Symbols,
+    trace::{Registers, Tracer},
+};
+
+// The full Chip 8 system: cpu, memory, loaded rom and keypad
+pub struct Machine {
+    pub cpu: Cpu,
+    pub ram: Ram,
+    pub cartridge: Cartridge,
+    pub keypad: Keypad,
+    pub tracer: Option<Tracer>,
+    pub profiler: Option<Profiler>,
+    pub coverage: Option<Coverage>,
+    pub smc: Option<SmcDetector>,
+    pub lint: Option<LintTracer>,
+    pub symbols: Symbols, // Labels shown in traces, reports and debugger views
+    pub symbols_path: Option<PathBuf>, // Read again when the rom is reloaded
+    pub cycles: u64, // Instructions run since the machine was built
+}
+
+impl Machine {
+    // Build a machine with the font set and rom loaded into ram
+    pub fn new(quirks: Quirks, rom_path: &str) -> Result<Self, RomError> {
+        let mut machine = Self {
+            cpu: Cpu::new(quirks),
+            ram: Ram::new(),
+            cartridge: Cartridge::new(),
+            keypad: Keypad::new(),
+            tracer: None,
+            profiler: None,
+            coverage: None,
+            smc: None,
+            lint: None,
+            symbols: Symbols::default(),
+            symbols_path: None,
+            cycles: 0,
+        };
+
+        machine.ram.load_font_set();
+        machine.cartridge.load_rom(&mut machine.ram, rom_path)?;
+        Ok(machine)
+    }
+
+    // Run one 60Hz frame worth of cpu ticks, ending early if the cpu faults
+    pub fn run_frame(&mut self, ticks: u32) {
+        self.start_frame();
+        for _tick in 0..ticks {
+            self.step();
+            if self.cpu.fault().is_some() {
+                break;
+            }
+        }
+    }
+
+    // Signal a frame boundary to the cpu and age debugger highlights
+    pub fn start_frame(&mut self) {
+        self.cpu.vblank();
+        self.ram.age_writes();
+    }
+
+    // Run a single cpu instruction, with tracing, profiling, coverage and self modifying
+    // code checks if enabled
+    // A dxyn stalled on display wait isn't counted or recorded
+    pub fn step(&mut self) {
+        self.ram.step_writes.clear();
+        if self.tracer.is_none() && self.profiler.is_none() && self.coverage.is_none()
+            && self.smc.is_none() && self.lint.is_none() {
+            if self.cpu.tick(&mut self.ram, &mut self.keypad) {
+                self.cycles += 1;
+            }
+            return;
+        }
+
+        let pc = self.cpu.pc();
+        let opcode = disasm::opcode_at(&self.ram.mem, pc);
+        let before = Registers::of(&self.cpu);
+        if !self.cpu.tick(&mut self.ram, &mut self.keypad) {
+            return;
+        }
+        let after = Registers::of(&self.cpu);
+
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record(pc, opcode, before.sp, after.sp, self.cpu.pc());
+        }
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record(pc, opcode, self.cpu.pc());
+        }
+        if let Some(smc) = &mut self.smc {
+            smc.record(pc, &self.ram.step_writes);
+        }
+        if let Some(lint) = &mut self.lint {
+            lint.record(pc, opcode, &before);
+        }
+        // A failed write ends the trace rather than the emulation
+        if let Some(tracer) = &mut self.tracer {
+            if let Err(e) = tracer.record(self.cycles, pc, opcode, &before, &after, &self.symbols) {
+                eprintln!("Trace stopped, failed to write: {}", e);
+                self.tracer = None;
+            }
+        }
+        self.cycles += 1;
+    }
+
+    // Self modifying code reports since the last call
+    pub fn take_smc_reports(&mut self) -> Vec<String> {
+        self.smc.as_mut().map_or_else(Vec::new, SmcDetector::take_reports)
+    }
+
+    // Restart the cpu, keeping ram as is
+    pub fn soft_reset(&mut self) {
+        self.reset_cpu();
+    }
+
+    // Restart the cpu, clear ram and copy the font set and rom back in
+    pub fn hard_reset(&mut self) {
+        self.reset_cpu();
+        self.reset_smc();
+        self.ram = Ram::new();
+        self.ram.load_font_set();
+        self.cartridge.write_to_ram(&mut self.ram);
+    }
+
+    // Hard reset with the rom read again from disk
+    // On a read failure the machine keeps running the previous rom
+    pub fn reload_rom(&mut self) -> Result<(), RomError> {
+        let mut ram = Ram::new();
+        ram.load_font_set();
+        let path = self.cartridge.path().to_string();
+        self.cartridge.load_rom(&mut ram, &path)?;
+
+        self.reset_cpu();
+        self.reset_smc();
+        self.ram = ram;
+        self.reset_reports();
+        self.reload_symbols();
+        Ok(())
+    }
+
+    // A new build starts its profile, coverage and instruction count over so reports
+    // never mix two roms
+    fn reset_reports(&mut self) {
+        if let Some(profiler) = &mut self.profiler {
+            *profiler = Profiler::new(profiler.prefix());
+        }
+        if let Some(coverage) = &mut self.coverage {
+            *coverage = Coverage::new(coverage.prefix());
+        }
+        self.cycles = 0;
+    }
+
+    // Labels move with each build, a symbol file that fails to load leaves none
+    fn reload_symbols(&mut self) {
+        let Some(path) = &self.symbols_path else {
+            return;
+        };
+        self.symbols = Symbols::load(path).unwrap_or_else(|e| {
+            eprintln!("Failed to reload symbols {}: {}", path.display(), e);
+            Symbols::default()
+        });
+    }
+
+    // Ram starts over, so forget what ran and what was written
+    fn reset_smc(&mut self) {
+        if let Some(smc) = &mut self.smc {
+            *smc = SmcDetector::new(smc.mode);
+        }
+    }
+
+    // The reset empties the call stack, so the profiler leaves every subroutine too
+    fn reset_cpu(&mut self) {
+        self.cpu.reset();
+        if let Some(profiler) = &mut self.profiler {
+            profiler.reset_stack();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reload_starts_reports_and_symbols_over() {
+        let dir = std::env::temp_dir();
+        let rom_path = dir.join("chip_8_machine_reload.ch8");
+        let symbols_path = dir.join("chip_8_machine_reload.sym");
+        std::fs::write(&rom_path, [0x12, 0x00]).unwrap();
+        std::fs::write(&symbols_path, "main = 0x200").unwrap();
+
+        let mut machine = Machine::new(Quirks::new(), rom_path.to_str().unwrap()).unwrap();
+        machine.profiler = Some(Profiler::new("unused"));
+        machine.symbols_path = Some(symbols_path.clone());
+        machine.reload_symbols();
+        machine.run_frame(10);
+        assert_eq!((machine.cycles, machine.symbols.label(0x200)), (10, Some("main")));
+
+        std::fs::write(&rom_path, [0x00, 0xE0, 0x12, 0x02]).unwrap();
+        std::fs::write(&symbols_path, "start = 0x200\nloop = 0x202").unwrap();
+        machine.reload_rom().unwrap();
+        assert_eq!(machine.cycles, 0);
+        assert_eq!(machine.symbols.label(0x200), Some("start"));
+        assert_eq!(machine.symbols.label(0x202), Some("loop"));
+        assert_eq!(machine.profiler.as_ref().map(Profiler::prefix), Some("unused"));
+    }
+}
@@ -0,0 +1,973 @@
+//Module Todo:
+// Support the SCHIP/XO-CHIP instructions
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+};
+
+use crate::ROM_START;
+
+// Source register or constant for an instruction
+#[derive(Clone, Copy)]
+enum Operand {
+    Reg(usize),
+    Imm(u8),
+}
+
+// A condition a skip instruction can test directly
+#[derive(Clone, Copy)]
+enum Cond {
+    Eq(usize, Operand),
+    Ne(usize, Operand),
+    Key(usize),
+    NotKey(usize),
+}
+
+impl Cond {
+    fn negate(self) -> Self {
+        match self {
+            Cond::Eq(x, operand) => Cond::Ne(x, operand),
+            Cond::Ne(x, operand) => Cond::Eq(x, operand),
+            Cond::Key(x) => Cond::NotKey(x),
+            Cond::NotKey(x) => Cond::Key(x),
+        }
+    }
+
+    // Opcode that skips the next instruction when the condition holds
+    fn skip_opcode(self) -> u16 {
+        match self {
+            Cond::Eq(x, Operand::Imm(n)) => 0x3000 | (x as u16) << 8 | n as u16,
+            Cond::Ne(x, Operand::Imm(n)) => 0x4000 | (x as u16) << 8 | n as u16,
+            Cond::Eq(x, Operand::Reg(y)) => 0x5000 | (x as u16) << 8 | (y as u16) << 4,
+            Cond::Ne(x, Operand::Reg(y)) => 0x9000 | (x as u16) << 8 | (y as u16) << 4,
+            Cond::Key(x) => 0xE09E | (x as u16) << 8,
+            Cond::NotKey(x) => 0xE0A1 | (x as u16) << 8,
+        }
+    }
+}
+
+// Macro and string mode expansions before a program is assumed to recurse forever
+const MAX_EXPANSIONS: usize = 100_000;
+
+// Open control flow blocks waiting for their closing keyword
+enum Block {
+    If { jump_pos: usize }, // Jump over the if body, patched at else or end
+    Else { jump_pos: usize }, // Jump over the else body, patched at end
+    Loop { start: u16, breaks: Vec<usize> }, // While jumps patched at again
+}
+
+// A source token, strings have their quotes removed and escapes decoded
+#[derive(Clone)]
+struct Token<'a> {
+    text: Cow<'a, str>,
+    line: usize,
+    quoted: bool,
+}
+
+// How a label's address is written into the rom once it is known
+#[derive(Clone, Copy)]
+enum Fixup {
+    Address, // Low 12 bits of an opcode
+    High(Option<u8>), // Byte operand with a nybble and the address top, or the whole high byte
+    Low, // Byte operand with the low byte of the address
+}
+
+// A :macro, expanded with its arguments wherever its name appears
+struct Macro<'a> {
+    args: Vec<String>,
+    body: Vec<Token<'a>>,
+    calls: usize,
+}
+
+// A :stringmode, expanded for each character of a string found in its alphabet
+struct StringMode<'a> {
+    alphabet: Vec<char>,
+    body: Vec<Token<'a>>,
+}
+
+struct Assembler<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    rom: Vec<u8>,
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, usize>,
+    fixups: Vec<(usize, String, usize, Fixup)>, // Rom position, label, line and how to patch it
+    blocks: Vec<Block>,
+    macros: HashMap<String, Macro<'a>>,
+    string_modes: HashMap<String, Vec<StringMode<'a>>>,
+    expansions: usize,
+}
+
+// Assemble Octo source into Chip 8 program bytes loaded at ROM_START
+// Covers labels, :const, :alias, :org, :byte, :macro, :calc, :next, :call,
+// :unpack, :stringmode, :assert, the Chip 8 statements and
+// if/then, if/begin/else/end and loop/while/again
+pub fn assemble(source: &str) -> Result<Vec<u8>, String> {
+    let mut assembler = Assembler {
+        tokens: tokenize(source)?,
+        pos: 0,
+        rom: Vec::new(),
+        labels: HashMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        fixups: Vec::new(),
+        blocks: Vec::new(),
+        macros: HashMap::new(),
+        string_modes: HashMap::new(),
+        expansions: 0,
+    };
+
+    // Octo programs start with a jump to main
+    assembler.emit_address(0x1000, "main", 0);
+    while assembler.pos < assembler.tokens.len() {
+        assembler.statement()?;
+    }
+    if !assembler.blocks.is_empty() {
+        return Err(String::from("unclosed if or loop block at end of program"));
+    }
+
+    for (rom_pos, label, line, fixup) in &assembler.fixups {
+        let addr = *assembler.labels.get(label)
+            .ok_or_else(|| format!("line {}: undefined label {}", line, label))?;
+        let rom = &mut assembler.rom;
+        match fixup {
+            Fixup::Address => {
+                rom[*rom_pos] |= (addr >> 8) as u8 & 0x0F;
+                rom[*rom_pos + 1] = addr as u8;
+            },
+            Fixup::High(nybble) => rom[*rom_pos + 1] = high_byte(addr, *nybble),
+            Fixup::Low => rom[*rom_pos + 1] = addr as u8,
+        }
+    }
+
+    Ok(assembler.rom)
+}
+
+// Split source into tokens, dropping # comments and reading "strings" as single tokens
+fn tokenize(source: &str) -> Result<Vec<Token<'_>>, String> {
+    let mut tokens = Vec::new();
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let mut rest = text;
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() || rest.starts_with('#') {
+                break;
+            }
+            if let Some(string) = rest.strip_prefix('"') {
+                let (text, len) = read_string(string)
+                    .ok_or_else(|| format!("line {}: unterminated string", line))?;
+                tokens.push(Token { text: Cow::Owned(text), line, quoted: true });
+                rest = &string[len..];
+            } else {
+                let end = rest.find(|c: char| c.is_whitespace() || c == '#').unwrap_or(rest.len());
+                tokens.push(Token { text: Cow::Borrowed(&rest[..end]), line, quoted: false });
+                rest = &rest[end..];
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// Read a string up to its closing quote, returning the decoded text and the bytes used
+fn read_string(text: &str) -> Option<(String, usize)> {
+    let mut decoded = String::new();
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((decoded, i + 1)),
+            '\\' => decoded.push(match chars.next()?.1 {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                '0' => '\0',
+                other => other,
+            }),
+            c => decoded.push(c),
+        }
+    }
+    None
+}
+
+// First :unpack byte, a nybble and the top of a 12 bit address or a whole high byte
+fn high_byte(addr: u16, nybble: Option<u8>) -> u8 {
+    match nybble {
+        Some(nybble) => nybble << 4 | (addr >> 8) as u8 & 0x0F,
+        None => (addr >> 8) as u8,
+    }
+}
+
+// Octo :calc binary operators, which all take the same precedence
+fn binary(op: &str) -> Option<fn(f64, f64) -> f64> {
+    let apply: fn(f64, f64) -> f64 = match op {
+        "+" => |a, b| a + b,
+        "-" => |a, b| a - b,
+        "*" => |a, b| a * b,
+        "/" => |a, b| a / b,
+        "%" => |a, b| a % b,
+        "&" => |a, b| (a as i64 & b as i64) as f64,
+        "|" => |a, b| (a as i64 | b as i64) as f64,
+        "^" => |a, b| (a as i64 ^ b as i64) as f64,
+        "<<" => |a, b| (a as i64).wrapping_shl(b as u32) as f64,
+        ">>" => |a, b| (a as i64).wrapping_shr(b as u32) as f64,
+        "pow" => f64::powf,
+        "min" => f64::min,
+        "max" => f64::max,
+        "<" => |a, b| (a < b) as u8 as f64,
+        "<=" => |a, b| (a <= b) as u8 as f64,
+        "==" => |a, b| (a == b) as u8 as f64,
+        "!=" => |a, b| (a != b) as u8 as f64,
+        ">=" => |a, b| (a >= b) as u8 as f64,
+        ">" => |a, b| (a > b) as u8 as f64,
+        _ => return None,
+    };
+    Some(apply)
+}
+
+// Octo :calc unary operators, @ is handled by the assembler as it reads the rom
+fn unary(op: &str) -> Option<fn(f64) -> f64> {
+    let apply: fn(f64) -> f64 = match op {
+        "-" => |a| -a,
+        "~" => |a| !(a as i64) as f64,
+        "!" => |a| (a == 0.0) as u8 as f64,
+        "sin" => f64::sin,
+        "cos" => f64::cos,
+        "tan" => f64::tan,
+        "exp" => f64::exp,
+        "log" => f64::ln,
+        "abs" => f64::abs,
+        "sqrt" => f64::sqrt,
+        "sign" => |a| if a == 0.0 { 0.0 } else { a.signum() },
+        "ceil" => f64::ceil,
+        "floor" => f64::floor,
+        _ => return None,
+    };
+    Some(apply)
+}
+
+impl<'a> Assembler<'a> {
+    fn line(&self) -> usize {
+        self.tokens.get(self.pos.saturating_sub(1)).map_or(0, |token| token.line)
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("line {}: {}", self.line(), message)
+    }
+
+    fn next_token(&mut self) -> Result<Token<'a>, String> {
+        let token = self.tokens.get(self.pos)
+            .cloned()
+            .ok_or_else(|| String::from("unexpected end of program"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn next(&mut self) -> Result<Cow<'a, str>, String> {
+        let token = self.next_token()?;
+        if token.quoted {
+            return Err(self.error(&format!("unexpected string \"{}\"", token.text)));
+        }
+        Ok(token.text)
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).filter(|token| !token.quoted).map(|token| &*token.text)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        let token = self.next()?;
+        if token != expected {
+            return Err(self.error(&format!("expected {} but found {}", expected, token)));
+        }
+        Ok(())
+    }
+
+    fn here(&self) -> u16 {
+        (ROM_START + self.rom.len()) as u16
+    }
+
+    fn emit(&mut self, opcode: u16) {
+        self.rom.push((opcode >> 8) as u8);
+        self.rom.push(opcode as u8);
+    }
+
+    // Emit an opcode with a 12 bit address that may be resolved later
+    fn emit_address(&mut self, opcode: u16, label: &str, line: usize) {
+        self.fixups.push((self.rom.len(), String::from(label), line, Fixup::Address));
+        self.emit(opcode);
+    }
+
+    fn patch_jump(&mut self, rom_pos: usize, addr: u16) {
+        self.rom[rom_pos] = 0x10 | (addr >> 8) as u8 & 0x0F;
+        self.rom[rom_pos + 1] = addr as u8;
+    }
+
+    fn register(&self, token: &str) -> Option<usize> {
+        if let Some(reg) = self.aliases.get(token) {
+            return Some(*reg);
+        }
+        let digit = token.strip_prefix('v').or_else(|| token.strip_prefix('V'))?;
+        if digit.len() != 1 {
+            return None;
+        }
+        usize::from_str_radix(digit, 16).ok()
+    }
+
+    fn number(&self, token: &str) -> Option<i32> {
+        if let Some(value) = self.constants.get(token) {
+            return Some(value.floor() as i32);
+        }
+        let (negative, digits) = match token.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, token),
+        };
+        let value = if let Some(hex) = digits.strip_prefix("0x") {
+            i32::from_str_radix(hex, 16).ok()?
+        } else if let Some(bin) = digits.strip_prefix("0b") {
+            i32::from_str_radix(bin, 2).ok()?
+        } else {
+            digits.parse().ok()?
+        };
+        Some(if negative { -value } else { value })
+    }
+
+    // Read a number, constant or { calc expression }, returning its text and value if it
+    // has one yet, labels are left for the caller to resolve
+    fn value(&mut self) -> Result<(Cow<'a, str>, Option<i32>), String> {
+        if self.peek() == Some("{") {
+            let value = self.calc()?;
+            return Ok((Cow::Owned(value.to_string()), Some(value.floor() as i32)));
+        }
+        let token = self.next()?;
+        let value = self.number(&token);
+        Ok((token, value))
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        let (token, value) = self.value()?;
+        match value {
+            Some(value) if (-128..=255).contains(&value) => Ok(value as u8),
+            _ => Err(self.error(&format!("expected a byte but found {}", token))),
+        }
+    }
+
+    fn reg(&mut self) -> Result<usize, String> {
+        let token = self.next()?;
+        self.register(&token)
+            .ok_or_else(|| self.error(&format!("expected a register but found {}", token)))
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        if let Some(reg) = self.peek().and_then(|token| self.register(token)) {
+            self.pos += 1;
+            return Ok(Operand::Reg(reg));
+        }
+        Ok(Operand::Imm(self.byte()?))
+    }
+
+    // Emit an opcode taking a label, numeric or calculated 12 bit address
+    fn address_opcode(&mut self, opcode: u16) -> Result<(), String> {
+        let (token, value) = self.value()?;
+        match value {
+            Some(addr) if (0..=0xFFF).contains(&addr) => self.emit(opcode | addr as u16),
+            Some(_) => return Err(self.error(&format!("address {} out of range", token))),
+            None => {
+                let line = self.line();
+                self.emit_address(opcode, &token, line);
+            },
+        }
+        Ok(())
+    }
+
+    // Evaluate a { calc expression }, Octo style: right to left with no precedence
+    fn calc(&mut self) -> Result<f64, String> {
+        self.expect("{")?;
+        let value = self.calc_expr()?;
+        self.expect("}")?;
+        Ok(value)
+    }
+
+    fn calc_expr(&mut self) -> Result<f64, String> {
+        let lhs = self.calc_term()?;
+        if matches!(self.peek(), Some("}") | Some(")")) {
+            return Ok(lhs);
+        }
+        let op = self.next()?;
+        let apply = binary(&op)
+            .ok_or_else(|| self.error(&format!("unknown calc operator {}", op)))?;
+        let rhs = self.calc_expr()?;
+        Ok(apply(lhs, rhs))
+    }
+
+    fn calc_term(&mut self) -> Result<f64, String> {
+        let token = self.next()?;
+        if token == "(" {
+            let value = self.calc_expr()?;
+            self.expect(")")?;
+            return Ok(value);
+        }
+        if token == "@" {
+            // The byte already assembled at an address, 0 past the end
+            let addr = self.calc_term()? as usize;
+            let byte = addr.checked_sub(ROM_START).and_then(|index| self.rom.get(index));
+            return Ok(byte.map_or(0.0, |byte| *byte as f64));
+        }
+        if let Some(apply) = unary(&token) {
+            return Ok(apply(self.calc_term()?));
+        }
+        self.calc_value(&token)
+            .ok_or_else(|| self.error(&format!("unknown calc value {}", token)))
+    }
+
+    fn calc_value(&self, token: &str) -> Option<f64> {
+        let decimal = token.starts_with(|c: char| c.is_ascii_digit() || c == '.');
+        match token {
+            "HERE" => Some(self.here() as f64),
+            "PI" => Some(std::f64::consts::PI),
+            "E" => Some(std::f64::consts::E),
+            _ => self.constants.get(token).copied()
+                .or_else(|| self.labels.get(token).map(|addr| *addr as f64))
+                .or_else(|| self.number(token).map(f64::from))
+                .or_else(|| token.parse().ok().filter(|_| decimal)),
+        }
+    }
+
+    // Read the tokens between { and its matching }
+    fn block(&mut self) -> Result<Vec<Token<'a>>, String> {
+        self.expect("{")?;
+        let mut depth = 0;
+        let mut body = Vec::new();
+        loop {
+            let token = self.next_token().map_err(|_| self.error("unclosed {"))?;
+            if !token.quoted {
+                match &*token.text {
+                    "{" => depth += 1,
+                    "}" if depth == 0 => return Ok(body),
+                    "}" => depth -= 1,
+                    _ => {},
+                }
+            }
+            body.push(token);
+        }
+    }
+
+    // Insert expanded tokens to be assembled next
+    fn splice(&mut self, tokens: Vec<Token<'a>>) -> Result<(), String> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(self.error("too many expansions, is a macro recursive?"));
+        }
+        self.tokens.splice(self.pos..self.pos, tokens);
+        Ok(())
+    }
+
+    // Replace a macro invocation with its body, substituting arguments and CALLS
+    fn expand_macro(&mut self, name: &str) -> Result<(), String> {
+        let mut values = Vec::new();
+        for _arg in 0..self.macros[name].args.len() {
+            values.push(self.next_token()?);
+        }
+        let Some(mac) = self.macros.get_mut(name) else {
+            return Ok(());
+        };
+        let calls = Token { text: Cow::Owned(mac.calls.to_string()), line: 0, quoted: false };
+        mac.calls += 1;
+        let body = mac.body.iter()
+            .map(|token| {
+                let arg = mac.args.iter().position(|arg| *arg == *token.text).map(|i| &values[i]);
+                match (token.quoted, arg) {
+                    (false, Some(value)) => value.clone(),
+                    (false, None) if token.text == "CALLS" => {
+                        Token { line: token.line, ..calls.clone() }
+                    },
+                    _ => token.clone(),
+                }
+            })
+            .collect();
+        self.splice(body)
+    }
+
+    // Expand a string mode for each character of a string, substituting CHAR, INDEX and VALUE
+    fn expand_string(&mut self, name: &str) -> Result<(), String> {
+        let string = self.next_token()?;
+        if !string.quoted {
+            return Err(self.error(&format!("{} needs a string but found {}", name, string.text)));
+        }
+        let mut expanded = Vec::new();
+        for (index, c) in string.text.chars().enumerate() {
+            let (mode, value) = self.string_modes[name].iter()
+                .find_map(|mode| Some((mode, mode.alphabet.iter().position(|a| *a == c)?)))
+                .ok_or_else(|| self.error(&format!("{} has no mode for {:?}", name, c)))?;
+            for token in &mode.body {
+                let number = match &*token.text {
+                    _ if token.quoted => None,
+                    "CHAR" => Some(c as usize),
+                    "INDEX" => Some(index),
+                    "VALUE" => Some(value),
+                    _ => None,
+                };
+                expanded.push(match number {
+                    Some(number) => Token { text: Cow::Owned(number.to_string()), ..token.clone() },
+                    None => token.clone(),
+                });
+            }
+        }
+        self.splice(expanded)
+    }
+
+    // Parse a condition after if or while, emitting any vf comparison code first
+    fn condition(&mut self) -> Result<Cond, String> {
+        let x = self.reg()?;
+        let op = self.next()?;
+        match &*op {
+            "key" => return Ok(Cond::Key(x)),
+            "-key" => return Ok(Cond::NotKey(x)),
+            "==" => return Ok(Cond::Eq(x, self.operand()?)),
+            "!=" => return Ok(Cond::Ne(x, self.operand()?)),
+            _ => {},
+        }
+
+        // Ordered comparisons use vf = a - b, where the flag is set when a >= b
+        let rhs = self.operand()?;
+        let (a, b, a_at_least_b) = match &*op {
+            "<" => (Operand::Reg(x), rhs, false),
+            ">=" => (Operand::Reg(x), rhs, true),
+            ">" => (rhs, Operand::Reg(x), false),
+            "<=" => (rhs, Operand::Reg(x), true),
+            _ => return Err(self.error(&format!("unknown comparison {}", op))),
+        };
+        match (a, b) {
+            (a, Operand::Reg(b)) => {
+                match a {
+                    Operand::Reg(a) => self.emit(0x8F00 | (a as u16) << 4),
+                    Operand::Imm(n) => self.emit(0x6F00 | n as u16),
+                }
+                self.emit(0x8F05 | (b as u16) << 4);
+            },
+            (Operand::Reg(a), Operand::Imm(n)) => {
+                self.emit(0x6F00 | n as u16);
+                self.emit(0x8F07 | (a as u16) << 4);
+            },
+            _ => return Err(self.error("comparison needs at least one register")),
+        }
+        Ok(Cond::Eq(0xF, Operand::Imm(a_at_least_b as u8)))
+    }
+
+    fn statement(&mut self) -> Result<(), String> {
+        let token = self.next()?;
+
+        match &*token {
+            ":" => {
+                let name = self.next()?;
+                let here = self.here();
+                self.labels.insert(name.into_owned(), here);
+            },
+            ":next" => {
+                // Label the operand byte of the next instruction
+                let name = self.next()?;
+                let here = self.here() + 1;
+                self.labels.insert(name.into_owned(), here);
+            },
+            ":const" => {
+                let name = self.next()?;
+                let (token, value) = self.value()?;
+                let value = value
+                    .filter(|value| (-128..=0xFFFF).contains(value))
+                    .ok_or_else(|| self.error(&format!(":const {} needs a number, found {}", name, token)))?;
+                self.constants.insert(name.into_owned(), value as f64);
+            },
+            ":calc" => {
+                let name = self.next()?;
+                let value = self.calc()?;
+                self.constants.insert(name.into_owned(), value);
+            },
+            ":alias" => {
+                let name = self.next()?;
+                let reg = self.reg()?;
+                self.aliases.insert(name.into_owned(), reg);
+            },
+            ":org" => {
+                let (token, addr) = self.value()?;
+                let addr = addr
+                    .filter(|addr| *addr as usize >= ROM_START + self.rom.len() && *addr <= 0xFFFF)
+                    .ok_or_else(|| self.error(&format!(":org {} is behind the current address", token)))?;
+                self.rom.resize(addr as usize - ROM_START, 0);
+            },
+            ":byte" => {
+                let value = self.byte()?;
+                self.rom.push(value);
+            },
+            ":call" => self.address_opcode(0x2000)?,
+            ":unpack" => self.unpack()?,
+            ":macro" => {
+                let name = self.next()?;
+                let mut args = Vec::new();
+                while self.peek().is_some_and(|token| token != "{") {
+                    args.push(self.next()?.into_owned());
+                }
+                let body = self.block()?;
+                self.macros.insert(name.into_owned(), Macro { args, body, calls: 0 });
+            },
+            ":stringmode" => {
+                let name = self.next()?;
+                let alphabet = self.next_token()?;
+                if !alphabet.quoted {
+                    return Err(self.error(&format!(":stringmode {} needs an alphabet", name)));
+                }
+                let alphabet = alphabet.text.chars().collect();
+                let body = self.block()?;
+                let modes = self.string_modes.entry(name.into_owned()).or_default();
+                modes.push(StringMode { alphabet, body });
+            },
+            ":assert" => {
+                let message = match self.tokens.get(self.pos) {
+                    Some(token) if token.quoted => Some(self.next_token()?.text),
+                    _ => None,
+                };
+                if self.calc()? == 0.0 {
+                    let message = message.unwrap_or(Cow::Borrowed("assertion failed"));
+                    return Err(self.error(&message));
+                }
+            },
+            ":breakpoint" | ":proto" => {
+                self.next()?;
+            },
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            },
+            "clear" => self.emit(0x00E0),
+            "return" | ";" => self.emit(0x00EE),
+            "jump" => self.address_opcode(0x1000)?,
+            "jump0" => self.address_opcode(0xB000)?,
+            "native" => self.address_opcode(0x0000)?,
+            "bcd" => { let x = self.reg()?; self.emit(0xF033 | (x as u16) << 8) },
+            "save" => { let x = self.reg()?; self.emit(0xF055 | (x as u16) << 8) },
+            "load" => { let x = self.reg()?; self.emit(0xF065 | (x as u16) << 8) },
+            "sprite" => {
+                let x = self.reg()?;
+                let y = self.reg()?;
+                let n = self.byte()?;
+                if n > 0xF {
+                    return Err(self.error("sprite height must be 0 to 15"));
+                }
+                self.emit(0xD000 | (x as u16) << 8 | (y as u16) << 4 | n as u16);
+            },
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let x = self.reg()?;
+                let low = if &*token == "delay" { 0x15 } else { 0x18 };
+                self.emit(0xF000 | (x as u16) << 8 | low);
+            },
+            "i" => self.index_statement()?,
+            "if" => self.if_statement()?,
+            "else" => match self.blocks.pop() {
+                Some(Block::If { jump_pos }) => {
+                    let else_jump = self.rom.len();
+                    self.emit(0x1000);
+                    let here = self.here();
+                    self.patch_jump(jump_pos, here);
+                    self.blocks.push(Block::Else { jump_pos: else_jump });
+                },
+                _ => return Err(self.error("else without if ... begin")),
+            },
+            "end" => match self.blocks.pop() {
+                Some(Block::If { jump_pos }) | Some(Block::Else { jump_pos }) => {
+                    let here = self.here();
+                    self.patch_jump(jump_pos, here);
+                },
+                _ => return Err(self.error("end without if ... begin")),
+            },
+            "loop" => {
+                let start = self.here();
+                self.blocks.push(Block::Loop { start, breaks: Vec::new() });
+            },
+            "while" => {
+                let cond = self.condition()?;
+                // Skip the exit jump while the condition holds
+                self.emit(cond.skip_opcode());
+                let jump_pos = self.rom.len();
+                self.emit(0x1000);
+                match self.blocks.iter_mut().rev().find(|block| matches!(block, Block::Loop { .. })) {
+                    Some(Block::Loop { breaks, .. }) => breaks.push(jump_pos),
+                    _ => return Err(self.error("while outside of a loop")),
+                }
+            },
+            "again" => match self.blocks.pop() {
+                Some(Block::Loop { start, breaks }) => {
+                    self.emit(0x1000 | start);
+                    let here = self.here();
+                    for jump_pos in breaks {
+                        self.patch_jump(jump_pos, here);
+                    }
+                },
+                _ => return Err(self.error("again without loop")),
+            },
+            _ if token.starts_with(':') => {
+                return Err(self.error(&format!("unsupported directive {}", token)));
+            },
+            _ => {
+                if let Some(x) = self.register(&token) {
+                    self.register_statement(x)?;
+                } else if self.macros.contains_key(&*token) {
+                    self.expand_macro(&token)?;
+                } else if self.string_modes.contains_key(&*token) {
+                    self.expand_string(&token)?;
+                } else if let Some(value) = self.number(&token) {
+                    // Bare numbers are data bytes
+                    if !(-128..=255).contains(&value) {
+                        return Err(self.error(&format!("byte {} out of range", token)));
+                    }
+                    self.rom.push(value as u8);
+                } else if is_identifier(&token) {
+                    // A bare label calls it as a subroutine
+                    let line = self.line();
+                    self.emit_address(0x2000, &token, line);
+                } else {
+                    return Err(self.error(&format!("unexpected {}", token)));
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    // Load v0 and v1 with a label's address, under a nybble or as a long 16 bit address
+    fn unpack(&mut self) -> Result<(), String> {
+        let nybble = if self.peek() == Some("long") {
+            self.next()?;
+            None
+        } else {
+            match self.byte()? {
+                nybble @ 0..=0xF => Some(nybble),
+                _ => return Err(self.error(":unpack nybble must be 0 to 15 or long")),
+            }
+        };
+        let (token, value) = self.value()?;
+        match value {
+            Some(addr) if (0..=0xFFFF).contains(&addr) => {
+                self.emit(0x6000 | high_byte(addr as u16, nybble) as u16);
+                self.emit(0x6100 | addr as u16 & 0xFF);
+            },
+            Some(_) => return Err(self.error(&format!("address {} out of range", token))),
+            None => {
+                let line = self.line();
+                self.fixups.push((self.rom.len(), token.to_string(), line, Fixup::High(nybble)));
+                self.emit(0x6000);
+                self.fixups.push((self.rom.len(), token.into_owned(), line, Fixup::Low));
+                self.emit(0x6100);
+            },
+        }
+        Ok(())
+    }
+
+    fn index_statement(&mut self) -> Result<(), String> {
+        let op = self.next()?;
+        match &*op {
+            ":=" => match self.peek() {
+                Some("hex") => {
+                    self.next()?;
+                    let x = self.reg()?;
+                    self.emit(0xF029 | (x as u16) << 8);
+                },
+                Some("bighex") | Some("long") => {
+                    return Err(self.error("SCHIP and XO-CHIP index loads are not supported"));
+                },
+                _ => self.address_opcode(0xA000)?,
+            },
+            "+=" => {
+                let x = self.reg()?;
+                self.emit(0xF01E | (x as u16) << 8);
+            },
+            _ => return Err(self.error(&format!("unknown index operation {}", op))),
+        }
+        Ok(())
+    }
+
+    fn if_statement(&mut self) -> Result<(), String> {
+        let cond = self.condition()?;
+        match &*self.next()? {
+            // Skip the single statement when the condition fails
+            "then" => {
+                self.emit(cond.negate().skip_opcode());
+            },
+            // Skip the jump over the block when the condition holds
+            "begin" => {
+                self.emit(cond.skip_opcode());
+                let jump_pos = self.rom.len();
+                self.emit(0x1000);
+                self.blocks.push(Block::If { jump_pos });
+            },
+            other => return Err(self.error(&format!("expected then or begin but found {}", other))),
+        }
+        Ok(())
+    }
+
+    fn register_statement(&mut self, x: usize) -> Result<(), String> {
+        let x_bits = (x as u16) << 8;
+        let op = self.next()?;
+
+        if &*op == ":=" {
+            match self.peek() {
+                Some("key") => { self.next()?; self.emit(0xF00A | x_bits) },
+                Some("delay") => { self.next()?; self.emit(0xF007 | x_bits) },
+                Some("random") => {
+                    self.next()?;
+                    let mask = self.byte()?;
+                    self.emit(0xC000 | x_bits | mask as u16);
+                },
+                _ => match self.operand()? {
+                    Operand::Reg(y) => self.emit(0x8000 | x_bits | (y as u16) << 4),
+                    Operand::Imm(n) => self.emit(0x6000 | x_bits | n as u16),
+                },
+            }
+            return Ok(());
+        }
+
+        let operand = self.operand()?;
+        let opcode = match (&*op, operand) {
+            ("+=", Operand::Imm(n)) => 0x7000 | x_bits | n as u16,
+            ("-=", Operand::Imm(n)) => 0x7000 | x_bits | n.wrapping_neg() as u16,
+            (_, Operand::Imm(_)) => {
+                return Err(self.error(&format!("{} needs a register operand", op)));
+            },
+            (_, Operand::Reg(y)) => {
+                let low = match &*op {
+                    "|=" => 0x1,
+                    "&=" => 0x2,
+                    "^=" => 0x3,
+                    "+=" => 0x4,
+                    "-=" => 0x5,
+                    ">>=" => 0x6,
+                    "=-" => 0x7,
+                    "<<=" => 0xE,
+                    _ => return Err(self.error(&format!("unknown register operation {}", op))),
+                };
+                0x8000 | x_bits | (y as u16) << 4 | low
+            },
+        };
+        self.emit(opcode);
+        Ok(())
+    }
+}
+
+fn is_identifier(token: &str) -> bool {
+    token.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cpu::Cpu,
+        input::Keypad,
+        quirks::Quirks,
+        ram::Ram,
+        ROM_START,
+    };
+
+    // Assemble and run a program for a few instructions, returning the registers
+    fn run(source: &str) -> [u8; 16] {
+        let rom = assemble(source).unwrap();
+        let mut ram = Ram::new();
+        ram.mem[ROM_START..ROM_START + rom.len()].copy_from_slice(&rom);
+        let mut cpu = Cpu::new(Quirks::new());
+        let mut keypad = Keypad::new();
+        for _tick in 0..16 {
+            cpu.tick(&mut ram, &mut keypad);
+        }
+        *cpu.v()
+    }
+
+    #[test]
+    fn comparisons_run_correctly() {
+        let cases = [("<", u8::lt as fn(&u8, &u8) -> bool), (">=", u8::ge), (">", u8::gt), ("<=", u8::le)];
+        for (op, expected) in cases {
+            for (a, b) in [(3, 5), (5, 5), (5, 3), (0, 0xFF)] {
+                let registers = format!(": main v0 := {} v1 := {} v2 := 0 v3 := 0", a, b);
+                let compare = format!("if v0 {} v1 then v2 := 1 if v0 {} {} then v3 := 1", op, op, b);
+                let v = run(&format!("{} {} loop again", registers, compare));
+                let expected = expected(&a, &b) as u8;
+                assert_eq!((v[2], v[3]), (expected, expected), "{} {} {}", a, op, b);
+            }
+        }
+    }
+
+    #[test]
+    fn arithmetic_leaves_the_flag_in_vf() {
+        let v = run(": main vf := 200 v1 := 100 vf += v1 v2 := vf
+            vf := 3 vf -= v1 v3 := vf loop again");
+        assert_eq!((v[2], v[3]), (1, 0));
+    }
+
+    #[test]
+    fn assembles_random_hex_and_buzzer() {
+        let rom = assemble(": main v0 := random 0x0F i := hex v0 buzzer := v0").unwrap();
+        assert_eq!(rom, [0x12, 0x02, 0xC0, 0x0F, 0xF0, 0x29, 0xF0, 0x18]);
+    }
+
+    #[test]
+    fn const_takes_addresses() {
+        let rom = assemble(":const SPOT 0x300 : main i := SPOT").unwrap();
+        assert_eq!(rom, [0x12, 0x02, 0xA3, 0x00]);
+    }
+
+    #[test]
+    fn macros_substitute_arguments_and_calls() {
+        let rom = assemble(":macro set reg value { reg := value vf := CALLS }
+            :calc SIX { 2 * 3 } : main set v1 7 set v2 SIX").unwrap();
+        assert_eq!(rom, [0x12, 0x02, 0x61, 0x07, 0x6F, 0x00, 0x62, 0x06, 0x6F, 0x01]);
+    }
+
+    #[test]
+    fn calc_reads_right_to_left() {
+        let rom = assemble(":calc A { 2 * 3 + 1 } :calc B { ( 2 * 3 ) + 1 }
+            :calc C { HERE - 0x1F0 } :calc D { - 1 + sqrt 16 } : main A B C D").unwrap();
+        assert_eq!(rom[2..], [8, 7, 0x12, 3]);
+    }
+
+    #[test]
+    fn next_labels_the_operand_byte() {
+        let rom = assemble(": main :next count v0 := 5 :calc N { @ count } N").unwrap();
+        assert_eq!(rom, [0x12, 0x02, 0x60, 0x05, 0x05]);
+        let rom = assemble(": main :next count v0 := 5 i := count").unwrap();
+        assert_eq!(rom[4..], [0xA2, 0x03]);
+    }
+
+    #[test]
+    fn unpack_and_call_take_labels() {
+        let rom = assemble(": main :unpack 0xA data :unpack long data :call data : data").unwrap();
+        assert_eq!(rom, [0x12, 0x02, 0x60, 0xA2, 0x61, 0x0C, 0x60, 0x02, 0x61, 0x0C, 0x22, 0x0C]);
+        let rom = assemble(": main :unpack 1 0x345 :call { 0x200 + 4 }").unwrap();
+        assert_eq!(rom[2..], [0x60, 0x13, 0x61, 0x45, 0x22, 0x04]);
+    }
+
+    #[test]
+    fn stringmodes_expand_each_character() {
+        let rom = assemble(":stringmode text \"abc\" { VALUE }
+            :stringmode text \"# \" { CHAR INDEX } : main text \"ba# \"").unwrap();
+        assert_eq!(rom[2..], [1, 0, b'#', 2, b' ', 3]);
+        let error = assemble(":stringmode text \"ab\" { VALUE } : main text \"z\"").unwrap_err();
+        assert!(error.contains("no mode for 'z'"), "{}", error);
+    }
+
+    #[test]
+    fn asserts_report_their_message() {
+        assert!(assemble(": main :assert { 1 < 2 }").is_ok());
+        let error = assemble(": main\n:assert \"too big\" { HERE < 0x200 }").unwrap_err();
+        assert_eq!(error, "line 2: too big");
+    }
+
+    #[test]
+    fn bad_directives_are_named() {
+        let error = assemble(": main :pointer x").unwrap_err();
+        assert!(error.contains(":pointer"), "{}", error);
+        let error = assemble(":const X zz : main").unwrap_err();
+        assert!(error.contains(":const X"), "{}", error);
+        let error = assemble(":macro loopy { loopy } : main loopy").unwrap_err();
+        assert!(error.contains("recursive"), "{}", error);
+    }
+}
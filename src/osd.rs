@@ -0,0 +1,111 @@
+//Module Todo:
+// N/A
+
+use sdl2::{
+    pixels::Color,
+    rect::Rect,
+    render::Canvas,
+    video::Window,
+};
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::font;
+
+const MESSAGE_DURATION: Duration = Duration::from_secs(2);
+const MAX_MESSAGES: usize = 4;
+const STATS_INTERVAL: Duration = Duration::from_millis(500);
+const TEXT_COLOUR: Color = Color::RGB(255, 255, 255);
+const PAUSED_COLOUR: Color = Color::RGB(255, 200, 0);
+const BACKING_COLOUR: Color = Color::RGBA(0, 0, 0, 160); // Keeps text readable over lit pixels
+
+// On screen display drawn over the game for status messages and counters
+pub struct Osd {
+    messages: VecDeque<(String, Instant)>, // Newest last
+    pub show_stats: bool, // FPS and instructions per second counter
+    pub paused: bool,
+    frames: u64, // Emulation frames and instructions since stats were last updated
+    ticks: u64,
+    stats_start: Instant,
+    fps: f64,
+    ips: f64,
+}
+
+impl Osd {
+    pub fn new(show_stats: bool) -> Self {
+        Self {
+            messages: VecDeque::new(),
+            show_stats,
+            paused: false,
+            frames: 0,
+            ticks: 0,
+            stats_start: Instant::now(),
+            fps: 0.0,
+            ips: 0.0,
+        }
+    }
+
+    // Show a transient message, dropping the oldest when too many are shown
+    pub fn message(&mut self, text: &str) {
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back((String::from(text), Instant::now()));
+    }
+
+    // Count emulation frames and instructions run, refreshing the rates periodically
+    pub fn count(&mut self, frames: u64, ticks: u64) {
+        self.frames += frames;
+        self.ticks += ticks;
+
+        let elapsed = self.stats_start.elapsed();
+        if elapsed >= STATS_INTERVAL {
+            self.fps = self.frames as f64 / elapsed.as_secs_f64();
+            self.ips = self.ticks as f64 / elapsed.as_secs_f64();
+            self.frames = 0;
+            self.ticks = 0;
+            self.stats_start = Instant::now();
+        }
+    }
+
+    // Draw over the game area, text pixels are pixel window pixels square
+    pub fn draw(&mut self, canvas: &mut Canvas<Window>, area: Rect, pixel: u32) {
+        self.messages.retain(|(_, shown)| shown.elapsed() < MESSAGE_DURATION);
+
+        let margin = (2 * pixel) as i32;
+        let line_height = (font::LINE_HEIGHT * pixel) as i32;
+
+        // Counters in the top left, paused indicator in the top right
+        if self.show_stats {
+            let stats = format!("FPS {:.0}  IPS {:.0}", self.fps, self.ips);
+            draw_label(canvas, &stats, area.x() + margin, area.y() + margin, pixel, TEXT_COLOUR);
+        }
+        if self.paused {
+            let x = area.right() - margin - font::text_width("PAUSED", pixel) as i32;
+            draw_label(canvas, "PAUSED", x, area.y() + margin, pixel, PAUSED_COLOUR);
+        }
+
+        // Messages stack up from the bottom left, newest at the bottom
+        let mut y = area.bottom() - margin - (font::GLYPH_HEIGHT * pixel) as i32;
+        for (text, _) in self.messages.iter().rev() {
+            draw_label(canvas, text, area.x() + margin, y, pixel, TEXT_COLOUR);
+            y -= line_height + margin;
+        }
+    }
+}
+
+// Draw text on a translucent backing box
+fn draw_label(canvas: &mut Canvas<Window>, text: &str, x: i32, y: i32, pixel: u32, colour: Color) {
+    let backing = Rect::new(
+        x - pixel as i32,
+        y - pixel as i32,
+        font::text_width(text, pixel) + 2 * pixel,
+        (font::GLYPH_HEIGHT + 2) * pixel,
+    );
+    canvas.set_draw_color(BACKING_COLOUR);
+    canvas.fill_rect(backing).expect("Failed to draw osd");
+    font::draw_text(canvas, text, x, y, pixel, colour);
+}
@@ -0,0 +1,48 @@
+//Module Todo:
+// N/A
+
+// Background and foreground colours used to present vram
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Palette {
+    pub background: [u8; 3], // RGB
+    pub foreground: [u8; 3], // RGB
+}
+
+impl Palette {
+    pub fn new() -> Self {
+        Self {
+            background: [0x00, 0x00, 0x00],
+            foreground: [0xFF, 0xFF, 0xFF],
+        }
+    }
+
+    // Parse a "#rrggbb" colour
+    pub fn parse_colour(colour: &str) -> Option<[u8; 3]> {
+        let hex = colour.trim().strip_prefix('#').unwrap_or(colour.trim());
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+
+        let mut rgb = [0; 3];
+        for (i, channel) in rgb.iter_mut().enumerate() {
+            *channel = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+        }
+        Some(rgb)
+    }
+
+    // Map a grey RGBA frame onto the palette, blending for partly lit pixels
+    // Frame bytes are stored as A, B, G, R per pixel to match the RGBA8888 texture
+    pub fn apply(&self, frame: &[u8], output: &mut [u8]) {
+        for (pixel, out) in frame.chunks_exact(4).zip(output.chunks_exact_mut(4)) {
+            let level = pixel[3] as u16;
+            let mix = |bg: u8, fg: u8| {
+                ((bg as u16 * (0xFF - level) + fg as u16 * level) / 0xFF) as u8
+            };
+
+            out[0] = 0xFF;
+            out[1] = mix(self.background[2], self.foreground[2]);
+            out[2] = mix(self.background[1], self.foreground[1]);
+            out[3] = mix(self.background[0], self.foreground[0]);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//Module Todo:
+// N/A
+
+use sdl2::{
+    pixels::Color,
+    rect::Rect,
+    render::Canvas,
+    video::Window,
+};
+
+use crate::{
+    debugger::Debugger,
+    disasm,
+    font,
+    machine::Machine,
+};
+
+const REGISTER_COLUMNS: u32 = 16; // Panel widths in characters
+const DISASM_COLUMNS: u32 = 26;
+const MEMORY_ROWS: u32 = 16;
+const BYTES_PER_ROW: usize = 16;
+const PANEL_COLOUR: Color = Color::RGB(24, 24, 32);
+const TITLE_COLOUR: Color = Color::RGB(120, 140, 200);
+const TEXT_COLOUR: Color = Color::RGB(210, 210, 210);
+const PC_COLOUR: Color = Color::RGB(255, 220, 80);
+const BREAKPOINT_COLOUR: Color = Color::RGB(255, 90, 90);
+const WRITE_COLOUR: Color = Color::RGB(255, 150, 40);
+
+// Debugger panels laid out beside and below the game screen
+pub struct Panels {
+    pixel: u32, // Window pixels per font pixel
+    game: Rect,
+    registers: Rect,
+    disasm: Rect,
+    memory: Rect,
+    disasm_rows: Vec<usize>, // Address shown on each disassembly row, for clicks
+    memory_scroll: i32, // Rows scrolled away from the row holding i
+}
+
+impl Panels {
+    // Registers and disassembly go to the right of the game, memory below it
+    pub fn new(game_width: u32, game_height: u32, pixel: u32) -> Self {
+        let padding = 2 * pixel;
+        let column_width = font::ADVANCE * pixel;
+        let register_width = REGISTER_COLUMNS * column_width + 2 * padding;
+        let disasm_width = DISASM_COLUMNS * column_width + 2 * padding;
+        let memory_height = (MEMORY_ROWS + 1) * font::LINE_HEIGHT * pixel + 2 * padding;
+        let height = game_height + memory_height;
+
+        Self {
+            pixel,
+            game: Rect::new(0, 0, game_width, game_height),
+            registers: Rect::new(game_width as i32, 0, register_width, height),
+            disasm: Rect::new((game_width + register_width) as i32, 0, disasm_width, height),
+            memory: Rect::new(0, game_height as i32, game_width, memory_height),
+            disasm_rows: Vec::new(),
+            memory_scroll: 0,
+        }
+    }
+
+    // Window size needed to fit the game and every panel
+    pub fn window_size(&self) -> (u32, u32) {
+        (self.disasm.right() as u32, self.registers.height())
+    }
+
+    pub fn game_area(&self) -> Rect {
+        self.game
+    }
+
+    // Address of the disassembly row at a window position
+    pub fn disasm_address_at(&self, x: i32, y: i32) -> Option<usize> {
+        if !self.disasm.contains_point((x, y)) {
+            return None;
+        }
+        let row = (y - self.first_row_y(self.disasm)) / self.line_height();
+        usize::try_from(row).ok().and_then(|row| self.disasm_rows.get(row).copied())
+    }
+
+    pub fn scroll_memory(&mut self, rows: i32) {
+        self.memory_scroll += rows;
+    }
+
+    pub fn draw(&mut self, canvas: &mut Canvas<Window>, machine: &Machine, debugger: &Debugger) {
+        canvas.set_draw_color(PANEL_COLOUR);
+        canvas.fill_rects(&[self.registers, self.disasm, self.memory])
+            .expect("Failed to draw debugger panels");
+
+        self.draw_registers(canvas, machine, debugger);
+        self.draw_disasm(canvas, machine, debugger);
+        self.draw_memory(canvas, machine);
+    }
+
+    fn line_height(&self) -> i32 {
+        (font::LINE_HEIGHT * self.pixel) as i32
+    }
+
+    // Top of the first text row under a panel title
+    fn first_row_y(&self, panel: Rect) -> i32 {
+        panel.y() + (2 * self.pixel) as i32 + self.line_height()
+    }
+
+    fn text(&self, canvas: &mut Canvas<Window>, panel: Rect, column: u32, row: i32,
+        text: &str, colour: Color) {
+        let x = panel.x() + ((2 + column * font::ADVANCE) * self.pixel) as i32;
+        let y = self.first_row_y(panel) + row * self.line_height();
+        font::draw_text(canvas, text, x, y, self.pixel, colour);
+    }
+
+    fn title(&self, canvas: &mut Canvas<Window>, panel: Rect, title: &str) {
+        self.text(canvas, panel, 0, -1, title, TITLE_COLOUR);
+    }
+
+    // V registers in two columns, then pc, i, sp, dt, the call stack and breakpoints
+    fn draw_registers(&self, canvas: &mut Canvas<Window>, machine: &Machine, debugger: &Debugger) {
+        let panel = self.registers;
+        let cpu = &machine.cpu;
+        let v = cpu.v();
+        self.title(canvas, panel, "REGISTERS");
+        for row in 0..8 {
+            let line = format!("V{:X} {:02X}   V{:X} {:02X}", row, v[row], row + 8, v[row + 8]);
+            self.text(canvas, panel, 0, row as i32, &line, TEXT_COLOUR);
+        }
+        self.text(canvas, panel, 0, 9, &format!("PC {:03X}  I {:03X}", cpu.pc(), cpu.i()),
+            TEXT_COLOUR);
+        self.text(canvas, panel, 0, 10, &format!("SP {:X}    DT {:02X}", cpu.sp(), cpu.dt()),
+            TEXT_COLOUR);
+
+        let mut row = 12;
+        self.text(canvas, panel, 0, row, "CALL STACK", TITLE_COLOUR);
+        if cpu.call_stack().is_empty() {
+            row += 1;
+            self.text(canvas, panel, 0, row, "-", TEXT_COLOUR);
+        }
+        // Innermost call first
+        for (depth, addr) in cpu.call_stack().iter().enumerate().rev() {
+            row += 1;
+            self.text(canvas, panel, 0, row, &format!("{:X}  {:03X}", depth, addr), TEXT_COLOUR);
+        }
+
+        row += 2;
+        self.text(canvas, panel, 0, row, "BREAKPOINTS", TITLE_COLOUR);
+        if debugger.breakpoints.is_empty() {
+            row += 1;
+            self.text(canvas, panel, 0, row, "-", TEXT_COLOUR);
+        }
+        for addr in &debugger.breakpoints {
+            row += 1;
+            self.text(canvas, panel, 0, row, &format!("{:03X}", addr), BREAKPOINT_COLOUR);
+        }
+    }
+
+    // Instructions around pc, marked with > for pc and * for breakpoints
+    fn draw_disasm(&mut self, canvas: &mut Canvas<Window>, machine: &Machine, debugger: &Debugger) {
+        let panel = self.disasm;
+        let rows = ((panel.bottom() - self.first_row_y(panel)) / self.line_height()).max(1) as usize;
+        let pc = machine.cpu.pc();
+        let mem = &machine.ram.mem;
+
+        let last_start = mem.len().saturating_sub(rows * 2);
+        let start = pc.saturating_sub(rows / 2 * 2).min(last_start);
+        self.disasm_rows = (0..rows).map(|row| start + row * 2).collect();
+
+        self.title(canvas, panel, "DISASSEMBLY");
+        for (row, &addr) in self.disasm_rows.iter().enumerate() {
+            let opcode = disasm::opcode_at(mem, addr);
+            let breakpoint = debugger.breakpoints.contains(&addr);
+            let line = format!("{}{}{:03X}  {:04X}  {}",
+                if addr == pc { '>' } else { ' ' },
+                if breakpoint { '*' } else { ' ' },
+                addr, opcode, disasm::disassemble_with(opcode, &machine.symbols));
+            let colour = if addr == pc {
+                PC_COLOUR
+            } else if breakpoint {
+                BREAKPOINT_COLOUR
+            } else {
+                TEXT_COLOUR
+            };
+            self.text(canvas, panel, 0, row as i32, &line, colour);
+        }
+    }
+
+    // Hex rows following i, recently written bytes highlighted
+    fn draw_memory(&self, canvas: &mut Canvas<Window>, machine: &Machine) {
+        let panel = self.memory;
+        let ram = &machine.ram;
+        let total_rows = (ram.mem.len() / BYTES_PER_ROW) as i32;
+        let i_row = (machine.cpu.i() / BYTES_PER_ROW) as i32;
+        let first_row = (i_row - MEMORY_ROWS as i32 / 2 + self.memory_scroll)
+            .clamp(0, total_rows - MEMORY_ROWS as i32);
+
+        self.title(canvas, panel, &format!("MEMORY  I {:03X}", machine.cpu.i()));
+        for row in 0..MEMORY_ROWS as i32 {
+            let base = (first_row + row) as usize * BYTES_PER_ROW;
+            self.text(canvas, panel, 0, row, &format!("{:03X}", base), TITLE_COLOUR);
+            for column in 0..BYTES_PER_ROW {
+                let addr = base + column;
+                let colour = if ram.recent_writes[addr] > 0 { WRITE_COLOUR } else { TEXT_COLOUR };
+                self.text(canvas, panel, 5 + column as u32 * 3, row,
+                    &format!("{:02X}", ram.mem[addr]), colour);
+            }
+        }
+    }
+}
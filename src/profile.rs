@@ -0,0 +1,231 @@
+//Module Todo:
+// N/A
+
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs,
+    io,
+};
+
+use serde::Serialize;
+
+use crate::{
+    disasm,
+    ram::RAM_SIZE,
+    symbols::Symbols,
+    ROM_START,
+};
+
+const HOT_ADDRESSES: usize = 20; // Addresses listed in the text report
+
+// One node of the call tree, a subroutine reached through a particular chain of calls
+struct CallNode {
+    addr: usize,
+    parent: Option<usize>,
+    cycles: u64, // Instructions run in this subroutine itself, excluding callees
+}
+
+// Counts instructions per address and opcode class, and per subroutine through a call tree
+// Reports go to PREFIX.txt, PREFIX.json and PREFIX.folded
+pub struct Profiler {
+    prefix: String,
+    instructions: u64,
+    pc_counts: Vec<u64>,
+    class_counts: [u64; 16],
+    calls: HashMap<usize, u64>, // Times each subroutine was called
+    nodes: Vec<CallNode>,
+    children: HashMap<(usize, usize), usize>, // (parent node, subroutine) to child node
+    current: usize, // Node of the running subroutine, the root is the program itself
+}
+
+#[derive(Serialize)]
+struct AddressReport {
+    pc: usize,
+    count: u64,
+    instruction: String,
+}
+
+#[derive(Serialize)]
+struct ClassReport {
+    class: String,
+    count: u64,
+}
+
+#[derive(Serialize)]
+struct SubroutineReport {
+    addr: usize,
+    label: Option<String>,
+    calls: u64,
+    inclusive: u64,
+    exclusive: u64,
+}
+
+#[derive(Serialize)]
+struct Report {
+    instructions: u64,
+    addresses: Vec<AddressReport>,
+    classes: Vec<ClassReport>,
+    subroutines: Vec<SubroutineReport>,
+}
+
+impl Profiler {
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            instructions: 0,
+            pc_counts: vec![0; RAM_SIZE],
+            class_counts: [0; 16],
+            calls: HashMap::new(),
+            nodes: vec![CallNode { addr: ROM_START, parent: None, cycles: 0 }],
+            children: HashMap::new(),
+            current: 0,
+        }
+    }
+
+    // Count an executed instruction, following calls and returns by the stack pointer
+    pub fn record(&mut self, pc: usize, opcode: u16, sp_before: usize, sp_after: usize,
+        pc_after: usize) {
+        self.instructions += 1;
+        self.pc_counts[pc % RAM_SIZE] += 1;
+        self.class_counts[(opcode >> 12) as usize] += 1;
+        // The call counts toward the caller and the return toward the callee
+        self.nodes[self.current].cycles += 1;
+
+        if sp_after > sp_before {
+            *self.calls.entry(pc_after).or_insert(0) += 1;
+            let parent = self.current;
+            let next = self.nodes.len();
+            self.current = *self.children.entry((parent, pc_after)).or_insert(next);
+            if self.current == next {
+                self.nodes.push(CallNode { addr: pc_after, parent: Some(parent), cycles: 0 });
+            }
+        } else if sp_after < sp_before {
+            // Returns past the program's own entry, such as after a reset, stay at the root
+            self.current = self.nodes[self.current].parent.unwrap_or(0);
+        }
+    }
+
+    // Leave every subroutine, as the cpu does on reset
+    pub fn reset_stack(&mut self) {
+        self.current = 0;
+    }
+
+    // Subroutine addresses on the path from the root to a node, outermost first
+    fn path(&self, mut node: usize) -> Vec<usize> {
+        let mut path = vec![self.nodes[node].addr];
+        while let Some(parent) = self.nodes[node].parent {
+            path.push(self.nodes[parent].addr);
+            node = parent;
+        }
+        path.reverse();
+        path
+    }
+
+    fn report(&self, mem: &[u8], symbols: &Symbols) -> Report {
+        let mut addresses: Vec<AddressReport> = (0..RAM_SIZE)
+            .filter(|&pc| self.pc_counts[pc] > 0)
+            .map(|pc| AddressReport {
+                pc,
+                count: self.pc_counts[pc],
+                instruction: disasm::disassemble_with(disasm::opcode_at(mem, pc), symbols),
+            })
+            .collect();
+        addresses.sort_by(|a, b| b.count.cmp(&a.count).then(a.pc.cmp(&b.pc)));
+
+        let classes = (0..16)
+            .filter(|&class| self.class_counts[class] > 0)
+            .map(|class| ClassReport {
+                class: format!("{:X}", class),
+                count: self.class_counts[class],
+            })
+            .collect();
+
+        // Inclusive time counts each node once per subroutine, even through recursion
+        let mut inclusive: HashMap<usize, u64> = HashMap::new();
+        let mut exclusive: HashMap<usize, u64> = HashMap::new();
+        for (index, node) in self.nodes.iter().enumerate().skip(1) {
+            *exclusive.entry(node.addr).or_insert(0) += node.cycles;
+            // Skip the root, which is the program rather than a subroutine
+            let mut path = self.path(index).split_off(1);
+            path.sort_unstable();
+            path.dedup();
+            for addr in path {
+                *inclusive.entry(addr).or_insert(0) += node.cycles;
+            }
+        }
+        let mut subroutines: Vec<SubroutineReport> = self.calls.iter()
+            .map(|(&addr, &calls)| SubroutineReport {
+                addr,
+                label: symbols.label(addr).map(String::from),
+                calls,
+                inclusive: inclusive.get(&addr).copied().unwrap_or(0),
+                exclusive: exclusive.get(&addr).copied().unwrap_or(0),
+            })
+            .collect();
+        subroutines.sort_by(|a, b| b.inclusive.cmp(&a.inclusive).then(a.addr.cmp(&b.addr)));
+
+        Report {
+            instructions: self.instructions,
+            addresses,
+            classes,
+            subroutines,
+        }
+    }
+
+    fn text_report(&self, report: &Report) -> String {
+        let percent = |count: u64| 100.0 * count as f64 / self.instructions.max(1) as f64;
+        let mut text = format!("Profile of {} instructions\n\nHot addresses\n", report.instructions);
+        for address in report.addresses.iter().take(HOT_ADDRESSES) {
+            let _ = writeln!(text, "  {:03X}  {:>10}  {:>5.1}%  {}", address.pc, address.count,
+                percent(address.count), address.instruction);
+        }
+        text.push_str("\nOpcode classes\n");
+        for class in &report.classes {
+            let _ = writeln!(text, "  {}xxx  {:>10}  {:>5.1}%", class.class, class.count,
+                percent(class.count));
+        }
+        text.push_str("\nSubroutines       calls   inclusive   exclusive\n");
+        for sub in &report.subroutines {
+            let line = format!("  {:03X}      {:>10}  {:>10}  {:>10}  {}", sub.addr, sub.calls,
+                sub.inclusive, sub.exclusive, sub.label.as_deref().unwrap_or(""));
+            let _ = writeln!(text, "{}", line.trim_end());
+        }
+        text
+    }
+
+    // One line per call chain, in the folded format read by flamegraph tools
+    fn folded(&self, symbols: &Symbols) -> String {
+        let mut lines: Vec<String> = self.nodes.iter()
+            .enumerate()
+            .filter(|(_, node)| node.cycles > 0)
+            .map(|(index, node)| {
+                let frames: Vec<String> = self.path(index).iter()
+                    .enumerate()
+                    .map(|(depth, addr)| if depth == 0 {
+                        String::from("main")
+                    } else {
+                        symbols.label(*addr).map_or_else(|| format!("sub_{:03X}", addr), String::from)
+                    })
+                    .collect();
+                format!("{} {}", frames.join(";"), node.cycles)
+            })
+            .collect();
+        lines.sort();
+        lines.join("\n") + "\n"
+    }
+
+    // Write every report, disassembling hot addresses from the current memory
+    pub fn write_reports(&self, mem: &[u8], symbols: &Symbols) -> io::Result<()> {
+        let report = self.report(mem, symbols);
+
+        fs::write(format!("{}.txt", self.prefix), self.text_report(&report))?;
+        let json = serde_json::to_string_pretty(&report).map_err(io::Error::other)?;
+        fs::write(format!("{}.json", self.prefix), json + "\n")?;
+        fs::write(format!("{}.folded", self.prefix), self.folded(symbols))
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+}
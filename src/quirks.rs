@@ -0,0 +1,128 @@
+//Module Todo:
+// Add the remaining test suite quirks
+
+// Behaviour differences between Chip 8 interpreters
+// Names in comments are the matching chip-8-database quirk names
+#[derive(Clone, Copy, Debug)]
+pub struct Quirks {
+    pub vf_reset: bool, // 8xy1, 8xy2 and 8xy3 reset vf to 0 (logic)
+    pub shift_vx: bool, // 8xy6 and 8xye shift vx in place instead of vy (shift)
+    pub memory_leave_i: bool, // Fx55 and fx65 leave i unchanged (memoryLeaveIUnchanged)
+    pub memory_increment_by_x: bool, // Fx55 and fx65 add x instead of x + 1 to i (memoryIncrementByX)
+    pub jump_vx: bool, // Bnnn jumps to xnn + vx instead of nnn + v0 (jump)
+    pub display_wait: bool, // Dxyn waits for the next frame before drawing (vblank)
+    pub wrap_sprites: bool, // Sprites wrap around screen edges instead of clipping (wrap)
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Quirks {
+    pub fn new() -> Self {
+        Self {
+            vf_reset: true,
+            shift_vx: true,
+            memory_leave_i: true,
+            memory_increment_by_x: false,
+            jump_vx: true,
+            display_wait: false,
+            wrap_sprites: false,
+        }
+    }
+
+    // Defaults of the chip-8-database originalChip8, superchip and xochip platforms
+    pub fn for_platform(platform: Platform) -> Self {
+        let original = platform == Platform::Chip8;
+        let superchip = platform == Platform::SuperChip;
+        Self {
+            vf_reset: original,
+            shift_vx: superchip,
+            memory_leave_i: superchip,
+            memory_increment_by_x: false,
+            jump_vx: superchip,
+            display_wait: original,
+            wrap_sprites: platform == Platform::XoChip,
+        }
+    }
+
+    // Look up a quirk by its chip-8-database name
+    fn by_name(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "logic" => Some(&mut self.vf_reset),
+            "shift" => Some(&mut self.shift_vx),
+            "memoryLeaveIUnchanged" => Some(&mut self.memory_leave_i),
+            "memoryIncrementByX" => Some(&mut self.memory_increment_by_x),
+            "jump" => Some(&mut self.jump_vx),
+            "vblank" => Some(&mut self.display_wait),
+            "wrap" => Some(&mut self.wrap_sprites),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<bool> {
+        let mut quirks = *self;
+        quirks.by_name(name).map(|quirk| *quirk)
+    }
+
+    // Set a quirk by its chip-8-database name, returning false for unknown names
+    pub fn set(&mut self, name: &str, value: bool) -> bool {
+        match self.by_name(name) {
+            Some(quirk) => {
+                *quirk = value;
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+// Chip 8 variants a rom can target
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
+pub enum Platform {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    pub const ALL: [Platform; 3] = [Platform::Chip8, Platform::SuperChip, Platform::XoChip];
+
+    // Guess the target platform from a rom file extension
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "ch8" | "c8" => Some(Platform::Chip8),
+            "sc8" => Some(Platform::SuperChip),
+            "xo8" => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+
+    // Platform given on the command line
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "chip8" => Some(Platform::Chip8),
+            "schip" | "superchip" => Some(Platform::SuperChip),
+            "xochip" => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+
+    // Bytes of memory the platform addresses, including the interpreter area below the rom
+    pub fn memory_size(&self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::SuperChip => 0x1000,
+            Platform::XoChip => 0x10000,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::SuperChip => "SUPER-CHIP",
+            Platform::XoChip => "XO-CHIP",
+        }
+    }
+}
@@ -1,28 +1,55 @@
 //Module Todo:
 // This module is in need of refactoring at some point
 // Test read and write byte functions?
-// Move load_font_set to display module?
 
 // use crate::cpu::Cpu;
 
-const RAM_SIZE: usize = 0xFFF; //0xFFF = 4096
+pub const RAM_SIZE: usize = 0x1000; //0x1000 = 4096
+const WRITE_HIGHLIGHT_FRAMES: u8 = 60; // How long debuggers show a write as recent
+
+const FONT_SET: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
 
 pub struct Ram {
     pub mem: [u8; RAM_SIZE],
+    pub recent_writes: [u8; RAM_SIZE], // Frames left to show each address as recently written
+    pub step_writes: Vec<usize>, // Addresses written by the current instruction
+}
+
+impl Default for Ram {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Ram {
     pub fn new() -> Self {
         Self {
             mem: [0; RAM_SIZE],
+            recent_writes: [0; RAM_SIZE],
+            step_writes: Vec::new(),
         }
     }
 
-    //Potentially move this function to display module later
+    // Copy the font sprites into the interpreter area at the start of ram
     pub fn load_font_set(&mut self) {
-        for i in 0..80 {
-            self.mem[i] = crate::display::FONT_SET[i];
-        };
+        self.mem[..FONT_SET.len()].copy_from_slice(&FONT_SET);
     }
 
     // // Do I need a ram read function here based on cpu.i?
@@ -41,5 +68,14 @@ impl Ram {
 
     pub fn write_ram(&mut self, addr: usize, data: u8) {
         self.mem[addr] = data;
+        self.recent_writes[addr] = WRITE_HIGHLIGHT_FRAMES;
+        self.step_writes.push(addr);
+    }
+
+    // Count down the recent write highlights once per frame
+    pub fn age_writes(&mut self) {
+        for frames in self.recent_writes.iter_mut() {
+            *frames = frames.saturating_sub(1);
+        }
     }
 }
\ No newline at end of file
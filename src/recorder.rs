@@ -0,0 +1,141 @@
+//Module Todo:
+// N/A
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    screenshot,
+    CHIP8_WIDTH,
+    CHIP8_HEIGHT,
+};
+
+const RECORD_SCALE: u32 = 4;
+const RECORD_WIDTH: u32 = CHIP8_WIDTH * RECORD_SCALE;
+const RECORD_HEIGHT: u32 = CHIP8_HEIGHT * RECORD_SCALE;
+const FRAME_RATE: u64 = 60;
+const GIF_QUANT_SPEED: i32 = 10;
+
+enum Encoder {
+    Gif(gif::Encoder<BufWriter<File>>),
+    Y4m(Box<dyn Write>),
+}
+
+// Records presented frames to an animated gif or an uncompressed y4m stream
+pub struct Recorder {
+    encoder: Encoder,
+    frame_count: u64,
+}
+
+impl Recorder {
+    // Start a recording, picking the format from the file extension
+    // A path of "-" writes a y4m stream to stdout
+    pub fn new(path: &str) -> io::Result<Self> {
+        let encoder = if path == "-" {
+            Self::y4m_encoder(Box::new(BufWriter::new(io::stdout())))?
+        } else {
+            let file = BufWriter::new(File::create(path)?);
+            match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+                Some("gif") => {
+                    let mut encoder = gif::Encoder::new(
+                        file, RECORD_WIDTH as u16, RECORD_HEIGHT as u16, &[],
+                    ).map_err(io::Error::other)?;
+                    encoder.set_repeat(gif::Repeat::Infinite).map_err(io::Error::other)?;
+                    Encoder::Gif(encoder)
+                },
+                Some("y4m") => Self::y4m_encoder(Box::new(file))?,
+                _ => return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Unknown recording format for {}, use .gif or .y4m", path),
+                )),
+            }
+        };
+
+        Ok(Self {
+            encoder,
+            frame_count: 0,
+        })
+    }
+
+    fn y4m_encoder(mut writer: Box<dyn Write>) -> io::Result<Encoder> {
+        writeln!(writer, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C420jpeg",
+            RECORD_WIDTH, RECORD_HEIGHT, FRAME_RATE)?;
+        Ok(Encoder::Y4m(writer))
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    // Append an RGBA frame to the recording
+    pub fn add_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        let rgb = screenshot::frame_to_rgb(frame, RECORD_SCALE);
+
+        match &mut self.encoder {
+            Encoder::Gif(encoder) => {
+                // Gif delays are in 1/100s, so spread 60Hz frames over 1 and 2 unit delays
+                let start = self.frame_count * 100 / FRAME_RATE;
+                let end = (self.frame_count + 1) * 100 / FRAME_RATE;
+                let mut gif_frame = gif::Frame::from_rgb_speed(
+                    RECORD_WIDTH as u16, RECORD_HEIGHT as u16, &rgb, GIF_QUANT_SPEED,
+                );
+                gif_frame.delay = (end - start) as u16;
+                encoder.write_frame(&gif_frame).map_err(io::Error::other)?;
+            },
+            Encoder::Y4m(writer) => {
+                writer.write_all(b"FRAME\n")?;
+                writer.write_all(&rgb_to_yuv420(&rgb))?;
+            },
+        }
+
+        self.frame_count += 1;
+        Ok(())
+    }
+
+    // Flush any buffered output and close the recording
+    pub fn finish(self) -> io::Result<()> {
+        match self.encoder {
+            Encoder::Gif(encoder) => {
+                encoder.into_inner().map_err(io::Error::other)?.flush()
+            },
+            Encoder::Y4m(mut writer) => writer.flush(),
+        }
+    }
+}
+
+// Convert RGB rows to planar full range YUV 4:2:0
+fn rgb_to_yuv420(rgb: &[u8]) -> Vec<u8> {
+    let width = RECORD_WIDTH as usize;
+    let height = RECORD_HEIGHT as usize;
+    let mut y_plane = Vec::with_capacity(width * height);
+    let mut u_plane = Vec::with_capacity(width * height / 4);
+    let mut v_plane = Vec::with_capacity(width * height / 4);
+
+    for row in 0..height {
+        for col in 0..width {
+            let pixel = (row * width + col) * 3;
+            let (r, g, b) = (rgb[pixel] as f32, rgb[pixel + 1] as f32, rgb[pixel + 2] as f32);
+            y_plane.push((0.299 * r + 0.587 * g + 0.114 * b).round() as u8);
+
+            // Record scale is even, so every 2x2 block shares a colour
+            if row % 2 == 0 && col % 2 == 0 {
+                u_plane.push((128.0 - 0.168736 * r - 0.331264 * g + 0.5 * b).round() as u8);
+                v_plane.push((128.0 + 0.5 * r - 0.418688 * g - 0.081312 * b).round() as u8);
+            }
+        }
+    }
+
+    y_plane.append(&mut u_plane);
+    y_plane.append(&mut v_plane);
+    y_plane
+}
+
+// Build a recording path in dir from the rom name and starting frame number
+#[cfg(feature = "sdl")]
+pub fn recording_path(dir: &str, rom_path: &str, frame_count: u64, extension: &str) -> String {
+    let file_name = format!("{}_{:06}.{}", screenshot::rom_name(rom_path), frame_count, extension);
+    Path::new(dir).join(file_name).to_string_lossy().into_owned()
+}
@@ -0,0 +1,156 @@
+//Module Todo:
+// N/A
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+pub const FRAME_RATE: u64 = 60;
+pub const DEFAULT_CLOCK_HZ: u32 = 700;
+pub const MAX_CLOCK_HZ: u32 = 10_000_000; // Far beyond any rom, and the tick carry can't overflow
+const MAX_CATCH_UP_FRAMES: u32 = 5; // Frames run at once before skipping ahead
+const UNCAPPED_BATCH_FRAMES: u32 = 10; // Frames run per present when uncapped
+pub const MIN_SPEED_SCALE: f64 = 0.01;
+pub const MAX_SPEED_SCALE: f64 = 1000.0;
+
+// Emulation speed relative to real time
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Speed {
+    Scaled(f64), // Multiple of normal 60Hz speed
+    Uncapped, // Run frames as fast as possible
+}
+
+impl Speed {
+    // Speed from a multiple of normal speed, 0 for uncapped
+    // Scales outside MIN_SPEED_SCALE to MAX_SPEED_SCALE, or not a number, are refused
+    pub fn from_scale(scale: f64) -> Option<Self> {
+        if scale == 0.0 {
+            Some(Speed::Uncapped)
+        } else {
+            (MIN_SPEED_SCALE..=MAX_SPEED_SCALE).contains(&scale).then_some(Speed::Scaled(scale))
+        }
+    }
+}
+
+// Whether a clock rate can be scheduled, at least one instruction a second up to MAX_CLOCK_HZ
+pub fn valid_clock_hz(hz: u32) -> bool {
+    (1..=MAX_CLOCK_HZ).contains(&hz)
+}
+
+// Fixed timestep scheduler that runs emulation frames at exactly 60Hz
+// Timers tick once per emulation frame, so they stay in step at any speed
+pub struct Scheduler {
+    clock_hz: u32,
+    tick_remainder: u32, // Leftover clock cycles carried between frames
+    speed: Speed,
+    start: Instant,
+    frame_index: u64, // Frames scheduled since start
+}
+
+impl Scheduler {
+    pub fn new(clock_hz: u32) -> Self {
+        Self {
+            clock_hz,
+            tick_remainder: 0,
+            speed: Speed::Scaled(1.0),
+            start: Instant::now(),
+            frame_index: 0,
+        }
+    }
+
+    // Change speed, restarting the schedule so no backlog carries over
+    #[cfg(feature = "sdl")]
+    pub fn set_speed(&mut self, speed: Speed) {
+        if speed != self.speed {
+            self.speed = speed;
+            self.resync();
+        }
+    }
+
+    // Restart frame deadlines from now
+    pub fn resync(&mut self) {
+        self.start = Instant::now();
+        self.frame_index = 0;
+    }
+
+    // Frame deadlines are computed from the start time so rounding never drifts
+    fn frame_deadline(&self, frame_index: u64) -> Instant {
+        match self.speed {
+            Speed::Scaled(scale) => self.start
+                + Duration::from_secs_f64(frame_index as f64 / (FRAME_RATE as f64 * scale)),
+            Speed::Uncapped => self.start,
+        }
+    }
+
+    // Number of emulation frames due by now
+    // If emulation falls too far behind, skip ahead rather than spiral
+    pub fn frames_due(&mut self) -> u32 {
+        if self.speed == Speed::Uncapped {
+            return UNCAPPED_BATCH_FRAMES;
+        }
+
+        let now = Instant::now();
+        let mut frames = 0;
+
+        while now >= self.frame_deadline(self.frame_index + 1) {
+            if frames == MAX_CATCH_UP_FRAMES {
+                self.resync();
+                break;
+            }
+            self.frame_index += 1;
+            frames += 1;
+        }
+
+        frames
+    }
+
+    // Cpu ticks to run for one frame, carrying the remainder of the clock rate
+    pub fn ticks_for_frame(&mut self) -> u32 {
+        self.tick_remainder += self.clock_hz;
+        let ticks = self.tick_remainder / FRAME_RATE as u32;
+        self.tick_remainder %= FRAME_RATE as u32;
+        ticks
+    }
+
+    // Sleep until the next frame is due
+    pub fn sleep_until_next_frame(&self) {
+        let deadline = self.frame_deadline(self.frame_index + 1);
+        let now = Instant::now();
+        if deadline > now {
+            thread::sleep(deadline - now);
+        }
+    }
+
+    // Wait one normal length frame while paused without building a backlog
+    pub fn idle(&mut self) {
+        thread::sleep(Duration::from_nanos(1_000_000_000 / FRAME_RATE));
+        self.resync();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_carry_the_remainder_at_the_clock_limits() {
+        let mut scheduler = Scheduler::new(1);
+        let ticks: u32 = (0..FRAME_RATE).map(|_frame| scheduler.ticks_for_frame()).sum();
+        assert_eq!(ticks, 1);
+
+        let mut scheduler = Scheduler::new(MAX_CLOCK_HZ);
+        let ticks: u32 = (0..FRAME_RATE).map(|_frame| scheduler.ticks_for_frame()).sum();
+        assert_eq!(ticks, MAX_CLOCK_HZ);
+        assert!(!valid_clock_hz(0) && !valid_clock_hz(MAX_CLOCK_HZ + 1));
+    }
+
+    #[test]
+    fn speed_scales_out_of_range_are_refused() {
+        assert_eq!(Speed::from_scale(0.0), Some(Speed::Uncapped));
+        assert_eq!(Speed::from_scale(4.0), Some(Speed::Scaled(4.0)));
+        for scale in [1e-300, -1.0, f64::NAN, f64::INFINITY, MAX_SPEED_SCALE * 2.0] {
+            assert_eq!(Speed::from_scale(scale), None, "{}", scale);
+        }
+    }
+}
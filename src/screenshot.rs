@@ -0,0 +1,71 @@
+//Module Todo:
+// N/A
+
+use std::path::Path;
+#[cfg(feature = "sdl")]
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::PathBuf,
+};
+
+use crate::{
+    CHIP8_WIDTH,
+    CHIP8_HEIGHT,
+};
+
+// Convert an RGBA frame to scaled RGB rows for image output
+// Frame bytes are stored as A, B, G, R per pixel to match the RGBA8888 texture
+pub fn frame_to_rgb(frame: &[u8], scale: u32) -> Vec<u8> {
+    let width = (CHIP8_WIDTH * scale) as usize;
+    let height = (CHIP8_HEIGHT * scale) as usize;
+    let mut rgb = Vec::with_capacity(width * height * 3);
+
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = ((y / scale as usize) * CHIP8_WIDTH as usize + x / scale as usize) * 4;
+            rgb.push(frame[pixel + 3]);
+            rgb.push(frame[pixel + 2]);
+            rgb.push(frame[pixel + 1]);
+        }
+    }
+
+    rgb
+}
+
+// Save an RGBA frame as a png, scaled up by an integer factor
+#[cfg(feature = "sdl")]
+pub fn save_png(frame: &[u8], scale: u32, path: &Path) -> Result<(), png::EncodingError> {
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(file),
+        CHIP8_WIDTH * scale,
+        CHIP8_HEIGHT * scale,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&frame_to_rgb(frame, scale))?;
+    Ok(())
+}
+
+// Rom file name without its directory or extension, used to name captures
+pub fn rom_name(rom_path: &str) -> &str {
+    Path::new(rom_path)
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or("chip8")
+}
+
+// Build a screenshot file name from the rom name and frame number
+#[cfg(feature = "sdl")]
+pub fn screenshot_path(rom_path: &str, frame_count: u64, scale: u32) -> PathBuf {
+    let rom_name = rom_name(rom_path);
+
+    if scale == 1 {
+        PathBuf::from(format!("{}_{:06}.png", rom_name, frame_count))
+    } else {
+        PathBuf::from(format!("{}_{:06}_x{}.png", rom_name, frame_count, scale))
+    }
+}
@@ -0,0 +1,86 @@
+//Module Todo:
+// N/A
+
+use crate::ram::RAM_SIZE;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SmcMode {
+    Warn, // Report and keep running
+    Break, // Stop in the debugger, headless runs only warn
+}
+
+impl SmcMode {
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "warn" => Some(SmcMode::Warn),
+            "break" => Some(SmcMode::Break),
+            _ => None,
+        }
+    }
+}
+
+// Tracks which bytes have run as code and which the program wrote, to catch writes into
+// code that already ran and execution of bytes the program wrote as data
+// Each instruction is reported once for each kind of problem
+pub struct SmcDetector {
+    pub mode: SmcMode,
+    executed: Vec<bool>,
+    written: Vec<bool>,
+    reported_data: Vec<bool>, // Instructions reported for running written data
+    reported_writes: Vec<bool>, // Instructions reported for writing over code
+    pending: Vec<String>, // Reports not yet shown by the frontend
+}
+
+impl SmcDetector {
+    pub fn new(mode: SmcMode) -> Self {
+        Self {
+            mode,
+            executed: vec![false; RAM_SIZE],
+            written: vec![false; RAM_SIZE],
+            reported_data: vec![false; RAM_SIZE],
+            reported_writes: vec![false; RAM_SIZE],
+            pending: Vec::new(),
+        }
+    }
+
+    // Check an instruction that ran at pc and the addresses it wrote
+    pub fn record(&mut self, pc: usize, writes: &[usize]) {
+        let pc = pc % RAM_SIZE;
+        let bytes = [pc, (pc + 1) % RAM_SIZE];
+        let runs_data = bytes.iter().any(|&addr| self.written[addr] && !self.executed[addr]);
+        if runs_data && !self.reported_data[pc] {
+            self.reported_data[pc] = true;
+            self.pending.push(format!("Executing data written by the program at {:03X}", pc));
+        }
+        for addr in bytes {
+            self.executed[addr] = true;
+        }
+
+        let overwritten: Vec<usize> = writes.iter().copied()
+            .filter(|&addr| self.executed[addr])
+            .collect();
+        if let (Some(first), Some(last)) = (overwritten.first(), overwritten.last()) {
+            if !self.reported_writes[pc] {
+                self.reported_writes[pc] = true;
+                let range = if first == last {
+                    format!("{:03X}", first)
+                } else {
+                    format!("{:03X}-{:03X}", first, last)
+                };
+                self.pending.push(format!("Instruction at {:03X} overwrote code at {}", pc, range));
+            }
+        }
+        for &addr in writes {
+            self.written[addr] = true;
+        }
+    }
+
+    // Reports since the last call, oldest first
+    pub fn take_reports(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.pending)
+    }
+
+    pub fn has_reports(&self) -> bool {
+        !self.pending.is_empty()
+    }
+}
@@ -0,0 +1,127 @@
+//Module Todo:
+// N/A
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::debugger;
+
+#[derive(Debug)]
+pub enum SymbolError {
+    Io(io::Error),
+    Parse(usize, String), // Line number and the offending line
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolError::Io(e) => write!(f, "{}", e),
+            SymbolError::Parse(line, text) => write!(f, "line {}: expected \"label = addr\" or \"addr label\", found {}",
+                line, text),
+        }
+    }
+}
+
+// Labels for rom addresses, from an Octo style .sym file or plain "addr label" lines
+#[derive(Default)]
+pub struct Symbols {
+    labels: BTreeMap<usize, String>,
+    addresses: HashMap<String, usize>,
+}
+
+// A hex address with an optional 0x or # prefix
+fn parse_symbol_address(text: &str) -> Option<usize> {
+    debugger::parse_address(text.strip_prefix('#').unwrap_or(text))
+}
+
+// One symbol definition, either Octo style "label = addr" or plain "addr label"
+// Each format has one order and labels must start with a letter or _, so a label that looks
+// like hex, such as add or fed, is never read as an address
+fn parse_line(code: &str) -> Option<(usize, &str)> {
+    let (addr, label) = match code.split_whitespace().collect::<Vec<_>>()[..] {
+        [label, "=", addr] => (addr, label),
+        [addr, label] => (addr, label),
+        _ => return None,
+    };
+    let identifier = label.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_');
+    Some((parse_symbol_address(addr)?, label)).filter(|_| identifier)
+}
+
+impl Symbols {
+    // Read a symbol file where each line is "label = addr" or "addr label"
+    // Blank lines and anything after a ; are ignored
+    pub fn load(path: &Path) -> Result<Self, SymbolError> {
+        Self::parse(&fs::read_to_string(path).map_err(SymbolError::Io)?)
+    }
+
+    fn parse(text: &str) -> Result<Self, SymbolError> {
+        let mut symbols = Self::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let code = line.split(';').next().unwrap_or("");
+            if code.trim().is_empty() {
+                continue;
+            }
+            let (addr, label) = parse_line(code)
+                .ok_or_else(|| SymbolError::Parse(number + 1, line.trim().to_string()))?;
+            symbols.insert(addr, label);
+        }
+        Ok(symbols)
+    }
+
+    // The first label given for an address is the one shown
+    fn insert(&mut self, addr: usize, label: &str) {
+        self.labels.entry(addr).or_insert_with(|| label.to_string());
+        self.addresses.insert(label.to_string(), addr);
+    }
+
+    pub fn len(&self) -> usize {
+        self.addresses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+
+    pub fn label(&self, addr: usize) -> Option<&str> {
+        self.labels.get(&addr).map(String::as_str)
+    }
+
+    // Resolve a label, or failing that a hex address
+    pub fn resolve(&self, text: &str) -> Option<usize> {
+        self.addresses.get(text).copied().or_else(|| debugger::parse_address(text))
+    }
+}
+
+// The symbol file looked for beside a rom: game.sym, then game.ch8.sym
+pub fn default_path(rom_path: &str) -> Option<PathBuf> {
+    let rom = Path::new(rom_path);
+    [rom.with_extension("sym"), PathBuf::from(format!("{}.sym", rom_path))]
+        .into_iter()
+        .find(|path| path.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_looking_labels_are_not_addresses() {
+        let symbols = Symbols::parse("add = 0x210\nfed = #2A0\n300 abc ; plain\n\n0x302 fed2").unwrap();
+        assert_eq!(symbols.resolve("add"), Some(0x210));
+        assert_eq!(symbols.resolve("fed"), Some(0x2A0));
+        assert_eq!(symbols.label(0x300), Some("abc"));
+        assert_eq!(symbols.label(0x302), Some("fed2"));
+    }
+
+    #[test]
+    fn label_before_address_needs_an_equals_sign() {
+        assert!(matches!(Symbols::parse("add 0x210"), Err(SymbolError::Parse(1, _))));
+        assert!(matches!(Symbols::parse("main 0x200"), Err(SymbolError::Parse(1, _))));
+    }
+}
@@ -0,0 +1,416 @@
+//Module Todo:
+// N/A
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    cpu::Cpu,
+    debugger,
+    disasm,
+    symbols::Symbols,
+};
+
+const BINARY_MAGIC: &[u8; 4] = b"C8TR";
+const BINARY_VERSION: u8 = 2; // Version 1 traces had no ST changes and still read fine
+
+// Register ids used in binary records, 0x0 to 0xF are v0 to vf
+const REGISTER_I: u8 = 0x10;
+const REGISTER_SP: u8 = 0x11;
+const REGISTER_DT: u8 = 0x12;
+const REGISTER_ST: u8 = 0x13;
+
+// Register values compared before and after each instruction
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct Registers {
+    pub v: [u8; 16],
+    pub i: usize,
+    pub sp: usize,
+    pub dt: u8,
+    pub st: u8,
+}
+
+impl Registers {
+    pub fn of(cpu: &Cpu) -> Self {
+        Self {
+            v: *cpu.v(),
+            i: cpu.i(),
+            sp: cpu.sp(),
+            dt: cpu.dt(),
+            st: cpu.st(),
+        }
+    }
+
+    // Registers whose value differs in after, as (id, new value)
+    pub fn changes(&self, after: &Self) -> Vec<(u8, u16)> {
+        let mut changes: Vec<(u8, u16)> = (0..16)
+            .filter(|&x| self.v[x] != after.v[x])
+            .map(|x| (x as u8, after.v[x] as u16))
+            .collect();
+        if self.i != after.i {
+            changes.push((REGISTER_I, after.i as u16));
+        }
+        if self.sp != after.sp {
+            changes.push((REGISTER_SP, after.sp as u16));
+        }
+        if self.dt != after.dt {
+            changes.push((REGISTER_DT, after.dt as u16));
+        }
+        if self.st != after.st {
+            changes.push((REGISTER_ST, after.st as u16));
+        }
+        changes
+    }
+}
+
+fn register_name(id: u8) -> String {
+    match id {
+        REGISTER_I => String::from("I"),
+        REGISTER_SP => String::from("SP"),
+        REGISTER_DT => String::from("DT"),
+        REGISTER_ST => String::from("ST"),
+        x => format!("V{:X}", x),
+    }
+}
+
+fn register_id(name: &str) -> Option<u8> {
+    match name {
+        "I" => Some(REGISTER_I),
+        "SP" => Some(REGISTER_SP),
+        "DT" => Some(REGISTER_DT),
+        "ST" => Some(REGISTER_ST),
+        _ => name.strip_prefix('V')
+            .and_then(|x| u8::from_str_radix(x, 16).ok())
+            .filter(|x| *x < 0x10),
+    }
+}
+
+// One executed instruction and the registers it changed, as (id, new value)
+#[derive(Clone, PartialEq, Debug)]
+pub struct TraceRecord {
+    pub cycle: u64,
+    pub pc: usize,
+    pub opcode: u16,
+    pub changes: Vec<(u8, u16)>,
+}
+
+impl TraceRecord {
+    pub fn to_text(&self, symbols: &Symbols) -> String {
+        let changes: Vec<String> = self.changes.iter()
+            .map(|&(id, value)| match id {
+                REGISTER_I => format!("I={:03X}", value),
+                _ => format!("{}={:02X}", register_name(id), value),
+            })
+            .collect();
+        let line = format!("{:>10} {:03X} {:04X} {:<16} {}",
+            self.cycle, self.pc, self.opcode, disasm::disassemble_with(self.opcode, symbols), changes.join(" "));
+        line.trim_end().to_string()
+    }
+
+    // Parse a text record, skipping the mnemonic between the opcode and the changes
+    fn from_text(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        let cycle = fields.next()?.parse().ok()?;
+        let pc = usize::from_str_radix(fields.next()?, 16).ok()?;
+        let opcode = u16::from_str_radix(fields.next()?, 16).ok()?;
+        let changes = fields
+            .filter_map(|field| field.split_once('='))
+            .map(|(name, value)| Some((register_id(name)?, u16::from_str_radix(value, 16).ok()?)))
+            .collect::<Option<_>>()?;
+        Some(Self { cycle, pc, opcode, changes })
+    }
+
+    // cycle u64, pc u16, opcode u16, change count u8, then (id u8, value u16) pairs
+    fn to_binary(&self) -> Vec<u8> {
+        let mut record = Vec::with_capacity(13 + self.changes.len() * 3);
+        record.extend_from_slice(&self.cycle.to_le_bytes());
+        record.extend_from_slice(&(self.pc as u16).to_le_bytes());
+        record.extend_from_slice(&self.opcode.to_le_bytes());
+        record.push(self.changes.len() as u8);
+        for (id, value) in &self.changes {
+            record.push(*id);
+            record.extend_from_slice(&value.to_le_bytes());
+        }
+        record
+    }
+
+    fn from_binary(bytes: &[u8]) -> Option<(Self, usize)> {
+        let header = bytes.get(..13)?;
+        let count = header[12] as usize;
+        let changes = bytes.get(13..13 + count * 3)?
+            .chunks(3)
+            .map(|change| (change[0] <= REGISTER_ST)
+                .then(|| (change[0], u16::from_le_bytes([change[1], change[2]]))))
+            .collect::<Option<_>>()?;
+        let record = Self {
+            cycle: u64::from_le_bytes(header[..8].try_into().ok()?),
+            pc: u16::from_le_bytes([header[8], header[9]]) as usize,
+            opcode: u16::from_le_bytes([header[10], header[11]]),
+            changes,
+        };
+        Some((record, 13 + count * 3))
+    }
+
+    // Apply the changed registers to a running register state
+    pub fn apply(&self, registers: &mut Registers) {
+        for &(id, value) in &self.changes {
+            match id {
+                REGISTER_I => registers.i = value as usize,
+                REGISTER_SP => registers.sp = value as usize,
+                REGISTER_DT => registers.dt = value as u8,
+                REGISTER_ST => registers.st = value as u8,
+                x => registers.v[x as usize] = value as u8,
+            }
+        }
+    }
+}
+
+// Read a text or binary trace, telling them apart by the binary header
+pub fn read_trace(path: &str) -> io::Result<Vec<TraceRecord>> {
+    let bytes = fs::read(path)?;
+    let invalid = |what: String| io::Error::new(io::ErrorKind::InvalidData, what);
+
+    if let Some(mut records) = bytes.strip_prefix(BINARY_MAGIC.as_slice()) {
+        if !records.first().is_some_and(|version| (1..=BINARY_VERSION).contains(version)) {
+            return Err(invalid(format!("Unsupported trace version in {}", path)));
+        }
+        records = &records[1..];
+        let mut trace = Vec::new();
+        while !records.is_empty() {
+            let (record, len) = TraceRecord::from_binary(records)
+                .ok_or_else(|| invalid(format!("Truncated trace record in {}", path)))?;
+            trace.push(record);
+            records = &records[len..];
+        }
+        return Ok(trace);
+    }
+
+    String::from_utf8_lossy(&bytes).lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| TraceRecord::from_text(line)
+            .ok_or_else(|| invalid(format!("Invalid trace record on line {} of {}", number + 1, path))))
+        .collect()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TraceFormat {
+    Text, // One line per instruction
+    Binary, // Fixed header then packed little endian records
+}
+
+// Which executed instructions are written
+// Tracing starts disarmed when a start trigger is set, and each stop trigger disarms it again
+#[derive(Clone, Default)]
+pub struct TraceFilter {
+    pub range: Option<(usize, usize)>, // Inclusive pc range
+    pub classes: Vec<u8>, // Opcode high nibbles, empty for all
+    pub start: Option<usize>,
+    pub stop: Option<usize>,
+}
+
+impl TraceFilter {
+    fn matches(&self, pc: usize, opcode: u16) -> bool {
+        let in_range = self.range.is_none_or(|(start, end)| (start..=end).contains(&pc));
+        let class = (opcode >> 12) as u8;
+        in_range && (self.classes.is_empty() || self.classes.contains(&class))
+    }
+}
+
+// Parse a pc range given as START-END in hex
+pub fn parse_range(text: &str) -> Option<(usize, usize)> {
+    let (start, end) = text.split_once('-')?;
+    let start = debugger::parse_address(start)?;
+    let end = debugger::parse_address(end)?;
+    (start <= end).then_some((start, end))
+}
+
+// Parse a comma separated list of opcode classes given as their first hex digit
+pub fn parse_classes(text: &str) -> Option<Vec<u8>> {
+    text.split(',')
+        .map(|class| u8::from_str_radix(class.trim(), 16).ok().filter(|class| *class < 0x10))
+        .collect()
+}
+
+// Writes a record for each executed instruction that passes the filter
+pub struct Tracer {
+    writer: Box<dyn Write>,
+    format: TraceFormat,
+    filter: TraceFilter,
+    armed: bool,
+}
+
+impl Tracer {
+    // Start a trace, binary for a .bin path and text otherwise
+    // A path of "-" writes text to stdout
+    pub fn new(path: &str, filter: TraceFilter) -> io::Result<Self> {
+        let (writer, format): (Box<dyn Write>, _) = if path == "-" {
+            (Box::new(BufWriter::new(io::stdout())), TraceFormat::Text)
+        } else {
+            let format = match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+                Some("bin") => TraceFormat::Binary,
+                _ => TraceFormat::Text,
+            };
+            (Box::new(BufWriter::new(File::create(path)?)), format)
+        };
+
+        let mut tracer = Self {
+            writer,
+            format,
+            armed: filter.start.is_none(),
+            filter,
+        };
+        if format == TraceFormat::Binary {
+            tracer.writer.write_all(BINARY_MAGIC)?;
+            tracer.writer.write_all(&[BINARY_VERSION])?;
+        }
+        Ok(tracer)
+    }
+
+    // Flush the rest of the trace, reporting errors a drop would ignore
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    // Record the instruction at pc given the registers before and after it ran
+    pub fn record(&mut self, cycle: u64, pc: usize, opcode: u16,
+        before: &Registers, after: &Registers, symbols: &Symbols) -> io::Result<()> {
+        if self.filter.start == Some(pc) {
+            self.armed = true;
+        }
+        let armed = self.armed;
+        // The stop instruction is still written
+        if self.filter.stop == Some(pc) {
+            self.armed = false;
+        }
+        if !armed || !self.filter.matches(pc, opcode) {
+            return Ok(());
+        }
+
+        let record = TraceRecord {
+            cycle,
+            pc,
+            opcode,
+            changes: before.changes(after),
+        };
+        match self.format {
+            TraceFormat::Text => writeln!(self.writer, "{}", record.to_text(symbols)),
+            TraceFormat::Binary => self.writer.write_all(&record.to_binary()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Registers after an instruction that touched every kind of register
+    fn changed() -> Registers {
+        let mut after = Registers { i: 0x3A0, sp: 2, dt: 0x10, st: 0x20, ..Registers::default() };
+        after.v[0] = 0x12;
+        after.v[0xF] = 1;
+        after
+    }
+
+    // Write records through a tracer to a temp file named for the test, then read them back
+    fn traced(name: &str, filter: TraceFilter, steps: &[(usize, u16)]) -> Vec<TraceRecord> {
+        let path = std::env::temp_dir().join(name);
+        let path = path.to_str().unwrap();
+        let mut tracer = Tracer::new(path, filter).unwrap();
+        let (before, after) = (Registers::default(), changed());
+        for (cycle, &(pc, opcode)) in steps.iter().enumerate() {
+            tracer.record(cycle as u64, pc, opcode, &before, &after, &Symbols::default()).unwrap();
+        }
+        tracer.finish().unwrap();
+        read_trace(path).unwrap()
+    }
+
+    fn pcs(records: &[TraceRecord]) -> Vec<usize> {
+        records.iter().map(|record| record.pc).collect()
+    }
+
+    #[test]
+    fn changes_include_every_register() {
+        let changes = Registers::default().changes(&changed());
+        assert_eq!(changes, [(0x0, 0x12), (0xF, 1), (REGISTER_I, 0x3A0), (REGISTER_SP, 2),
+            (REGISTER_DT, 0x10), (REGISTER_ST, 0x20)]);
+
+        let mut registers = Registers::default();
+        TraceRecord { cycle: 0, pc: 0x200, opcode: 0xF018, changes }.apply(&mut registers);
+        assert!(registers == changed());
+    }
+
+    #[test]
+    fn text_and_binary_traces_round_trip() {
+        let steps = [(0x200, 0x6012), (0x202, 0xF018), (0x204, 0x2300)];
+        let text = traced("chip_8_trace_round_trip.txt", TraceFilter::default(), &steps);
+        let binary = traced("chip_8_trace_round_trip.bin", TraceFilter::default(), &steps);
+        assert_eq!(text, binary);
+        assert_eq!(pcs(&text), [0x200, 0x202, 0x204]);
+        assert_eq!(text[1], TraceRecord {
+            cycle: 1,
+            pc: 0x202,
+            opcode: 0xF018,
+            changes: Registers::default().changes(&changed()),
+        });
+        let line = text[1].to_text(&Symbols::default());
+        assert!(line.ends_with("V0=12 VF=01 I=3A0 SP=02 DT=10 ST=20"), "{}", line);
+    }
+
+    #[test]
+    fn binary_traces_check_their_header_and_records() {
+        let path = std::env::temp_dir().join("chip_8_trace_versions.bin");
+        let path_text = path.to_str().unwrap();
+        let record = TraceRecord { cycle: 7, pc: 0x200, opcode: 0x6001, changes: vec![(0, 1)] };
+
+        // Version 1 traces predate ST but are otherwise the same
+        fs::write(&path, [&BINARY_MAGIC[..], &[1], &record.to_binary()].concat()).unwrap();
+        assert_eq!(read_trace(path_text).unwrap(), vec![record.clone()]);
+
+        fs::write(&path, [&BINARY_MAGIC[..], &[BINARY_VERSION + 1]].concat()).unwrap();
+        assert!(read_trace(path_text).is_err());
+
+        let mut unknown = record.to_binary();
+        unknown[13] = REGISTER_ST + 1;
+        fs::write(&path, [&BINARY_MAGIC[..], &[BINARY_VERSION], &unknown].concat()).unwrap();
+        assert!(read_trace(path_text).is_err());
+
+        let truncated = &record.to_binary()[..14];
+        fs::write(&path, [&BINARY_MAGIC[..], &[BINARY_VERSION], truncated].concat()).unwrap();
+        assert!(read_trace(path_text).is_err());
+    }
+
+    #[test]
+    fn filters_by_pc_range() {
+        let filter = TraceFilter { range: parse_range("202-204"), ..TraceFilter::default() };
+        let steps = [(0x200, 0x00E0), (0x202, 0x00E0), (0x204, 0x00E0), (0x206, 0x00E0)];
+        assert_eq!(pcs(&traced("chip_8_trace_range.txt", filter, &steps)), [0x202, 0x204]);
+    }
+
+    #[test]
+    fn filters_by_opcode_class() {
+        let classes = parse_classes("d, 2").unwrap();
+        let filter = TraceFilter { classes, ..TraceFilter::default() };
+        let steps = [(0x200, 0xD015), (0x202, 0x6001), (0x204, 0x2300), (0x300, 0x00EE)];
+        assert_eq!(pcs(&traced("chip_8_trace_classes.txt", filter, &steps)), [0x200, 0x204]);
+        assert_eq!(parse_classes("1,10"), None);
+    }
+
+    #[test]
+    fn start_and_stop_triggers_arm_the_trace() {
+        let (start, stop) = (Some(0x202), Some(0x204));
+        let filter = TraceFilter { start, stop, ..TraceFilter::default() };
+        let pc_loop = [0x200, 0x202, 0x204, 0x206, 0x200, 0x202, 0x204, 0x206];
+        let steps: Vec<(usize, u16)> = pc_loop.iter().map(|&pc| (pc, 0x00E0)).collect();
+        // The stop instruction is written, then nothing until start comes round again
+        let records = traced("chip_8_trace_triggers.txt", filter, &steps);
+        assert_eq!(pcs(&records), [0x202, 0x204, 0x202, 0x204]);
+
+        let filter = TraceFilter { stop: Some(0x202), ..TraceFilter::default() };
+        let records = traced("chip_8_trace_stop.txt", filter, &steps);
+        assert_eq!(pcs(&records), [0x200, 0x202]);
+    }
+}
@@ -0,0 +1,591 @@
+//Module Todo:
+// N/A
+
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, ClearType},
+};
+
+use std::{
+    io::{self, Write},
+    panic,
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    args::Args,
+    config::Settings,
+    debugger::Debugger,
+    disasm,
+    frame::Compositor,
+    gdb::GdbServer,
+    machine::Machine,
+    scheduler::Scheduler,
+    watcher::FileWatcher,
+    finish_trace,
+    load_machine,
+    resolve_breakpoints,
+    start_gdb,
+    write_reports,
+    CHIP8_WIDTH,
+    CHIP8_HEIGHT,
+};
+
+const KEY_HOLD: Duration = Duration::from_millis(150); // Terminals only report presses, so keys stay held this long
+const REFRESH_INTERVAL: Duration = Duration::from_millis(33); // Limit terminal output to about 30 frames a second
+const LIT_LEVEL: u8 = 0x80; // Composed pixel level drawn as lit
+
+// Pane layout in terminal cells
+const TOP_HEIGHT: u16 = 18;
+const REGISTERS_WIDTH: u16 = 20;
+const STACK_WIDTH: u16 = 12;
+const DISASM_WIDTH: u16 = 34;
+const MEMORY_WIDTH: u16 = 56;
+const BOTTOM_HEIGHT: u16 = 14;
+const BYTES_PER_ROW: usize = 16;
+const STATUS_ROW: u16 = TOP_HEIGHT + BOTTOM_HEIGHT;
+const HEIGHT: u16 = STATUS_ROW + 2;
+const HELP: &str = "P pause  . frame  F7 step  F2 break  F8 reports  : command (b d s c m q)  Esc quit";
+
+#[derive(Clone, Copy, PartialEq)]
+struct Cell {
+    ch: char,
+    highlight: bool, // Drawn in reverse video
+}
+
+const BLANK: Cell = Cell { ch: ' ', highlight: false };
+
+// Character grid the panes are drawn into before changed rows go to the terminal
+struct Grid {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+    presented: Vec<Cell>, // Cells last written to the terminal
+}
+
+impl Grid {
+    fn new(width: u16, height: u16) -> Self {
+        let size = width as usize * height as usize;
+        Self {
+            width,
+            height,
+            cells: vec![BLANK; size],
+            presented: Vec::new(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.cells.fill(BLANK);
+    }
+
+    fn set(&mut self, x: u16, y: u16, ch: char, highlight: bool) {
+        if x < self.width && y < self.height {
+            self.cells[y as usize * self.width as usize + x as usize] = Cell { ch, highlight };
+        }
+    }
+
+    fn text(&mut self, x: u16, y: u16, text: &str, highlight: bool) {
+        for (column, ch) in text.chars().enumerate() {
+            self.set(x + column as u16, y, ch, highlight);
+        }
+    }
+
+    // Draw a box border with a title in the top edge
+    fn pane(&mut self, x: u16, y: u16, width: u16, height: u16, title: &str) {
+        for column in x + 1..x + width - 1 {
+            self.set(column, y, '─', false);
+            self.set(column, y + height - 1, '─', false);
+        }
+        for row in y + 1..y + height - 1 {
+            self.set(x, row, '│', false);
+            self.set(x + width - 1, row, '│', false);
+        }
+        self.set(x, y, '┌', false);
+        self.set(x + width - 1, y, '┐', false);
+        self.set(x, y + height - 1, '└', false);
+        self.set(x + width - 1, y + height - 1, '┘', false);
+        self.text(x + 2, y, &format!(" {} ", title), false);
+    }
+
+    // Write rows that changed since the last present
+    fn present(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let width = self.width as usize;
+        let full_redraw = self.presented.len() != self.cells.len();
+        for (y, row) in self.cells.chunks(width).enumerate() {
+            if !full_redraw && row == &self.presented[y * width..(y + 1) * width] {
+                continue;
+            }
+
+            queue!(out, cursor::MoveTo(0, y as u16))?;
+            let mut highlight = false;
+            for cell in row {
+                if cell.highlight != highlight {
+                    highlight = cell.highlight;
+                    let attribute = if highlight { Attribute::Reverse } else { Attribute::Reset };
+                    queue!(out, SetAttribute(attribute))?;
+                }
+                queue!(out, Print(cell.ch))?;
+            }
+            queue!(out, SetAttribute(Attribute::Reset))?;
+        }
+        self.presented = self.cells.clone();
+        out.flush()
+    }
+
+    // Forget what the terminal shows so the next present redraws everything
+    fn invalidate(&mut self) {
+        self.presented.clear();
+    }
+}
+
+// Put the terminal back to normal, also used when the emulator panics
+fn restore_terminal() {
+    let mut out = io::stdout();
+    let _ = crossterm::execute!(out, SetAttribute(Attribute::Reset), cursor::Show,
+        terminal::LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+}
+
+// Terminal debugger frontend, driving the cpu without sdl
+struct Tui {
+    braille: bool, // 2x4 pixels per cell instead of 1x2 half blocks
+    compositor: Compositor,
+    grid: Grid,
+    held_keys: Vec<(String, Instant)>, // Host key names and when they were last pressed
+    paused: bool,
+    advance_frame: bool,
+    command: Option<String>, // Command line text while typing after :
+    status: String,
+    memory_addr: Option<usize>, // Memory pane start, following i when unset
+    gdb: Option<GdbServer>,
+    watcher: Option<FileWatcher>, // Reloads the rom when it changes on disk
+    quit: bool,
+}
+
+pub fn run(args: &Args, mut settings: Settings) -> ExitCode {
+    let mut machine = load_machine(args, &mut settings);
+    let mut debugger = Debugger::new(&resolve_breakpoints(args, &machine));
+    let mut tui = Tui {
+        braille: args.braille,
+        compositor: Compositor::new(settings.render_mode, settings.palette),
+        grid: Grid::new(width(), HEIGHT),
+        held_keys: Vec::new(),
+        paused: false,
+        advance_frame: false,
+        command: None,
+        status: String::new(),
+        memory_addr: None,
+        gdb: args.gdb_port.and_then(start_gdb),
+        watcher: args.watch.then(|| FileWatcher::new(&settings.rom_path)),
+        quit: false,
+    };
+
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        default_hook(info);
+    }));
+
+    let result = terminal::enable_raw_mode()
+        .and_then(|()| crossterm::execute!(io::stdout(), terminal::EnterAlternateScreen,
+            cursor::Hide, terminal::Clear(ClearType::All)))
+        .and_then(|()| tui.run_loop(&mut machine, &mut debugger, settings.clock_hz));
+    restore_terminal();
+
+    if machine.profiler.is_some() || machine.coverage.is_some() {
+        eprintln!("{}", write_reports(&machine));
+    }
+    let traced = finish_trace(&mut machine);
+    if let Err(e) = result {
+        eprintln!("Terminal error: {}", e);
+        return ExitCode::FAILURE;
+    }
+    if traced { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
+
+// Total width of the pane layout
+fn width() -> u16 {
+    let top = CHIP8_WIDTH as u16 + 2 + REGISTERS_WIDTH + STACK_WIDTH;
+    top.max(DISASM_WIDTH + MEMORY_WIDTH)
+}
+
+impl Tui {
+    fn run_loop(&mut self, machine: &mut Machine, debugger: &mut Debugger, clock_hz: u32)
+        -> io::Result<()> {
+        let mut out = io::stdout();
+        let mut scheduler = Scheduler::new(clock_hz);
+        let mut last_present = Instant::now() - REFRESH_INTERVAL;
+        let mut dirty = true; // Present even though no frame ran
+
+        while !self.quit {
+            while event::poll(Duration::ZERO)? {
+                match event::read()? {
+                    Event::Key(key) if key.kind != KeyEventKind::Release => {
+                        let was_paused = self.paused;
+                        self.handle_key(key, machine, debugger);
+                        if was_paused && !self.paused {
+                            scheduler.resync();
+                        }
+                        dirty = true;
+                    },
+                    Event::Resize(..) => {
+                        queue!(out, terminal::Clear(ClearType::All))?;
+                        self.grid.invalidate();
+                        dirty = true;
+                    },
+                    _ => {},
+                }
+            }
+            self.update_keypad(machine);
+            dirty |= self.poll_gdb(machine, debugger);
+            if self.watcher.as_mut().is_some_and(FileWatcher::changed) {
+                self.status = match machine.reload_rom() {
+                    Ok(()) => format!("Reloaded {}", machine.cartridge.path()),
+                    Err(e) => format!("Failed to reload {}: {}", machine.cartridge.path(), e),
+                };
+                self.compositor.refresh(&machine.cpu);
+                dirty = true;
+            }
+            let gdb_halted = self.gdb.as_ref().is_some_and(GdbServer::halted);
+
+            // Run every emulation frame due, the same way as the sdl frontend
+            let frames = if gdb_halted {
+                0
+            } else if !self.paused {
+                scheduler.frames_due()
+            } else if self.advance_frame {
+                self.advance_frame = false;
+                1
+            } else {
+                0
+            };
+            for _frame in 0..frames {
+                let ticks = scheduler.ticks_for_frame();
+                if let Some(reason) = debugger.run_frame(machine, ticks) {
+                    // Gdb resumes the game itself, otherwise pause for the user
+                    match &mut self.gdb {
+                        Some(server) if server.attached() => server.report_stop(machine),
+                        _ => self.paused = true,
+                    }
+                    self.status = reason;
+                    break;
+                }
+                self.compositor.compose(&machine.cpu);
+            }
+            if let Some(report) = machine.take_smc_reports().pop() {
+                self.status = report;
+            }
+            if frames > 0 {
+                self.compositor.refresh(&machine.cpu);
+                dirty = true;
+            }
+
+            if dirty && last_present.elapsed() >= REFRESH_INTERVAL {
+                self.draw(machine, debugger)?;
+                self.grid.present(&mut out)?;
+                last_present = Instant::now();
+                dirty = false;
+            } else if self.paused || gdb_halted {
+                scheduler.idle();
+            } else {
+                scheduler.sleep_until_next_frame();
+            }
+        }
+        Ok(())
+    }
+
+    // Let an attached gdb client inspect and control the machine,
+    // returning whether the panes need redrawing
+    fn poll_gdb(&mut self, machine: &mut Machine, debugger: &mut Debugger) -> bool {
+        let Some(server) = &mut self.gdb else {
+            return false;
+        };
+
+        let was_attached = server.attached();
+        let mut handled = match server.poll(machine, debugger) {
+            Ok(handled) => handled,
+            Err(e) => {
+                self.status = format!("Gdb connection lost: {}", e);
+                true
+            },
+        };
+        if server.attached() != was_attached {
+            self.status = String::from(if server.attached() { "Gdb attached" } else { "Gdb detached" });
+            handled = true;
+        }
+        if handled {
+            self.compositor.refresh(&machine.cpu);
+        }
+        handled
+    }
+
+    fn handle_key(&mut self, key: KeyEvent, machine: &mut Machine, debugger: &mut Debugger) {
+        if let Some(command) = &mut self.command {
+            match key.code {
+                KeyCode::Char(c) => command.push(c),
+                KeyCode::Backspace => {
+                    command.pop();
+                },
+                KeyCode::Enter => {
+                    let command = self.command.take().unwrap_or_default();
+                    self.run_command(&command, machine, debugger);
+                },
+                KeyCode::Esc => self.command = None,
+                _ => {},
+            }
+            return;
+        }
+
+        match key.code {
+            KeyCode::Esc => self.quit = true,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+            KeyCode::Char(':') => self.command = Some(String::new()),
+            KeyCode::Char('p') | KeyCode::Char('P') => {
+                self.paused = !self.paused;
+                self.status = String::from(if self.paused { "Paused" } else { "Resumed" });
+            },
+            KeyCode::Char('.') => self.advance_frame = self.paused,
+            KeyCode::F(2) => {
+                let pc = machine.cpu.pc();
+                self.toggle_breakpoint(debugger, pc);
+            },
+            KeyCode::F(7) => self.step(machine, debugger, 1),
+            KeyCode::F(8) => self.status = write_reports(machine),
+            KeyCode::F(5) => {
+                machine.soft_reset();
+                self.status = String::from("Soft reset");
+            },
+            KeyCode::F(6) => {
+                machine.hard_reset();
+                self.status = String::from("Hard reset");
+            },
+            code => {
+                if let Some(name) = key_name(code) {
+                    self.held_keys.retain(|(held, _)| *held != name);
+                    self.held_keys.push((name, Instant::now()));
+                }
+            },
+        }
+    }
+
+    // Commands typed after :, with addresses as labels or hex
+    fn run_command(&mut self, command: &str, machine: &mut Machine, debugger: &mut Debugger) {
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let arg = words.next();
+        let addr = arg.and_then(|arg| machine.symbols.resolve(arg));
+
+        match (name, arg) {
+            ("b" | "break", Some(_)) => match addr {
+                Some(addr) => {
+                    debugger.breakpoints.insert(addr);
+                    self.status = format!("Breakpoint set at {:03X}", addr);
+                },
+                None => self.status = format!("Invalid address {}", arg.unwrap_or("")),
+            },
+            ("d" | "delete", None) => {
+                debugger.breakpoints.clear();
+                self.status = String::from("Breakpoints cleared");
+            },
+            ("d" | "delete", Some(_)) => match addr {
+                Some(addr) if debugger.breakpoints.remove(&addr) => {
+                    self.status = format!("Breakpoint cleared at {:03X}", addr);
+                },
+                _ => self.status = format!("No breakpoint at {}", arg.unwrap_or("")),
+            },
+            ("s" | "step", _) => match arg.map(str::parse::<u32>) {
+                None => self.step(machine, debugger, 1),
+                Some(Ok(count)) => self.step(machine, debugger, count),
+                Some(Err(_)) => self.status = format!("Invalid step count {}", arg.unwrap_or("")),
+            },
+            ("c" | "continue", None) => {
+                self.paused = false;
+                self.status = String::from("Resumed");
+            },
+            ("m" | "mem", None) => self.memory_addr = None,
+            ("m" | "mem", Some(_)) => match addr {
+                Some(addr) => self.memory_addr = Some(addr),
+                None => self.status = format!("Invalid address {}", arg.unwrap_or("")),
+            },
+            ("q" | "quit", None) => self.quit = true,
+            ("", None) => {},
+            _ => self.status = format!("Unknown command {}", command),
+        }
+    }
+
+    // Pause and run count instructions
+    fn step(&mut self, machine: &mut Machine, debugger: &mut Debugger, count: u32) {
+        self.paused = true;
+        for _step in 0..count {
+            debugger.step(machine);
+        }
+        self.compositor.refresh(&machine.cpu);
+        self.status = format!("Stepped to {:03X}", machine.cpu.pc());
+    }
+
+    fn toggle_breakpoint(&mut self, debugger: &mut Debugger, addr: usize) {
+        let text = if debugger.toggle_breakpoint(addr) { "Breakpoint set at" } else { "Breakpoint cleared at" };
+        self.status = format!("{} {:03X}", text, addr);
+    }
+
+    // Feed recently pressed keys to the keypad as held keys
+    fn update_keypad(&mut self, machine: &mut Machine) {
+        self.held_keys.retain(|(_, pressed)| pressed.elapsed() < KEY_HOLD);
+        let keypad = &mut machine.keypad;
+        keypad.reset_keypad();
+        keypad.update_keys(self.held_keys.iter().map(|(name, _)| name.clone()).collect());
+        keypad.update_keypad();
+    }
+
+    fn draw(&mut self, machine: &Machine, debugger: &Debugger) -> io::Result<()> {
+        let (columns, rows) = terminal::size()?;
+        self.grid.clear();
+        if columns < self.grid.width || rows < self.grid.height {
+            let text = format!("Terminal too small, need {}x{}", self.grid.width, self.grid.height);
+            self.grid.text(0, 0, &text, false);
+            return Ok(());
+        }
+
+        self.draw_screen();
+        self.draw_registers(machine);
+        self.draw_stack(machine);
+        self.draw_disasm(machine, debugger);
+        self.draw_memory(machine);
+
+        let state = if self.paused { "PAUSED" } else { "RUNNING" };
+        let status = format!(" {:<8} {}", state, self.status);
+        self.grid.text(0, STATUS_ROW, &status, self.paused);
+        match &self.command {
+            Some(command) => self.grid.text(0, STATUS_ROW + 1, &format!(":{}_", command), false),
+            None => self.grid.text(0, STATUS_ROW + 1, HELP, false),
+        }
+        Ok(())
+    }
+
+    fn lit(&self, x: usize, y: usize) -> bool {
+        x < CHIP8_WIDTH as usize && y < CHIP8_HEIGHT as usize
+            && self.compositor.level(x, y) >= LIT_LEVEL
+    }
+
+    fn draw_screen(&mut self) {
+        let (cell_width, cell_height) = if self.braille { (2, 4) } else { (1, 2) };
+        let columns = CHIP8_WIDTH as usize / cell_width;
+        let rows = CHIP8_HEIGHT as usize / cell_height;
+        self.grid.pane(0, 0, columns as u16 + 2, TOP_HEIGHT, "SCREEN");
+
+        for row in 0..rows {
+            for column in 0..columns {
+                let x = column * cell_width;
+                let y = row * cell_height;
+                let ch = if self.braille {
+                    // Braille dot bits, left column top to bottom then right column
+                    const DOTS: [(usize, usize, u32); 8] = [
+                        (0, 0, 0x01), (0, 1, 0x02), (0, 2, 0x04), (1, 0, 0x08),
+                        (1, 1, 0x10), (1, 2, 0x20), (0, 3, 0x40), (1, 3, 0x80),
+                    ];
+                    let bits = DOTS.iter()
+                        .filter(|(dx, dy, _)| self.lit(x + dx, y + dy))
+                        .fold(0, |bits, (_, _, bit)| bits | bit);
+                    char::from_u32(0x2800 + bits).unwrap_or(' ')
+                } else {
+                    match (self.lit(x, y), self.lit(x, y + 1)) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    }
+                };
+                self.grid.set(column as u16 + 1, row as u16 + 1, ch, false);
+            }
+        }
+    }
+
+    fn draw_registers(&mut self, machine: &Machine) {
+        let x = CHIP8_WIDTH as u16 + 2;
+        let cpu = &machine.cpu;
+        let v = cpu.v();
+        self.grid.pane(x, 0, REGISTERS_WIDTH, TOP_HEIGHT, "REGS");
+        for row in 0..8 {
+            let line = format!("V{:X} {:02X}  V{:X} {:02X}", row, v[row], row + 8, v[row + 8]);
+            self.grid.text(x + 2, row as u16 + 1, &line, false);
+        }
+        self.grid.text(x + 2, 10, &format!("PC {:03X}  I {:03X}", cpu.pc(), cpu.i()), false);
+        self.grid.text(x + 2, 11, &format!("SP {:X}    DT {:02X}", cpu.sp(), cpu.dt()), false);
+    }
+
+    // Return addresses with the innermost call at the top
+    fn draw_stack(&mut self, machine: &Machine) {
+        let x = CHIP8_WIDTH as u16 + 2 + REGISTERS_WIDTH;
+        self.grid.pane(x, 0, STACK_WIDTH, TOP_HEIGHT, "STACK");
+        let stack = machine.cpu.call_stack();
+        if stack.is_empty() {
+            self.grid.text(x + 2, 1, "-", false);
+        }
+        for (row, (depth, addr)) in stack.iter().enumerate().rev().enumerate() {
+            self.grid.text(x + 2, row as u16 + 1, &format!("{:X} {:03X}", depth, addr), false);
+        }
+    }
+
+    // Instructions around pc, marked with > for pc and * for breakpoints
+    fn draw_disasm(&mut self, machine: &Machine, debugger: &Debugger) {
+        self.grid.pane(0, TOP_HEIGHT, DISASM_WIDTH, BOTTOM_HEIGHT, "DISASSEMBLY");
+        let rows = (BOTTOM_HEIGHT - 2) as usize;
+        let pc = machine.cpu.pc();
+        let mem = &machine.ram.mem;
+        let start = pc.saturating_sub(rows / 2 * 2).min(mem.len() - rows * 2);
+
+        for row in 0..rows {
+            let addr = start + row * 2;
+            let opcode = disasm::opcode_at(mem, addr);
+            let line = format!("{}{}{:03X} {:04X} {}",
+                if addr == pc { '>' } else { ' ' },
+                if debugger.breakpoints.contains(&addr) { '*' } else { ' ' },
+                addr, opcode, disasm::disassemble_with(opcode, &machine.symbols));
+            let line: String = line.chars().take(DISASM_WIDTH as usize - 2).collect();
+            self.grid.text(1, TOP_HEIGHT + 1 + row as u16, &line, addr == pc);
+        }
+    }
+
+    // Hex rows from the chosen address or i, recently written bytes highlighted
+    fn draw_memory(&mut self, machine: &Machine) {
+        let x = DISASM_WIDTH;
+        let ram = &machine.ram;
+        let rows = (BOTTOM_HEIGHT - 2) as usize;
+        let base = self.memory_addr.unwrap_or(machine.cpu.i()) / BYTES_PER_ROW * BYTES_PER_ROW;
+        let base = base.min(ram.mem.len() - rows * BYTES_PER_ROW);
+        let title = match self.memory_addr {
+            Some(_) => String::from("MEMORY"),
+            None => format!("MEMORY AT I {:03X}", machine.cpu.i()),
+        };
+        self.grid.pane(x, TOP_HEIGHT, MEMORY_WIDTH, BOTTOM_HEIGHT, &title);
+
+        for row in 0..rows {
+            let y = TOP_HEIGHT + 1 + row as u16;
+            let row_addr = base + row * BYTES_PER_ROW;
+            self.grid.text(x + 2, y, &format!("{:03X}", row_addr), false);
+            for column in 0..BYTES_PER_ROW {
+                let addr = row_addr + column;
+                let written = ram.recent_writes[addr] > 0;
+                let column_x = x + 6 + column as u16 * 3;
+                self.grid.text(column_x, y, &format!("{:02X}", ram.mem[addr]), written);
+            }
+        }
+    }
+}
+
+// Terminal key as the sdl key name used by the keymap
+fn key_name(code: KeyCode) -> Option<String> {
+    match code {
+        KeyCode::Char(' ') => Some(String::from("Space")),
+        KeyCode::Char(c) => Some(c.to_ascii_uppercase().to_string()),
+        KeyCode::Up => Some(String::from("Up")),
+        KeyCode::Down => Some(String::from("Down")),
+        KeyCode::Left => Some(String::from("Left")),
+        KeyCode::Right => Some(String::from("Right")),
+        _ => None,
+    }
+}
@@ -0,0 +1,51 @@
+//Module Todo:
+// N/A
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Polls a file's modification time to detect changes without platform services
+pub struct FileWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_poll: Instant,
+}
+
+impl FileWatcher {
+    pub fn new(path: &str) -> Self {
+        let path = PathBuf::from(path);
+        let modified = Self::modified_time(&path);
+
+        Self {
+            path,
+            modified,
+            last_poll: Instant::now(),
+        }
+    }
+
+    fn modified_time(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|meta| meta.modified()).ok()
+    }
+
+    // True once per change, checked at most every POLL_INTERVAL
+    // A missing file is ignored so editors that replace the file don't trigger early
+    pub fn changed(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+
+        match Self::modified_time(&self.path) {
+            Some(modified) if Some(modified) != self.modified => {
+                self.modified = Some(modified);
+                true
+            },
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,312 @@
+//Module Todo:
+// N/A
+
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::PixelFormatEnum;
+
+use std::process::{self, ExitCode};
+
+use crate::{
+    args::Args,
+    config::Settings,
+    debugger::Debugger,
+    display::Display,
+    gdb::GdbServer,
+    machine::Machine,
+    osd::Osd,
+    recorder,
+    scheduler::{Scheduler, Speed},
+    screenshot,
+    watcher::FileWatcher,
+    finish_trace,
+    load_machine,
+    resolve_breakpoints,
+    start_gdb,
+    start_recording,
+    stop_recording,
+    write_reports,
+    CHIP8_WIDTH,
+    CHIP8_HEIGHT,
+};
+
+const SLOW_MOTION_SPEEDS: [f64; 3] = [1.0, 0.5, 0.25];
+
+// Report a status message on stderr and the osd
+fn notify(osd: &mut Osd, text: &str) {
+    eprintln!("{}", text);
+    osd.message(text);
+}
+
+fn toggle_breakpoint(debugger: &mut Debugger, osd: &mut Osd, addr: usize) {
+    let text = if debugger.toggle_breakpoint(addr) { "Breakpoint set at" } else { "Breakpoint cleared at" };
+    notify(osd, &format!("{} {:03X}", text, addr));
+}
+
+fn reload_rom(machine: &mut Machine, osd: &mut Osd) {
+    match machine.reload_rom() {
+        Ok(()) => notify(osd, &format!("Reloaded {}", machine.cartridge.path())),
+        Err(e) => notify(osd, &format!("Failed to reload {}: {}", machine.cartridge.path(), e)),
+    }
+}
+
+// Run with an sdl window until it is closed
+pub fn run(args: &Args, mut settings: Settings) -> ExitCode {
+    let sdl_context = sdl2::init()
+        .expect("Failed to initialize the sdl library");
+    let mut events = sdl_context
+        .event_pump().expect("Failed to obtain event pump");
+
+    let mut machine = load_machine(args, &mut settings);
+    let unknown_key = machine.keypad.mapped_keys()
+        .find(|name| Keycode::from_name(name).is_none());
+    if let Some(name) = unknown_key {
+        eprintln!("Unknown key name {} in keymap", name);
+        process::exit(1);
+    }
+    let mut display = Display::new(&sdl_context, &settings);
+    display.osd.message(&format!("{} ({})",
+        screenshot::rom_name(&settings.rom_path), machine.cartridge.platform.name()));
+    let mut debugger = Debugger::new(&resolve_breakpoints(args, &machine));
+    let mut gdb = args.gdb_port.and_then(start_gdb);
+
+    let texture_creator = display.canvas.texture_creator();
+    let mut texture = texture_creator
+        .create_texture_streaming(PixelFormatEnum::RGBA8888, CHIP8_WIDTH, CHIP8_HEIGHT)
+        .expect("Failed to create texture");
+
+    let mut frame_count: u64 = 0;
+    let mut recorder = args.record_path.as_deref().and_then(start_recording);
+    let mut scheduler = Scheduler::new(settings.clock_hz);
+    let mut watcher = args.watch.then(|| FileWatcher::new(&settings.rom_path));
+
+    // Speed controls
+    let mut paused = false;
+    let mut advance_frame = false;
+    let mut fast_forward = false;
+    let mut slow_motion_index = 0;
+    let mut redraw = false; // Present while paused after stepping or debugger changes
+
+    'running: loop {
+        // Check for quit requests
+        for event in events.poll_iter() {
+            match event {
+                Event::Quit {..} |
+                Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                    break 'running
+                },
+                // F12 saves a native screenshot, Shift+F12 saves at display scale
+                Event::KeyDown { keycode: Some(Keycode::F12), keymod, .. } => {
+                    let native = !keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                    let saved = display.save_screenshot(
+                        &settings.screenshot_dir, &settings.rom_path, frame_count, native,
+                    );
+                    match saved {
+                        Ok(path) => notify(&mut display.osd,
+                            &format!("Saved screenshot to {}", path.display())),
+                        Err(e) => notify(&mut display.osd,
+                            &format!("Failed to save screenshot: {}", e)),
+                    }
+                },
+                // F9 toggles gif recording, F10 toggles y4m recording
+                Event::KeyDown { keycode: Some(key @ (Keycode::F9 | Keycode::F10)), .. } => {
+                    recorder = match recorder.take() {
+                        Some(rec) => {
+                            stop_recording(rec);
+                            display.osd.message("Recording stopped");
+                            None
+                        },
+                        None => {
+                            let extension = if key == Keycode::F9 { "gif" } else { "y4m" };
+                            let path = recorder::recording_path(
+                                &settings.recording_dir, &settings.rom_path, frame_count, extension,
+                            );
+                            let rec = start_recording(&path);
+                            if rec.is_some() {
+                                display.osd.message("Recording");
+                            }
+                            rec
+                        },
+                    };
+                },
+                // P pauses and resumes, period advances one frame while paused
+                Event::KeyDown { keycode: Some(Keycode::P), .. } => {
+                    paused = !paused;
+                    display.osd.paused = paused;
+                    redraw = true;
+                    scheduler.resync();
+                    eprintln!("{}", if paused { "Paused" } else { "Resumed" });
+                },
+                Event::KeyDown { keycode: Some(Keycode::Period), .. } => {
+                    advance_frame = paused;
+                },
+                // Hold tab to fast forward, M cycles slow motion speeds
+                Event::KeyDown { keycode: Some(Keycode::Tab), repeat: false, .. } => {
+                    fast_forward = true;
+                },
+                Event::KeyUp { keycode: Some(Keycode::Tab), .. } => {
+                    fast_forward = false;
+                },
+                Event::KeyDown { keycode: Some(Keycode::M), .. } => {
+                    slow_motion_index = (slow_motion_index + 1) % SLOW_MOTION_SPEEDS.len();
+                    notify(&mut display.osd,
+                        &format!("Speed {}×", SLOW_MOTION_SPEEDS[slow_motion_index]));
+                },
+                // F2 toggles a breakpoint at pc, F7 pauses or steps one instruction
+                Event::KeyDown { keycode: Some(Keycode::F2), .. } => {
+                    let pc = machine.cpu.pc();
+                    toggle_breakpoint(&mut debugger, &mut display.osd, pc);
+                    redraw = true;
+                },
+                Event::KeyDown { keycode: Some(Keycode::F7), .. } => {
+                    if paused {
+                        debugger.step(&mut machine);
+                    } else {
+                        paused = true;
+                        display.osd.paused = true;
+                    }
+                    redraw = true;
+                },
+                // Clicking a disassembly row toggles its breakpoint, the wheel scrolls memory
+                Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => {
+                    let addr = display.panels.as_ref()
+                        .and_then(|panels| panels.disasm_address_at(x, y));
+                    if let Some(addr) = addr {
+                        toggle_breakpoint(&mut debugger, &mut display.osd, addr);
+                        redraw = true;
+                    }
+                },
+                Event::MouseWheel { y, .. } => {
+                    if let Some(panels) = &mut display.panels {
+                        panels.scroll_memory(-y);
+                        redraw = true;
+                    }
+                },
+                // F3 toggles the fps and instructions per second counter
+                Event::KeyDown { keycode: Some(Keycode::F3), .. } => {
+                    display.osd.show_stats = !display.osd.show_stats;
+                },
+                // F8 writes the profiler and coverage reports so far
+                Event::KeyDown { keycode: Some(Keycode::F8), .. } => {
+                    notify(&mut display.osd, &write_reports(&machine));
+                },
+                // F5 soft resets, F6 hard resets, Shift+F6 also reloads the rom from disk
+                Event::KeyDown { keycode: Some(Keycode::F5), .. } => {
+                    machine.soft_reset();
+                    notify(&mut display.osd, "Soft reset");
+                },
+                Event::KeyDown { keycode: Some(Keycode::F6), keymod, .. } => {
+                    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        reload_rom(&mut machine, &mut display.osd);
+                    } else {
+                        machine.hard_reset();
+                        notify(&mut display.osd, "Hard reset");
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        // Hot reload the rom when it changes on disk
+        if let Some(watcher) = &mut watcher {
+            if watcher.changed() {
+                reload_rom(&mut machine, &mut display.osd);
+            }
+        }
+
+        scheduler.set_speed(if fast_forward {
+            settings.fast_forward
+        } else {
+            Speed::Scaled(SLOW_MOTION_SPEEDS[slow_motion_index])
+        });
+
+        // Let an attached gdb client inspect and control the machine
+        if let Some(server) = &mut gdb {
+            let was_attached = server.attached();
+            match server.poll(&mut machine, &mut debugger) {
+                Ok(handled) => redraw |= handled,
+                Err(e) => notify(&mut display.osd, &format!("Gdb connection lost: {}", e)),
+            }
+            if server.attached() != was_attached {
+                let text = if server.attached() { "Gdb attached" } else { "Gdb detached" };
+                notify(&mut display.osd, text);
+            }
+        }
+        let gdb_halted = gdb.as_ref().is_some_and(GdbServer::halted);
+
+        // Update keypad with newly pressed keys
+        machine.keypad.reset_keypad();
+        let pressed_keys: Vec<String> = events
+            .keyboard_state()
+            .pressed_scancodes()
+            .filter_map(Keycode::from_scancode)
+            .map(|key| key.name())
+            .collect();
+        machine.keypad.update_keys(pressed_keys);
+        machine.keypad.update_keypad();
+        
+        // Run every emulation frame due since the last present
+        let frames = if gdb_halted {
+            0
+        } else if !paused {
+            scheduler.frames_due()
+        } else if advance_frame {
+            advance_frame = false;
+            1
+        } else {
+            0
+        };
+        let mut ticks_run = 0;
+        for _frame in 0..frames {
+            let ticks = scheduler.ticks_for_frame();
+            if let Some(reason) = debugger.run_frame(&mut machine, ticks) {
+                // Gdb resumes the game itself, otherwise pause for the user
+                match &mut gdb {
+                    Some(server) if server.attached() => server.report_stop(&machine),
+                    _ => {
+                        paused = true;
+                        display.osd.paused = true;
+                    },
+                }
+                notify(&mut display.osd, &reason);
+                break;
+            }
+            ticks_run += ticks as u64;
+            display.compositor.compose(&machine.cpu);
+
+            // Record every emulated frame so clips keep their speed through catch-up
+            if let Some(rec) = &mut recorder {
+                if let Err(e) = rec.add_frame(display.frame()) {
+                    eprintln!("Failed to record frame: {}", e);
+                    recorder = None;
+                }
+            }
+        }
+        for report in machine.take_smc_reports() {
+            notify(&mut display.osd, &report);
+        }
+        display.osd.count(frames as u64, ticks_run);
+        // Without vsync, wait for the next frame instead of presenting a repeat
+        if frames == 0 && !settings.vsync && !redraw {
+            if paused || gdb_halted {
+                scheduler.idle();
+            } else {
+                scheduler.sleep_until_next_frame();
+            }
+            continue;
+        }
+
+        display.draw(&machine, &debugger, &mut texture);
+        redraw = false;
+        frame_count += frames as u64;
+    }
+
+    if let Some(rec) = recorder {
+        stop_recording(rec);
+    }
+    if machine.profiler.is_some() || machine.coverage.is_some() {
+        eprintln!("{}", write_reports(&machine));
+    }
+    if finish_trace(&mut machine) { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}